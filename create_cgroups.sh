@@ -1,15 +1,29 @@
 #!/bin/sh
 
 CONTROL_GROUPS="sp"
-SUBS="blkio cpuacct memory pids freezer"
+SUBS="blkio cpuacct cpu memory pids freezer"
+CONTROLLERS="+memory +pids +io +cpu"
 
 sudo -v
 
+if [ -f /sys/fs/cgroup/cgroup.controllers ]; then
+	# Unified hierarchy (cgroup v2).
+	echo "${CONTROLLERS}" | sudo tee /sys/fs/cgroup/cgroup.subtree_control > /dev/null
+	for cgroup in ${CONTROL_GROUPS}
+	do
+		sudo mkdir /sys/fs/cgroup/$cgroup/
+		echo "${CONTROLLERS}" | sudo tee /sys/fs/cgroup/$cgroup/cgroup.subtree_control > /dev/null
+		sudo chown -R ${USER} /sys/fs/cgroup/$cgroup/
+	done
+	exit 0
+fi
+
 for cgroup in ${CONTROL_GROUPS}
 do
 	for sub in ${SUBS}
 	do	
-		sudo mkdir /sys/fs/cgroup/$sub/$cgroup/
+		# cpu and cpuacct are often mounted together.
+		sudo mkdir -p /sys/fs/cgroup/$sub/$cgroup/
 		sudo chown -R ${USER} /sys/fs/cgroup/$sub/$cgroup/
 	done
 done
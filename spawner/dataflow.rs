@@ -1,24 +1,35 @@
-use crate::pipe::{ReadPipe, WritePipe};
+use crate::pipe::{self, ReadPipe, WritePipe};
 use crate::{Error, Result};
 
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 
+/// The amount of data pending for a nonblocking pipe at which the sources
+/// connected to it aren't read until it drains.
+const MAX_PENDING_DATA: usize = 64 * 1024;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct SourceId(usize);
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct DestinationId(usize);
 
+/// Handles the data of a source instead of sending it to every connection as is.
 pub trait SourceReader: Send {
-    fn read(&mut self, src: &mut ReadPipe, connections: &mut [Connection]) -> Result<()>;
+    /// Handles the next chunk of data read from the source.
+    fn read(&mut self, data: &[u8], connections: &mut [Connection]) -> Result<()>;
+
+    /// Called once the source is closed or `read` has failed with the `result` of reading,
+    /// returns the result of the source.
+    fn finish(&mut self, result: Result<()>, connections: &mut [Connection]) -> Result<()>;
 }
 
 #[derive(Debug)]
 enum ConnectionKind {
-    Pipe(WritePipe),
+    /// A pipe and, once it's nonblocking, the data that doesn't fit into it yet.
+    Pipe(WritePipe, Option<Vec<u8>>),
     File(BufWriter<WritePipe>),
 }
 
@@ -45,6 +56,13 @@ pub struct Source {
     reader: Option<Box<dyn SourceReader>>,
 }
 
+/// A source that is being read.
+struct ActiveSource {
+    connections: Vec<Connection>,
+    reader: Option<Box<dyn SourceReader>>,
+    result: Result<()>,
+}
+
 #[derive(Default)]
 pub struct Graph {
     srcs: HashMap<SourceId, Source>,
@@ -53,13 +71,16 @@ pub struct Graph {
     dst_id_generator: usize,
 }
 
+type SourceResults = HashMap<SourceId, Result<ReadPipe>>;
+
 pub struct TransmitterResults {
-    pub sources: HashMap<SourceId, Result<ReadPipe>>,
+    pub sources: SourceResults,
     _file_dsts: Vec<Destination>,
 }
 
 pub struct Transmitter {
     readers: Vec<(SourceId, JoinHandle<Result<ReadPipe>>)>,
+    pump: Option<(Vec<SourceId>, JoinHandle<SourceResults>)>,
     file_dsts: Vec<Destination>,
 }
 
@@ -81,7 +102,11 @@ impl Connection {
     pub fn send(&mut self, data: &[u8]) {
         let result = match self.state {
             ConnectionState::Alive(ref mut kind) => match *kind.lock().unwrap() {
-                ConnectionKind::Pipe(ref mut p) => p.write_all(data),
+                ConnectionKind::Pipe(ref mut p, None) => p.write_all(data),
+                ConnectionKind::Pipe(ref mut p, Some(ref mut pending)) => {
+                    pending.extend_from_slice(data);
+                    write_pending(p, pending)
+                }
                 ConnectionKind::File(ref mut f) => f.write_all(data),
             },
             ConnectionState::Dead => return,
@@ -94,6 +119,16 @@ impl Connection {
     fn is_dead(&self) -> bool {
         matches!(self.state, ConnectionState::Dead)
     }
+
+    fn is_backed_up(&self) -> bool {
+        match self.state {
+            ConnectionState::Alive(ref kind) => matches!(
+                *kind.lock().unwrap(),
+                ConnectionKind::Pipe(_, Some(ref pending)) if pending.len() >= MAX_PENDING_DATA
+            ),
+            ConnectionState::Dead => false,
+        }
+    }
 }
 
 impl Source {
@@ -174,7 +209,7 @@ impl Graph {
     }
 
     pub fn add_destination(&mut self, dst: WritePipe) -> DestinationId {
-        self.add_dst_impl(ConnectionKind::Pipe(dst))
+        self.add_dst_impl(ConnectionKind::Pipe(dst, None))
     }
 
     pub fn add_file_destination(&mut self, file: WritePipe) -> DestinationId {
@@ -201,7 +236,7 @@ impl Graph {
                 .into_inner()
                 .unwrap()
             {
-                ConnectionKind::Pipe(p) => p,
+                ConnectionKind::Pipe(p, _) => p,
                 ConnectionKind::File(f) => f.into_inner().unwrap(),
             }
         })
@@ -230,7 +265,31 @@ impl Graph {
         }
     }
 
+    /// Reads every source in its own thread.
     pub fn transmit_data(self) -> Transmitter {
+        let (srcs, file_dsts) = self.into_sources();
+        Transmitter {
+            readers: srcs
+                .into_iter()
+                .map(|(id, src)| (id, thread::spawn(move || read_source(src))))
+                .collect(),
+            pump: None,
+            file_dsts,
+        }
+    }
+
+    /// Reads all sources in a single thread, the pipes are made nonblocking. Unix only.
+    pub fn transmit_data_multiplexed(self) -> Transmitter {
+        let (srcs, file_dsts) = self.into_sources();
+        let ids = srcs.iter().map(|(&id, _)| id).collect();
+        Transmitter {
+            readers: Vec::new(),
+            pump: Some((ids, thread::spawn(move || pump(srcs)))),
+            file_dsts,
+        }
+    }
+
+    fn into_sources(self) -> (HashMap<SourceId, Source>, Vec<Destination>) {
         let file_dsts = self
             .dsts
             .into_iter()
@@ -242,14 +301,7 @@ impl Graph {
                 }
             })
             .collect();
-        Transmitter {
-            readers: self
-                .srcs
-                .into_iter()
-                .map(|(id, src)| (id, thread::spawn(move || read_source(src))))
-                .collect(),
-            file_dsts,
-        }
+        (self.srcs, file_dsts)
     }
 
     fn generate_src_id(&mut self) -> SourceId {
@@ -265,51 +317,220 @@ impl Graph {
 
 impl Transmitter {
     pub fn wait(self) -> TransmitterResults {
+        let mut sources = self
+            .readers
+            .into_iter()
+            .map(|(id, reader)| {
+                (
+                    id,
+                    reader
+                        .join()
+                        .unwrap_or_else(|_| Err(Error::from("Source reader panicked"))),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        if let Some((ids, pump)) = self.pump {
+            match pump.join() {
+                Ok(results) => sources.extend(results),
+                Err(_) => sources.extend(
+                    ids.into_iter()
+                        .map(|id| (id, Err(Error::from("Source reader panicked")))),
+                ),
+            }
+        }
         TransmitterResults {
-            sources: self
-                .readers
-                .into_iter()
-                .map(|(id, reader)| {
-                    (
-                        id,
-                        reader
-                            .join()
-                            .unwrap_or_else(|_| Err(Error::from("Source reader panicked"))),
-                    )
-                })
-                .collect::<HashMap<_, _>>(),
+            sources,
             _file_dsts: self.file_dsts,
         }
     }
 }
 
-fn read_source(src: Source) -> Result<ReadPipe> {
-    let reader = src.reader;
-    let mut pipe = src.pipe;
-    let mut connections = src.connections;
+impl ActiveSource {
+    fn new(src: Source) -> (ReadPipe, Self) {
+        (
+            src.pipe,
+            Self {
+                connections: src.connections,
+                reader: src.reader,
+                result: Ok(()),
+            },
+        )
+    }
 
-    if let Some(mut reader) = reader {
-        return reader.read(&mut pipe, &mut connections).map(|_| pipe);
+    /// Passes the `data` on, returns `false` once the source doesn't need to be read anymore.
+    fn transmit(&mut self, data: &[u8]) -> bool {
+        match self.reader {
+            Some(ref mut reader) => match reader.read(data, &mut self.connections) {
+                Ok(()) => true,
+                Err(e) => {
+                    self.result = Err(e);
+                    false
+                }
+            },
+            None => {
+                for c in self.connections.iter_mut() {
+                    c.send(data);
+                }
+                !self.connections.iter().all(Connection::is_dead)
+            }
+        }
     }
 
+    fn is_backed_up(&self) -> bool {
+        self.connections.iter().any(Connection::is_backed_up)
+    }
+
+    fn finish(self) -> Result<()> {
+        let mut connections = self.connections;
+        match self.reader {
+            Some(mut reader) => reader.finish(self.result, &mut connections),
+            None => self.result,
+        }
+    }
+}
+
+fn read_source(src: Source) -> Result<ReadPipe> {
+    let (pipe, mut src) = ActiveSource::new(src);
     let mut reader = BufReader::new(pipe);
     loop {
         let data_len = {
             let data = reader.fill_buf().unwrap_or(&[]);
-            if data.is_empty() {
+            if data.is_empty() || !src.transmit(data) {
                 break;
             }
-            for c in connections.iter_mut() {
-                c.send(data);
-            }
             data.len()
         };
         reader.consume(data_len);
+    }
+    src.finish().map(|_| reader.into_inner())
+}
+
+/// Reads the nonblocking `srcs` as their data arrives. The data that doesn't fit into
+/// the pipes of destinations is kept until they drain, and sources aren't read while
+/// too much data is pending for their destinations.
+fn pump(srcs: HashMap<SourceId, Source>) -> SourceResults {
+    let mut results = HashMap::new();
+    let mut dsts: Vec<Arc<Mutex<ConnectionKind>>> = Vec::new();
+    for c in srcs.values().flat_map(|src| src.connections.iter()) {
+        if let ConnectionState::Alive(ref kind) = c.state {
+            if !dsts.iter().any(|dst| Arc::ptr_eq(dst, kind)) {
+                dsts.push(kind.clone());
+            }
+        }
+    }
+    for dst in dsts.iter() {
+        if let ConnectionKind::Pipe(ref p, ref mut pending) = *dst.lock().unwrap() {
+            if p.set_nonblocking().is_ok() {
+                *pending = Some(Vec::new());
+            }
+        }
+    }
+    dsts.retain(|dst| matches!(*dst.lock().unwrap(), ConnectionKind::Pipe(_, Some(_))));
+
+    let mut active = Vec::new();
+    for (id, src) in srcs {
+        let (pipe, src) = ActiveSource::new(src);
+        match pipe.set_nonblocking() {
+            Ok(()) => active.push((id, pipe, src)),
+            Err(e) => {
+                results.insert(id, src.finish().and(Err(e)));
+            }
+        }
+    }
 
-        if connections.iter().all(Connection::is_dead) {
+    let mut buf = vec![0; MAX_PENDING_DATA];
+    loop {
+        // A destination is closed once it's drained and no source is connected to it.
+        dsts.retain(|dst| Arc::strong_count(dst) > 1 || has_pending_data(dst));
+        if active.is_empty() && dsts.is_empty() {
             break;
         }
+
+        let readable = (0..active.len())
+            .filter(|&i| !active[i].2.is_backed_up())
+            .collect::<Vec<_>>();
+        let writable = dsts
+            .iter()
+            .filter(|dst| has_pending_data(dst))
+            .collect::<Vec<_>>();
+        let ready = {
+            let guards = writable
+                .iter()
+                .map(|dst| dst.lock().unwrap())
+                .collect::<Vec<_>>();
+            let write_pipes = guards
+                .iter()
+                .filter_map(|kind| match **kind {
+                    ConnectionKind::Pipe(ref p, _) => Some(p),
+                    ConnectionKind::File(_) => None,
+                })
+                .collect::<Vec<_>>();
+            let read_pipes = readable.iter().map(|&i| &active[i].1).collect::<Vec<_>>();
+            pipe::poll(&read_pipes, &write_pipes)
+        };
+        let ready = match ready {
+            Ok(ready) => ready,
+            Err(e) => {
+                for (id, _, src) in active.drain(..) {
+                    results.insert(id, src.finish().and(Err(Error::from(copy_io_error(&e)))));
+                }
+                break;
+            }
+        };
+
+        for (dst, _) in writable
+            .iter()
+            .zip(&ready[readable.len()..])
+            .filter(|x| *x.1)
+        {
+            if let ConnectionKind::Pipe(ref mut p, Some(ref mut pending)) = *dst.lock().unwrap() {
+                if write_pending(p, pending).is_err() {
+                    // The connections find out on the next write.
+                    pending.clear();
+                }
+            }
+        }
+        // Finished sources are swapped with the last ones, which are already handled.
+        for (&i, _) in readable.iter().zip(&ready).filter(|x| *x.1).rev() {
+            let (_, ref mut pipe, ref mut src) = active[i];
+            let is_finished = match pipe.read(&mut buf) {
+                Ok(0) => true,
+                Ok(n) => !src.transmit(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => false,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => false,
+                Err(_) => true,
+            };
+            if is_finished {
+                let (id, pipe, src) = active.swap_remove(i);
+                results.insert(id, src.finish().map(|_| pipe));
+            }
+        }
+    }
+    results
+}
+
+fn has_pending_data(dst: &Arc<Mutex<ConnectionKind>>) -> bool {
+    matches!(*dst.lock().unwrap(), ConnectionKind::Pipe(_, Some(ref pending)) if !pending.is_empty())
+}
+
+/// Writes as much of the `pending` data as fits into the nonblocking `pipe`.
+fn write_pending(pipe: &mut WritePipe, pending: &mut Vec<u8>) -> io::Result<()> {
+    while !pending.is_empty() {
+        match pipe.write(pending) {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+            Ok(n) => {
+                pending.drain(..n);
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(e) => return Err(e),
+        }
     }
+    Ok(())
+}
 
-    Ok(reader.into_inner())
+fn copy_io_error(e: &io::Error) -> io::Error {
+    match e.raw_os_error() {
+        Some(code) => io::Error::from_raw_os_error(code),
+        None => io::Error::new(e.kind(), e.to_string()),
+    }
 }
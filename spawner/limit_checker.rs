@@ -1,6 +1,7 @@
-use crate::process::{GroupTimers, ResourceUsage};
-use crate::{ResourceLimits, Result, TerminationReason};
+use crate::process::{GroupMemory, GroupTimers, ResourceUsage};
+use crate::{MemoryMetric, ResourceLimits, Result, TerminationReason};
 
+use std::thread;
 use std::time::{Duration, Instant};
 
 pub struct LimitChecker {
@@ -49,6 +50,56 @@ impl LimitChecker {
         self.total_user_time = Duration::from_millis(0);
     }
 
+    /// Returns `true` if some of the limits can only be checked by polling the resource usage.
+    /// Limits enforced by the OS are checked on notifications if `os_limits_notified` is set.
+    pub fn is_polling_required(&self, os_limits_notified: bool) -> bool {
+        let limits = &self.limits;
+        let group_memory_limit =
+            limits.max_memory_usage.is_some() && limits.memory_metric == MemoryMetric::Group;
+        let os_limits = group_memory_limit || limits.active_processes.is_some();
+        limits.idle_time.is_some()
+            || limits.instructions.is_some()
+            || self.is_process_memory_limited()
+            || limits.total_bytes_written.is_some()
+            || limits.total_bytes_read.is_some()
+            || limits.total_processes_created.is_some()
+            || limits.active_network_connections.is_some()
+            || (os_limits && !os_limits_notified)
+    }
+
+    /// Returns the earliest time at which the wall clock time or user time limit can be exceeded.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let limits = &self.limits;
+        if limits.wall_clock_time.is_none() && limits.total_user_time.is_none() {
+            return None;
+        }
+        let prev_check = match self.prev_check {
+            Some(ref prev_check) => prev_check,
+            None => return Some(Instant::now()),
+        };
+        if self.time_accounting_stopped {
+            return None;
+        }
+
+        // The user time of a group grows at most as fast as wall clock time on every CPU.
+        let num_cpus = thread::available_parallelism().map_or(1, |n| n.get()) as u32;
+        let wall_clock_time_left = limits
+            .wall_clock_time
+            .map(|limit| limit.saturating_sub(self.wall_clock_time));
+        let user_time_left = limits.total_user_time.map(|limit| {
+            scale_time(
+                limit.saturating_sub(self.total_user_time) / num_cpus,
+                1.0 / limits.time_scale,
+            )
+        });
+        let time_left = match (wall_clock_time_left, user_time_left) {
+            (Some(w), Some(u)) => w.min(u),
+            (w, u) => w.or(u).unwrap(),
+        };
+        // A deadline too far away to be represented is never reached.
+        prev_check.time.checked_add(time_left)
+    }
+
     pub fn check(&mut self, usage: &ResourceUsage) -> Result<Option<TerminationReason>> {
         let timers = usage.timers()?.unwrap_or_default();
         self.update_timers(timers);
@@ -59,7 +110,7 @@ impl LimitChecker {
 
         let limits = &self.limits;
         let query_memory = limits.max_memory_usage.is_some();
-        let query_io = limits.total_bytes_written.is_some();
+        let query_io = limits.total_bytes_written.is_some() || limits.total_bytes_read.is_some();
         let query_network = limits.active_network_connections.is_some();
         let query_pid_counters =
             limits.active_processes.is_some() || limits.total_processes_created.is_some();
@@ -92,9 +143,16 @@ impl LimitChecker {
             TerminationReason::IdleTimeLimitExceeded
         } else if gr(self.total_user_time, limits.total_user_time) {
             TerminationReason::UserTimeLimitExceeded
+        } else if limits
+            .instructions
+            .is_some_and(|limit| timers.cpu_counter.is_some_and(|c| c.value() > limit))
+        {
+            TerminationReason::InstructionLimitExceeded
         } else if gr(io.total_bytes_written, limits.total_bytes_written) {
             TerminationReason::WriteLimitExceeded
-        } else if gr(memory.max_usage, limits.max_memory_usage) {
+        } else if gr(io.total_bytes_read, limits.total_bytes_read) {
+            TerminationReason::ReadLimitExceeded
+        } else if self.is_memory_limit_exceeded(&memory) {
             TerminationReason::MemoryLimitExceeded
         } else if gr(pid_counters.total_processes, limits.total_processes_created) {
             TerminationReason::ProcessLimitExceeded
@@ -110,6 +168,31 @@ impl LimitChecker {
         }))
     }
 
+    /// Returns `true` if the memory limit applies to the peak memory of a single process.
+    pub fn is_process_memory_limited(&self) -> bool {
+        self.limits.max_memory_usage.is_some() && self.limits.memory_metric == MemoryMetric::Process
+    }
+
+    /// Returns `true` if the memory usage of the chosen metric exceeds the memory limit.
+    pub fn is_memory_limit_exceeded(&self, memory: &GroupMemory) -> bool {
+        let usage = match self.limits.memory_metric {
+            MemoryMetric::Group => memory.max_usage,
+            MemoryMetric::Process => memory.max_process_usage,
+        };
+        self.limits
+            .max_memory_usage
+            .is_some_and(|limit| usage > limit)
+    }
+
+    /// Scales the CPU time of the group by the time scale of the limits.
+    pub fn scale_timers(&self, timers: GroupTimers) -> GroupTimers {
+        GroupTimers {
+            total_user_time: scale_time(timers.total_user_time, self.limits.time_scale),
+            total_kernel_time: scale_time(timers.total_kernel_time, self.limits.time_scale),
+            cpu_counter: timers.cpu_counter,
+        }
+    }
+
     fn update_timers(&mut self, timers: GroupTimers) {
         if self.time_accounting_stopped {
             return;
@@ -124,7 +207,9 @@ impl LimitChecker {
         let new_cpu_load = d_user.as_micros() as f64 / dt.as_micros() as f64;
 
         self.wall_clock_time += dt;
-        self.total_user_time += d_user;
+        self.total_user_time = self
+            .total_user_time
+            .saturating_add(scale_time(d_user, self.limits.time_scale));
         self.average_cpu_load = self.average_cpu_load * CPU_LOAD_SMOOTHING_FACTOR
             + new_cpu_load * (1.0 - CPU_LOAD_SMOOTHING_FACTOR);
         self.average_cpu_load_points += 1;
@@ -143,3 +228,8 @@ impl LimitChecker {
         }
     }
 }
+
+/// Multiplies the time by the factor, saturating instead of panicking on overflow.
+fn scale_time(time: Duration, factor: f64) -> Duration {
+    Duration::try_from_secs_f64(time.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+}
@@ -28,6 +28,15 @@ pub fn create() -> Result<(ReadPipe, WritePipe)> {
     Ok((ReadPipe(r), WritePipe(w)))
 }
 
+/// Waits until some of the `read` pipes have data or are closed, or some of the `write`
+/// pipes can be written to. Returns whether each pipe is ready, the `read` pipes first.
+/// Unix only.
+pub(crate) fn poll(read: &[&ReadPipe], write: &[&WritePipe]) -> io::Result<Vec<bool>> {
+    let read = read.iter().map(|p| &p.0).collect::<Vec<_>>();
+    let write = write.iter().map(|p| &p.0).collect::<Vec<_>>();
+    imp::poll(&read, &write)
+}
+
 impl ReadPipe {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         imp::ReadPipe::open(path).map(Self)
@@ -36,6 +45,11 @@ impl ReadPipe {
     pub fn null() -> Result<Self> {
         imp::ReadPipe::null().map(Self)
     }
+
+    /// Makes reads fail with `WouldBlock` instead of waiting for data. Unix only.
+    pub(crate) fn set_nonblocking(&self) -> Result<()> {
+        self.0.set_nonblocking()
+    }
 }
 
 impl IntoInner<imp::ReadPipe> for ReadPipe {
@@ -64,6 +78,11 @@ impl WritePipe {
     pub fn null() -> Result<Self> {
         imp::WritePipe::null().map(Self)
     }
+
+    /// Makes writes fail with `WouldBlock` instead of waiting for the pipe to drain. Unix only.
+    pub(crate) fn set_nonblocking(&self) -> Result<()> {
+        self.0.set_nonblocking()
+    }
 }
 
 impl IntoInner<imp::WritePipe> for WritePipe {
@@ -1,17 +1,30 @@
 use crate::pipe::{ReadPipe, WritePipe};
 use crate::sys::process as imp;
 use crate::sys::{AsInnerMut, IntoInner};
-use crate::Result;
+use crate::{Result, TerminationReason};
 
 use std::time::Duration;
 
 /// Describes the result of a process after it has terminated.
 #[derive(Clone, Debug, PartialEq)]
 pub enum ExitStatus {
-    Crashed(String),
+    Crashed(Crash),
     Finished(u32),
 }
 
+/// Describes how a process has crashed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crash {
+    /// The number of the signal that has terminated the process, always `None` on Windows.
+    pub signal: Option<i32>,
+    pub core_dumped: bool,
+    /// The exception code on Windows. On unix the code and the address of a fault are only
+    /// known to a tracer of the process, so this is always `None` there.
+    pub fault_code: Option<u32>,
+    /// A human readable cause, e.g. "Process terminated by the 'SIGSEGV' signal".
+    pub cause: String,
+}
+
 /// Describes the standard I/O streams of a process.
 pub struct Stdio {
     pub stdin: ReadPipe,
@@ -28,17 +41,63 @@ pub struct Process(imp::Process);
 #[derive(Copy, Clone, Debug)]
 pub struct GroupMemory {
     pub max_usage: u64,
+    /// The peak memory usage of a single process in the group: the resident set size
+    /// on unix and the committed memory on Windows. On unix it's only known for the
+    /// processes sampled by [`ResourceUsage::sample_process_memory`].
+    ///
+    /// [`ResourceUsage::sample_process_memory`]: struct.ResourceUsage.html#method.sample_process_memory
+    pub max_process_usage: u64,
+    /// The number of times the memory usage has hit the OS memory limit.
+    pub limit_hits: u64,
+    /// The number of processes killed by the OOM killer, always zero on Windows.
+    pub oom_kills: u64,
+}
+
+/// The resource usage of an exited process and the descendants it has waited for,
+/// as reported by `wait4`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Rusage {
+    pub user_time: Duration,
+    pub kernel_time: Duration,
+    /// The peak resident set size, in bytes.
+    pub max_rss: u64,
+    pub minor_page_faults: u64,
+    pub major_page_faults: u64,
+    pub voluntary_context_switches: u64,
+    pub involuntary_context_switches: u64,
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct GroupTimers {
     pub total_user_time: Duration,
     pub total_kernel_time: Duration,
+    /// The CPU performance counter of the group, if enabled and available, unix only.
+    pub cpu_counter: Option<CpuCounter>,
+}
+
+/// The value of a CPU performance counter. Unlike the CPU time it barely varies between runs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CpuCounter {
+    /// The number of instructions retired in user mode.
+    Instructions(u64),
+    /// The CPU time counted by the software task clock, used if hardware counters are unavailable.
+    TaskClock(Duration),
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct GroupIo {
+    /// The number of bytes read by the group through any file, including pipes.
+    pub total_bytes_read: u64,
+    /// The number of bytes written by the group through any file, including pipes.
     pub total_bytes_written: u64,
+    /// The number of bytes read from block devices, unix only.
+    pub disk_bytes_read: u64,
+    /// The number of bytes written to block devices, unix only.
+    pub disk_bytes_written: u64,
+    /// The number of read operations on block devices, unix only.
+    pub disk_reads: u64,
+    /// The number of write operations on block devices, unix only.
+    pub disk_writes: u64,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -56,10 +115,31 @@ pub struct GroupNetwork {
 pub enum OsLimit {
     Memory,
     ActiveProcess,
+    /// The maximum amount of swap the group can use, unix only.
+    Swap,
+    /// The memory usage above which the group is throttled, unix only.
+    MemoryHigh,
+    /// The CPU time the group may use per 100ms, in microseconds, unix only.
+    CpuQuota,
+    /// The rate of reading from every block device, in bytes per second, unix only.
+    DiskReadBandwidth,
+    /// The rate of writing to every block device, in bytes per second, unix only.
+    DiskWriteBandwidth,
 }
 
 pub struct ResourceUsage<'a>(imp::ResourceUsage<'a>);
 
+/// Notifications about the state changes of a process and its group.
+pub struct Events(imp::Events);
+
+/// Waits for the notifications of several processes at once.
+pub struct Poller(imp::Poller);
+
+/// Wakes up the supervisor waiting for the [`Events`] that watch it.
+///
+/// [`Events`]: struct.Events.html
+pub struct Waker(imp::Waker);
+
 /// Describes a group of processes.
 pub struct Group(imp::Group);
 
@@ -127,6 +207,17 @@ impl Process {
         self.0.exit_status()
     }
 
+    /// Returns the resource usage of the process once it has exited, unix only.
+    pub fn rusage(&self) -> Option<Rusage> {
+        self.0.rusage()
+    }
+
+    /// Returns the termination reason if the process was killed by the operating system
+    /// for exceeding one of its own limits.
+    pub fn termination_reason(&self) -> Option<TerminationReason> {
+        self.0.termination_reason()
+    }
+
     /// Suspends the main thread of a process.
     pub fn suspend(&self) -> Result<()> {
         self.0.suspend()
@@ -163,6 +254,80 @@ impl Process {
     }
 }
 
+impl Events {
+    pub fn new(ps: &Process, group: &Group) -> Result<Self> {
+        ps.0.events(&group.0).map(Self)
+    }
+
+    /// Blocks until a notification arrives or the timeout expires, `None` means no timeout.
+    /// Returns `false` if nothing was notified.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<bool> {
+        self.0.wait(timeout)
+    }
+
+    /// Handles pending notifications without blocking. Returns `false` if nothing was notified.
+    pub fn poll(&mut self) -> Result<bool> {
+        self.0.poll()
+    }
+
+    /// Returns `true` if the exit of the process is yet to be notified. Otherwise
+    /// the exit status of the process must be polled.
+    pub fn is_exit_notified(&self) -> bool {
+        self.0.is_exit_notified()
+    }
+
+    /// Returns `true` if hitting an OS limit of the group is notified. Otherwise
+    /// OS limits must be polled.
+    pub fn is_os_limit_notified(&self) -> bool {
+        self.0.is_os_limit_notified()
+    }
+
+    /// Notifies about calls of [`Waker::wake`], the waker must outlive the events.
+    ///
+    /// [`Waker::wake`]: struct.Waker.html#method.wake
+    pub fn watch(&mut self, waker: &Waker) -> Result<()> {
+        self.0.watch(&waker.0)
+    }
+
+    /// Returns `true` if the wakeups of a watched waker are notified. Otherwise
+    /// whatever the waker signals must be polled.
+    pub fn is_waker_notified(&self) -> bool {
+        self.0.is_waker_notified()
+    }
+}
+
+impl Waker {
+    pub fn new() -> Result<Self> {
+        imp::Waker::new().map(Self)
+    }
+
+    pub fn wake(&self) {
+        self.0.wake()
+    }
+}
+
+impl Poller {
+    pub fn new() -> Result<Self> {
+        imp::Poller::new().map(Self)
+    }
+
+    pub fn add(&mut self, events: &Events) -> Result<()> {
+        self.0.add(&events.0)
+    }
+
+    pub fn remove(&mut self, events: &Events) -> Result<()> {
+        self.0.remove(&events.0)
+    }
+
+    /// Blocks until some of the events have pending notifications or the timeout expires,
+    /// `None` means no timeout. The notifications are then handled by [`Events::poll`].
+    ///
+    /// [`Events::poll`]: struct.Events.html#method.poll
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.0.wait(timeout)
+    }
+}
+
 impl<'a> ResourceUsage<'a> {
     pub fn new(group: &'a Group) -> Self {
         Self(imp::ResourceUsage::new(&group.0))
@@ -172,6 +337,17 @@ impl<'a> ResourceUsage<'a> {
         self.0.update()
     }
 
+    /// Samples the peak memory of every process on each update. On unix this is costly,
+    /// so otherwise only the peak memory of the main process is known once it exits.
+    pub fn sample_process_memory(&mut self) {
+        self.0.sample_process_memory()
+    }
+
+    /// Subtracts the kernel memory from the peak memory usage of the group, unix only.
+    pub fn exclude_kernel_memory(&mut self) {
+        self.0.exclude_kernel_memory()
+    }
+
     pub fn timers(&self) -> Result<Option<GroupTimers>> {
         self.0.timers()
     }
@@ -207,6 +383,14 @@ impl Group {
         self.0.set_os_limit(limit, value)
     }
 
+    /// Attaches a CPU performance counter to the next process spawned in the group,
+    /// which then counts its descendants as well. Unix only. If performance events
+    /// aren't permitted, spawning fails if the counter is `required` and the counter
+    /// is omitted otherwise.
+    pub fn enable_cpu_counter(&mut self, required: bool) {
+        self.0.enable_cpu_counter(required)
+    }
+
     /// Returns `true` if the limit was hit.
     pub fn is_os_limit_hit(&self, limit: OsLimit) -> Result<bool> {
         self.0.is_os_limit_hit(limit)
@@ -215,6 +399,22 @@ impl Group {
     pub fn terminate(&self) -> Result<()> {
         self.0.terminate()
     }
+
+    /// Sends the signal to every process in the group. Returns `true` if the signal was sent,
+    /// signals are unix only.
+    pub fn send_signal(&self, signal: i32) -> Result<bool> {
+        self.0.send_signal(signal)
+    }
+
+    /// Suspends every process in the group, including the ones created by a program itself.
+    pub fn suspend(&self) -> Result<()> {
+        self.0.suspend()
+    }
+
+    /// Resumes every process in the group, as well as a process spawned suspended in it.
+    pub fn resume(&self) -> Result<()> {
+        self.0.resume()
+    }
 }
 
 impl IntoInner<imp::Stdio> for Stdio {
@@ -248,14 +448,24 @@ impl AsMut<ProcessInfo> for ProcessInfo {
 impl Default for GroupIo {
     fn default() -> Self {
         Self {
+            total_bytes_read: 0,
             total_bytes_written: 0,
+            disk_bytes_read: 0,
+            disk_bytes_written: 0,
+            disk_reads: 0,
+            disk_writes: 0,
         }
     }
 }
 
 impl Default for GroupMemory {
     fn default() -> Self {
-        Self { max_usage: 0 }
+        Self {
+            max_usage: 0,
+            max_process_usage: 0,
+            limit_hits: 0,
+            oom_kills: 0,
+        }
     }
 }
 
@@ -281,6 +491,17 @@ impl Default for GroupTimers {
         Self {
             total_user_time: Duration::from_millis(0),
             total_kernel_time: Duration::from_millis(0),
+            cpu_counter: None,
+        }
+    }
+}
+
+impl CpuCounter {
+    /// Returns the number of instructions or the task clock in nanoseconds.
+    pub fn value(&self) -> u64 {
+        match self {
+            CpuCounter::Instructions(n) => *n,
+            CpuCounter::TaskClock(t) => t.as_nanos() as u64,
         }
     }
 }
@@ -2,16 +2,16 @@ use crate::dataflow::{DestinationId, Graph, SourceId, Transmitter, TransmitterRe
 use crate::dataflow_analysis::DataflowOptimizer;
 use crate::pipe;
 use crate::process::{
-    ExitStatus, Group, GroupIo, GroupMemory, GroupNetwork, GroupPidCounters, GroupTimers,
-    ProcessInfo, Stdio,
+    ExitStatus, Group, GroupIo, GroupMemory, GroupNetwork, GroupPidCounters, GroupTimers, Poller,
+    ProcessInfo, Rusage, Stdio, Waker,
 };
-use crate::supervisor::Supervisor;
+use crate::supervisor::{Multiplexer, Supervisor};
 use crate::{Error, Result};
 
 use std::collections::HashSet;
 use std::fmt;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{channel, Receiver, SendError, Sender};
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
@@ -22,12 +22,45 @@ pub enum TerminationReason {
     WallClockTimeLimitExceeded,
     IdleTimeLimitExceeded,
     UserTimeLimitExceeded,
+    /// The CPU counter of the process group exceeded its limit.
+    InstructionLimitExceeded,
     WriteLimitExceeded,
+    ReadLimitExceeded,
     MemoryLimitExceeded,
     ProcessLimitExceeded,
     ActiveProcessLimitExceeded,
     ActiveNetworkConnectionLimitExceeded,
+    /// The process tried to write past its file size limit.
+    FileSizeLimitExceeded,
+    /// The process made a syscall forbidden by its syscall filter.
+    SecurityViolation(SecurityViolation),
     TerminatedByRunner,
+    /// The runner itself was asked to stop, e.g. the spawner received SIGINT or SIGTERM.
+    Interrupted,
+}
+
+/// Describes a forbidden syscall made by a process.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SecurityViolation {
+    /// The process that made the syscall, it may be a descendant of the spawned one.
+    pub pid: u32,
+    /// Unknown if the process was killed by the kernel, since the supervisor can't
+    /// be notified of the violations on older kernels.
+    pub syscall: Option<i32>,
+    pub syscall_name: Option<&'static str>,
+}
+
+/// The memory usage the memory limit is enforced on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MemoryMetric {
+    /// The memory usage of the whole group as accounted by the OS. On unix it includes
+    /// the page cache and, unless excluded, kernel memory.
+    Group,
+    /// The peak memory usage of a single process in the group, see
+    /// [`GroupMemory::max_process_usage`].
+    ///
+    /// [`GroupMemory::max_process_usage`]: process/struct.GroupMemory.html#structfield.max_process_usage
+    Process,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -44,20 +77,59 @@ pub struct ResourceLimits {
     pub wall_clock_time: Option<Duration>,
     /// The maximum allowed amount of user-mode execution time for a process group.
     pub total_user_time: Option<Duration>,
+    /// The share of a single CPU a process group may use, e.g. `0.5`, unix only.
+    pub cpu_quota: Option<f64>,
+    /// The factor the CPU time of a process group is multiplied by before it's checked
+    /// against the limits and reported, e.g. to emulate a slower machine.
+    pub time_scale: f64,
+    /// Whether the CPU performance counter of a process group is sampled, unix only.
+    pub cpu_counter: bool,
+    /// The maximum allowed number of user-mode instructions retired by a process group, unix only.
+    /// The counter follows the process tree of the program rather than the cgroup, so processes
+    /// that join the group otherwise aren't counted. Implies `cpu_counter`, which is then
+    /// required to start the program.
+    pub instructions: Option<u64>,
     /// The maximum allowed memory usage, in bytes.
     pub max_memory_usage: Option<u64>,
+    pub memory_metric: MemoryMetric,
+    /// The memory usage above which a process group is throttled, unix only.
+    pub memory_high: Option<u64>,
+    /// Whether a process group is not allowed to use swap, unix only.
+    pub disable_swap: bool,
+    /// Whether the kernel memory is excluded from the measured memory usage, unix only.
+    pub exclude_kernel_memory: bool,
     /// The maximum allowed amount of bytes written by a process group.
     pub total_bytes_written: Option<u64>,
+    /// The maximum allowed amount of bytes read by a process group.
+    pub total_bytes_read: Option<u64>,
+    /// The maximum rate of reading from block devices, in bytes per second, unix only.
+    pub disk_read_bandwidth: Option<u64>,
+    /// The maximum rate of writing to block devices, in bytes per second, unix only.
+    pub disk_write_bandwidth: Option<u64>,
     /// The maximum allowed number of processes created.
     pub total_processes_created: Option<usize>,
     /// The maximum allowed number of active processes.
     pub active_processes: Option<usize>,
     /// The maximum allowed number of active network connections.
     pub active_network_connections: Option<usize>,
+    /// How a process group is terminated, it's killed at once if `None`.
+    pub termination_policy: Option<TerminationPolicy>,
+}
+
+/// Lets a process group exit on its own once it exceeds a limit or is terminated by the runner.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TerminationPolicy {
+    /// The signal sent to every process in the group, unix only. Elsewhere the group
+    /// is killed at once.
+    pub signal: i32,
+    /// The time the group is given to exit before it's killed.
+    pub grace_period: Duration,
 }
 
 pub enum ProgramMessage {
     Terminate,
+    /// Terminates the program because the runner itself is stopping.
+    Interrupt,
     Suspend,
     Resume,
     StopTimeAccounting,
@@ -65,6 +137,23 @@ pub enum ProgramMessage {
     ResetTime,
 }
 
+/// Sends messages to a program and wakes up its supervisor, see [`message_channel`].
+///
+/// [`message_channel`]: fn.message_channel.html
+#[derive(Clone)]
+pub struct MessageSender {
+    sender: Sender<ProgramMessage>,
+    waker: Arc<Waker>,
+}
+
+/// Receives the messages to a program, see [`message_channel`].
+///
+/// [`message_channel`]: fn.message_channel.html
+pub struct MessageReceiver {
+    receiver: Receiver<ProgramMessage>,
+    waker: Arc<Waker>,
+}
+
 /// Summary information about process's execution.
 #[derive(Clone, Debug)]
 pub struct Report {
@@ -74,6 +163,11 @@ pub struct Report {
     pub timers: Option<GroupTimers>,
     pub pid_counters: Option<GroupPidCounters>,
     pub network: Option<GroupNetwork>,
+    /// The resource usage of the main process, unix only.
+    pub rusage: Option<Rusage>,
+    /// Whether the program exited on its own within the grace period of the termination
+    /// policy, `None` if it wasn't terminated that way.
+    pub exited_within_grace_period: Option<bool>,
     pub exit_status: ExitStatus,
     pub termination_reason: Option<TerminationReason>,
 }
@@ -89,7 +183,7 @@ pub struct Program {
     info: ProcessInfo,
     group: Option<Group>,
     resource_limits: Option<ResourceLimits>,
-    msg_receiver: Option<Receiver<ProgramMessage>>,
+    msg_receiver: Option<MessageReceiver>,
     monitor_interval: Duration,
     wait_for_children: bool,
 }
@@ -113,6 +207,7 @@ pub struct Session {
     graph: Graph,
     ignored_srcs: HashSet<SourceId>,
     ignored_dsts: HashSet<DestinationId>,
+    multiplex_supervisors: bool,
 }
 
 struct FlagGuard(Arc<AtomicBool>);
@@ -122,8 +217,19 @@ struct SupervisorThread {
     is_finished: Arc<AtomicBool>,
 }
 
+/// A thread that supervises all programs of a session.
+struct MultiplexerThread {
+    handle: JoinHandle<Vec<Result<Report>>>,
+    is_finished: Vec<Arc<AtomicBool>>,
+}
+
+enum Supervisors {
+    Threads(Vec<SupervisorThread>),
+    Multiplexed(MultiplexerThread),
+}
+
 pub struct Run {
-    supervisors: Vec<SupervisorThread>,
+    supervisors: Supervisors,
     mappings: Vec<StdioMapping>,
     transmitter: Transmitter,
 }
@@ -134,15 +240,68 @@ impl Default for ResourceLimits {
             wall_clock_time: None,
             idle_time: None,
             total_user_time: None,
+            cpu_quota: None,
+            time_scale: 1.0,
+            cpu_counter: false,
+            instructions: None,
             max_memory_usage: None,
+            memory_metric: MemoryMetric::Group,
+            memory_high: None,
+            disable_swap: false,
+            exclude_kernel_memory: false,
             total_bytes_written: None,
+            total_bytes_read: None,
+            disk_read_bandwidth: None,
+            disk_write_bandwidth: None,
             total_processes_created: None,
             active_processes: None,
             active_network_connections: None,
+            termination_policy: None,
         }
     }
 }
 
+/// Creates a channel for the messages to a program, the receiver is given to
+/// [`Program::msg_receiver`].
+///
+/// [`Program::msg_receiver`]: struct.Program.html#method.msg_receiver
+pub fn message_channel() -> Result<(MessageSender, MessageReceiver)> {
+    let (sender, receiver) = channel();
+    let waker = Arc::new(Waker::new()?);
+    Ok((
+        MessageSender {
+            sender,
+            waker: waker.clone(),
+        },
+        MessageReceiver { receiver, waker },
+    ))
+}
+
+impl MessageSender {
+    /// Fails if the receiver has been dropped, i.e. the program has finished.
+    pub fn send(&self, msg: ProgramMessage) -> std::result::Result<(), SendError<ProgramMessage>> {
+        self.sender.send(msg)?;
+        self.waker.wake();
+        Ok(())
+    }
+}
+
+impl MessageReceiver {
+    pub(crate) fn waker(&self) -> &Waker {
+        &self.waker
+    }
+
+    /// Receives at most `max` pending messages without blocking. If that many were received,
+    /// the supervisor is woken up again, since the rest aren't notified anymore.
+    pub(crate) fn try_recv(&self, max: usize) -> Vec<ProgramMessage> {
+        let messages = self.receiver.try_iter().take(max).collect::<Vec<_>>();
+        if messages.len() == max {
+            self.waker.wake();
+        }
+        messages
+    }
+}
+
 impl std::error::Error for ProgramErrors {}
 
 impl fmt::Display for ProgramErrors {
@@ -196,7 +355,7 @@ impl Program {
         self
     }
 
-    pub fn msg_receiver(&mut self, receiver: Receiver<ProgramMessage>) -> &mut Self {
+    pub fn msg_receiver(&mut self, receiver: MessageReceiver) -> &mut Self {
         self.msg_receiver = Some(receiver);
         self
     }
@@ -239,6 +398,12 @@ impl Session {
         self.ignored_dsts.insert(dst);
     }
 
+    /// Supervises all programs in a single thread instead of a thread per program.
+    /// On unix the pipes are also read by a single thread instead of a thread per source.
+    pub fn multiplex_supervisors(&mut self, multiplex: bool) {
+        self.multiplex_supervisors = multiplex;
+    }
+
     pub fn graph_mut(&mut self) -> &mut Graph {
         &mut self.graph
     }
@@ -248,13 +413,26 @@ impl Session {
     }
 
     pub fn run(mut self) -> Result<Run> {
-        self.optimize_io().map(|_| Run {
-            supervisors: self
-                .progs
-                .into_iter()
-                .map(|p| SupervisorThread::spawn(p.prog, p.stdio))
-                .collect(),
-            transmitter: self.graph.transmit_data(),
+        self.optimize_io()?;
+        let supervisors = if self.multiplex_supervisors {
+            Supervisors::Multiplexed(MultiplexerThread::spawn(self.progs)?)
+        } else {
+            Supervisors::Threads(
+                self.progs
+                    .into_iter()
+                    .map(|p| SupervisorThread::spawn(p.prog, p.stdio))
+                    .collect(),
+            )
+        };
+        // Pipes on Windows don't support polling.
+        let transmitter = if self.multiplex_supervisors && cfg!(unix) {
+            self.graph.transmit_data_multiplexed()
+        } else {
+            self.graph.transmit_data()
+        };
+        Ok(Run {
+            supervisors,
+            transmitter,
             mappings: self.mappings,
         })
     }
@@ -301,32 +479,10 @@ impl SupervisorThread {
         }
     }
 
-    fn wait(self, mapping: StdioMapping, results: &mut TransmitterResults) -> ProgramResult {
-        // Collect io errors for this program.
-        let mut errs = [mapping.stdout, mapping.stderr]
-            .iter()
-            .filter_map(|id| {
-                if results.sources.get(id).map(Result::is_err).unwrap_or(false) {
-                    Some(results.sources.remove(id).unwrap().unwrap_err())
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-
-        let result = self
-            .handle
+    fn join(self) -> Result<Report> {
+        self.handle
             .join()
             .unwrap_or_else(|_| Err(Error::from("Supervisor thread panicked")))
-            .map_err(|e| {
-                errs.push(e);
-            })
-            .ok();
-        if errs.is_empty() {
-            Ok(result.unwrap())
-        } else {
-            Err(ProgramErrors { errors: errs })
-        }
     }
 
     fn is_finished(&self) -> bool {
@@ -334,17 +490,142 @@ impl SupervisorThread {
     }
 }
 
+impl MultiplexerThread {
+    fn spawn(progs: Vec<ProgramExt>) -> Result<Self> {
+        let poller = Poller::new()?;
+        let is_finished = progs
+            .iter()
+            .map(|_| Arc::new(AtomicBool::new(false)))
+            .collect::<Vec<_>>();
+        let flags = is_finished.clone();
+        Ok(Self {
+            is_finished,
+            handle: thread::spawn(move || {
+                let guards = flags.into_iter().map(FlagGuard).collect::<Vec<_>>();
+                Self::monitor(progs, poller, &guards)
+            }),
+        })
+    }
+
+    fn monitor(
+        progs: Vec<ProgramExt>,
+        poller: Poller,
+        guards: &[FlagGuard],
+    ) -> Vec<Result<Report>> {
+        let mut results = progs.iter().map(|_| None).collect::<Vec<_>>();
+        let mut finish = |id: usize, result: Result<Report>| {
+            results[id] = Some(result);
+            guards[id].0.store(true, Ordering::Release);
+        };
+
+        let mut groups = Vec::new();
+        let mut supervisors = Vec::new();
+        for (id, p) in progs.into_iter().enumerate() {
+            let Program {
+                info,
+                group,
+                resource_limits,
+                msg_receiver,
+                monitor_interval,
+                wait_for_children,
+            } = p.prog;
+            let stdio = p.stdio;
+            let started = match group {
+                Some(g) => Ok(g),
+                None => Group::new(),
+            }
+            .and_then(|mut group| {
+                Supervisor::start(
+                    info,
+                    stdio,
+                    &mut group,
+                    resource_limits.unwrap_or_default(),
+                    monitor_interval,
+                    msg_receiver,
+                    wait_for_children,
+                )
+                .map(|supervisor| (group, supervisor))
+            });
+            match started {
+                Ok((group, supervisor)) => {
+                    groups.push(group);
+                    supervisors.push((id, supervisor));
+                }
+                Err(e) => finish(id, Err(e)),
+            }
+        }
+
+        let mut multiplexer = Multiplexer::new(poller);
+        for ((id, supervisor), group) in supervisors.into_iter().zip(groups.iter()) {
+            if let Err(e) = multiplexer.add(id, supervisor, group) {
+                finish(id, Err(e));
+            }
+        }
+        multiplexer.run(&mut finish);
+        results.into_iter().map(Option::unwrap).collect()
+    }
+
+    fn join(self) -> Vec<Result<Report>> {
+        let num_progs = self.is_finished.len();
+        self.handle.join().unwrap_or_else(|_| {
+            (0..num_progs)
+                .map(|_| Err(Error::from("Supervisor thread panicked")))
+                .collect()
+        })
+    }
+}
+
 impl Run {
     pub fn wait(self) -> Vec<ProgramResult> {
         let mut transmitter_results = self.transmitter.wait();
-        self.supervisors
+        let results = match self.supervisors {
+            Supervisors::Threads(threads) => {
+                threads.into_iter().map(SupervisorThread::join).collect()
+            }
+            Supervisors::Multiplexed(thread) => thread.join(),
+        };
+        results
             .into_iter()
             .zip(self.mappings.into_iter())
-            .map(|(supervisor, mapping)| supervisor.wait(mapping, &mut transmitter_results))
+            .map(|(result, mapping)| program_result(result, mapping, &mut transmitter_results))
             .collect::<Vec<_>>()
     }
 
     pub fn all_finished(&self) -> bool {
-        self.supervisors.iter().all(SupervisorThread::is_finished)
+        match self.supervisors {
+            Supervisors::Threads(ref threads) => threads.iter().all(SupervisorThread::is_finished),
+            Supervisors::Multiplexed(ref thread) => {
+                thread.is_finished.iter().all(|f| f.load(Ordering::Acquire))
+            }
+        }
+    }
+}
+
+fn program_result(
+    result: Result<Report>,
+    mapping: StdioMapping,
+    results: &mut TransmitterResults,
+) -> ProgramResult {
+    // Collect io errors for this program.
+    let mut errs = [mapping.stdout, mapping.stderr]
+        .iter()
+        .filter_map(|id| {
+            if results.sources.get(id).map(Result::is_err).unwrap_or(false) {
+                Some(results.sources.remove(id).unwrap().unwrap_err())
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let result = result
+        .map_err(|e| {
+            errs.push(e);
+        })
+        .ok();
+    if errs.is_empty() {
+        Ok(result.unwrap())
+    } else {
+        Err(ProgramErrors { errors: errs })
     }
 }
@@ -1,19 +1,45 @@
 use crate::limit_checker::LimitChecker;
-use crate::process::{Group, OsLimit, Process, ProcessInfo, ResourceUsage, Stdio};
-use crate::{ProgramMessage, Report, ResourceLimits, Result, TerminationReason};
+use crate::process::{Events, Group, OsLimit, Poller, Process, ProcessInfo, ResourceUsage, Stdio};
+use crate::{
+    Error, MemoryMetric, MessageReceiver, ProgramMessage, Report, ResourceLimits, Result,
+    TerminationPolicy, TerminationReason,
+};
 
-use std::sync::mpsc::Receiver;
-use std::thread;
 use std::time::{Duration, Instant};
 
+/// The interval of sampling the usage of processes that may exit unnoticed, as well as
+/// polling the messages and the exit status if these aren't notified.
+const SAMPLING_INTERVAL: Duration = Duration::from_millis(1);
+
 pub struct Supervisor {
     limit_checker: LimitChecker,
     process: Process,
+    events: Events,
     creation_time: Instant,
+    last_check_time: Instant,
     term_reason: Option<TerminationReason>,
-    msg_receiver: Option<Receiver<ProgramMessage>>,
+    msg_receiver: Option<MessageReceiver>,
     monitor_interval: Duration,
     wait_for_children: bool,
+    exclude_kernel_memory: bool,
+    termination_policy: Option<TerminationPolicy>,
+    // The time the group is killed at if it hasn't exited within the grace period.
+    kill_deadline: Option<Instant>,
+    exited_within_grace_period: Option<bool>,
+}
+
+/// Supervises several programs in a single thread.
+pub struct Multiplexer<'a> {
+    poller: Poller,
+    programs: Vec<MultiplexedProgram<'a>>,
+}
+
+struct MultiplexedProgram<'a> {
+    id: usize,
+    supervisor: Supervisor,
+    group: &'a Group,
+    usage: ResourceUsage<'a>,
+    wakeup_time: Instant,
 }
 
 impl Supervisor {
@@ -23,49 +49,198 @@ impl Supervisor {
         mut group: Group,
         limits: ResourceLimits,
         monitor_interval: Duration,
-        receiver: Option<Receiver<ProgramMessage>>,
+        receiver: Option<MessageReceiver>,
         wait_for_children: bool,
     ) -> Result<Report> {
+        Self::start(
+            info,
+            stdio,
+            &mut group,
+            limits,
+            monitor_interval,
+            receiver,
+            wait_for_children,
+        )
+        .and_then(|pm| pm.monitoring_loop(group))
+    }
+
+    /// Spawns a program in the group, the program is monitored by [`Multiplexer`].
+    ///
+    /// [`Multiplexer`]: struct.Multiplexer.html
+    pub fn start(
+        info: ProcessInfo,
+        stdio: Stdio,
+        group: &mut Group,
+        limits: ResourceLimits,
+        monitor_interval: Duration,
+        receiver: Option<MessageReceiver>,
+        wait_for_children: bool,
+    ) -> Result<Self> {
+        // The peak memory usage of a process has no OS limit, so it's polled.
         if let Some(mem_limit) = limits.max_memory_usage {
-            group.set_os_limit(OsLimit::Memory, mem_limit)?;
+            if limits.memory_metric == MemoryMetric::Group {
+                group.set_os_limit(OsLimit::Memory, mem_limit)?;
+            }
         }
         if let Some(num) = limits.active_processes {
             group.set_os_limit(OsLimit::ActiveProcess, num as u64)?;
         }
+        if let Some(value) = limits.memory_high {
+            group.set_os_limit(OsLimit::MemoryHigh, value)?;
+        }
+        if let Some(quota) = limits.cpu_quota {
+            group.set_os_limit(OsLimit::CpuQuota, (quota * 100_000.0) as u64)?;
+        }
+        if let Some(value) = limits.disk_read_bandwidth {
+            group.set_os_limit(OsLimit::DiskReadBandwidth, value)?;
+        }
+        if let Some(value) = limits.disk_write_bandwidth {
+            group.set_os_limit(OsLimit::DiskWriteBandwidth, value)?;
+        }
+        // The swap limit may depend on the memory limit, so it's set last.
+        if limits.disable_swap {
+            group.set_os_limit(OsLimit::Swap, 0)?;
+        }
+        if limits.cpu_counter || limits.instructions.is_some() {
+            group.enable_cpu_counter(limits.instructions.is_some());
+        }
 
-        Process::spawn_in_group(info, stdio, &mut group)
-            .map(|ps| Self {
-                limit_checker: LimitChecker::new(limits),
-                process: ps,
-                creation_time: Instant::now(),
-                term_reason: None,
-                msg_receiver: receiver,
-                monitor_interval,
-                wait_for_children,
-            })
-            .and_then(|pm| pm.monitoring_loop(group))
+        let ps = Process::spawn_in_group(info, stdio, group)?;
+        let mut events = Events::new(&ps, group)?;
+        if let Some(ref receiver) = receiver {
+            events.watch(receiver.waker())?;
+        }
+        let now = Instant::now();
+        Ok(Self {
+            exclude_kernel_memory: limits.exclude_kernel_memory,
+            termination_policy: limits.termination_policy,
+            kill_deadline: None,
+            exited_within_grace_period: None,
+            limit_checker: LimitChecker::new(limits),
+            events,
+            process: ps,
+            creation_time: now,
+            last_check_time: now,
+            term_reason: None,
+            msg_receiver: receiver,
+            monitor_interval,
+            wait_for_children,
+        })
     }
 
     fn monitoring_loop(mut self, group: Group) -> Result<Report> {
-        let mut usage = ResourceUsage::new(&group);
-        let mut last_check_time = Instant::now();
+        let mut usage = self.resource_usage(&group);
+        let mut notified = false;
         loop {
-            usage.update()?;
-            if let Some(report) = self.get_report(&group, &usage)? {
+            if let Some(report) = self.update(&group, &mut usage, notified)? {
                 return Ok(report);
             }
+            let wakeup_time = self.next_wakeup_time(&usage)?;
+            let timeout = wakeup_time.saturating_duration_since(Instant::now());
+            notified = self.events.wait(Some(timeout))?;
+        }
+    }
 
-            if last_check_time.elapsed() > self.monitor_interval {
-                last_check_time = Instant::now();
-                if let Some(tr) = self.check_limits(&group, &usage)? {
-                    group.terminate()?;
-                    self.term_reason = Some(tr);
-                }
+    /// Samples the usage of the group, checks the limits if something was notified or
+    /// the check is due and handles messages. Returns the report once the program has finished.
+    fn update(
+        &mut self,
+        group: &Group,
+        usage: &mut ResourceUsage,
+        notified: bool,
+    ) -> Result<Option<Report>> {
+        usage.update()?;
+        if let Some(report) = self.get_report(group, usage)? {
+            return Ok(Some(report));
+        }
+
+        let next_check_time = self.next_check_time();
+        let now = Instant::now();
+        let check_due = next_check_time.is_some_and(|t| t <= now);
+        if self.term_reason.is_none() && (notified || check_due) {
+            self.last_check_time = now;
+            if let Some(tr) = self.check_limits(group, usage)? {
+                self.terminate(group)?;
+                self.term_reason = Some(tr);
             }
+        }
+        if self.kill_deadline.is_some_and(|t| t <= now) {
+            self.kill_deadline = None;
+            self.exited_within_grace_period = Some(false);
+            group.terminate()?;
+        }
+
+        self.handle_messages(group)?;
+        Ok(None)
+    }
+
+    /// Creates the resource usage of the group sampled the way the limits require.
+    fn resource_usage<'a>(&self, group: &'a Group) -> ResourceUsage<'a> {
+        let mut usage = ResourceUsage::new(group);
+        if self.limit_checker.is_process_memory_limited() {
+            usage.sample_process_memory();
+        }
+        if self.exclude_kernel_memory {
+            usage.exclude_kernel_memory();
+        }
+        usage
+    }
+
+    /// Returns the time of the next limit check that isn't triggered by a notification.
+    fn next_check_time(&self) -> Option<Instant> {
+        let deadline = self.limit_checker.next_deadline();
+        // Time accounting is controlled by messages, which are polled unless notified.
+        let is_polling_required = self.are_messages_polled()
+            || self
+                .limit_checker
+                .is_polling_required(self.events.is_os_limit_notified());
+        if !is_polling_required {
+            return deadline;
+        }
+        let next_poll = self.last_check_time + self.monitor_interval;
+        Some(deadline.map_or(next_poll, |d| d.min(next_poll)))
+    }
+
+    fn next_wakeup_time(&self, usage: &ResourceUsage) -> Result<Instant> {
+        // A process that exits between two samples takes its usage with it, so the group
+        // is sampled frequently unless it consists of a single process with a notified exit.
+        let num_processes = usage.pid_counters()?.map_or(0, |c| c.active_processes);
+        let sampling_interval = if self.events.is_exit_notified()
+            && num_processes <= 1
+            && !self.are_messages_polled()
+        {
+            self.monitor_interval
+        } else {
+            SAMPLING_INTERVAL
+        };
+
+        let now = Instant::now();
+        let next_sample = now + sampling_interval;
+        let next_check_time = self.next_check_time().into_iter().chain(self.kill_deadline);
+        Ok(match next_check_time.min() {
+            Some(t) => next_sample.min(t.max(now + SAMPLING_INTERVAL)),
+            None => next_sample,
+        })
+    }
+
+    fn are_messages_polled(&self) -> bool {
+        self.msg_receiver.is_some() && !self.events.is_waker_notified()
+    }
 
-            self.handle_messages(&group)?;
-            thread::sleep(Duration::from_millis(1));
+    /// Terminates the group according to the termination policy. The group is given
+    /// the grace period only once, it's left to exit if that's already running.
+    fn terminate(&mut self, group: &Group) -> Result<()> {
+        if self.kill_deadline.is_some() {
+            return Ok(());
+        }
+        if let Some(policy) = self.termination_policy {
+            if self.exited_within_grace_period.is_none() && group.send_signal(policy.signal)? {
+                self.kill_deadline = Some(Instant::now() + policy.grace_period);
+                self.exited_within_grace_period = Some(true);
+                return Ok(());
+            }
         }
+        group.terminate()
     }
 
     fn check_limits(
@@ -96,43 +271,59 @@ impl Supervisor {
             return Ok(None);
         }
 
+        if self.term_reason.is_none() {
+            self.term_reason = self.process.termination_reason();
+        }
         if self.term_reason.is_none() {
             self.term_reason = self.check_limits(group, usage)?;
         }
 
+        // Processes that have exited are no longer sampled, but their peak resident
+        // set size is included in the resource usage of the main process.
+        let rusage = self.process.rusage();
+        let memory = usage.memory()?.map(|mut memory| {
+            if let Some(rusage) = rusage {
+                memory.max_process_usage = memory.max_process_usage.max(rusage.max_rss);
+            }
+            memory
+        });
+        if self.term_reason.is_none()
+            && memory.is_some_and(|m| self.limit_checker.is_memory_limit_exceeded(&m))
+        {
+            self.term_reason = Some(TerminationReason::MemoryLimitExceeded);
+        }
+
         Ok(Some(Report {
             wall_clock_time: self.creation_time.elapsed(),
-            memory: usage.memory()?,
+            memory,
             io: usage.io()?,
-            timers: usage.timers()?,
+            timers: usage.timers()?.map(|t| self.limit_checker.scale_timers(t)),
             pid_counters,
             network: usage.network()?,
+            rusage,
+            exited_within_grace_period: self.exited_within_grace_period,
             exit_status,
             termination_reason: self.term_reason,
         }))
     }
 
     fn handle_messages(&mut self, group: &Group) -> Result<()> {
-        let receiver = match &mut self.msg_receiver {
-            Some(r) => r,
+        let messages = match self.msg_receiver {
+            Some(ref r) => r.try_recv(10),
             None => return Ok(()),
         };
-        for msg in receiver.try_iter().take(10) {
+        for msg in messages {
             match msg {
                 ProgramMessage::Terminate => {
-                    group.terminate()?;
+                    self.terminate(group)?;
                     self.term_reason = Some(TerminationReason::TerminatedByRunner);
                 }
-                ProgramMessage::Suspend => {
-                    if self.process.exit_status()?.is_none() {
-                        self.process.suspend()?;
-                    }
-                }
-                ProgramMessage::Resume => {
-                    if self.process.exit_status()?.is_none() {
-                        self.process.resume()?;
-                    }
+                ProgramMessage::Interrupt => {
+                    self.terminate(group)?;
+                    self.term_reason = Some(TerminationReason::Interrupted);
                 }
+                ProgramMessage::Suspend => group.suspend()?,
+                ProgramMessage::Resume => group.resume()?,
                 ProgramMessage::ResetTime => self.limit_checker.reset_time(),
                 ProgramMessage::StopTimeAccounting => self.limit_checker.stop_time_accounting(),
                 ProgramMessage::ResumeTimeAccounting => self.limit_checker.resume_time_accounting(),
@@ -141,3 +332,75 @@ impl Supervisor {
         Ok(())
     }
 }
+
+impl<'a> Multiplexer<'a> {
+    pub fn new(poller: Poller) -> Self {
+        Self {
+            poller,
+            programs: Vec::new(),
+        }
+    }
+
+    /// Adds a program started in the `group`, its result is reported under the `id`.
+    pub fn add(&mut self, id: usize, supervisor: Supervisor, group: &'a Group) -> Result<()> {
+        self.poller.add(&supervisor.events)?;
+        let usage = supervisor.resource_usage(group);
+        self.programs.push(MultiplexedProgram {
+            id,
+            supervisor,
+            group,
+            usage,
+            wakeup_time: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Monitors the programs until all of them have finished, `on_finish` is called
+    /// with the id and the result of each program.
+    pub fn run<F>(mut self, mut on_finish: F)
+    where
+        F: FnMut(usize, Result<Report>),
+    {
+        while !self.programs.is_empty() {
+            let mut i = 0;
+            while i < self.programs.len() {
+                match self.programs[i].update() {
+                    Ok(None) => i += 1,
+                    result => {
+                        let program = self.programs.swap_remove(i);
+                        let _ = self.poller.remove(&program.supervisor.events);
+                        on_finish(program.id, result.map(Option::unwrap));
+                    }
+                }
+            }
+
+            let wakeup_time = match self.programs.iter().map(|p| p.wakeup_time).min() {
+                Some(t) => t,
+                None => break,
+            };
+            let timeout = wakeup_time.saturating_duration_since(Instant::now());
+            if let Err(e) = self.poller.wait(Some(timeout)) {
+                let msg = e.to_string();
+                for program in self.programs.drain(..) {
+                    on_finish(program.id, Err(Error::from(msg.clone())));
+                }
+            }
+        }
+    }
+}
+
+impl<'a> MultiplexedProgram<'a> {
+    fn update(&mut self) -> Result<Option<Report>> {
+        let notified = self.supervisor.events.poll()?;
+        if !notified && Instant::now() < self.wakeup_time {
+            return Ok(None);
+        }
+        let report = self
+            .supervisor
+            .update(self.group, &mut self.usage, notified)?;
+        if report.is_none() {
+            self.wakeup_time = self.supervisor.next_wakeup_time(&self.usage)?;
+        }
+        Ok(report)
+    }
+}
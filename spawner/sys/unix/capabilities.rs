@@ -0,0 +1,329 @@
+use crate::process::CpuCounter;
+use crate::sys::unix::cgroup::{is_unified_hierarchy, Cgroups};
+use crate::sys::unix::missing_decls::{
+    SECCOMP_GET_ACTION_AVAIL, SECCOMP_RET_KILL_PROCESS, SECCOMP_RET_USER_NOTIF,
+};
+use crate::sys::unix::namespaces::enter_user_namespace;
+use crate::sys::unix::perf::PerfCounter;
+use crate::sys::unix::pidfd::PidFd;
+use crate::sys::unix::seccomp::can_get_listener;
+
+use nix::errno::Errno;
+use nix::libc::{self, syscall, SYS_seccomp};
+use nix::sched::{sched_getaffinity, sched_setaffinity, unshare, CloneFlags};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{access, fork, geteuid, AccessFlags, ForkResult, Pid};
+
+use procfs::process::Process;
+
+use std::fmt;
+use std::path::Path;
+use std::process;
+
+/// How well a feature the sandbox relies on works on this host.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CapabilityStatus {
+    Available,
+    /// The feature works with limitations or a fallback is used instead.
+    Degraded,
+    Unavailable,
+}
+
+/// The result of a probe of a single feature.
+#[derive(Clone, Debug)]
+pub struct Capability {
+    pub name: &'static str,
+    pub status: CapabilityStatus,
+    /// What is missing and which options are affected, or a note on the available feature.
+    pub details: String,
+}
+
+/// Cgroup controllers of the legacy hierarchy and what's lost without the optional ones.
+const V1_CONTROLLERS: [(&str, &str, Option<&str>); 6] = [
+    ("cgroup.memory", "memory", None),
+    ("cgroup.cpuacct", "cpuacct", None),
+    ("cgroup.pids", "pids", None),
+    ("cgroup.freezer", "freezer", None),
+    ("cgroup.blkio", "blkio", None),
+    ("cgroup.cpu", "cpu", Some("-cpu-quota is unavailable")),
+];
+
+/// Cgroup controllers of the unified hierarchy, a file they add to a task cgroup and what's
+/// lost without the optional ones.
+const V2_CONTROLLERS: [(&str, &str, Option<&str>); 5] = [
+    ("cgroup.memory", "memory.max", None),
+    ("cgroup.pids", "pids.max", None),
+    ("cgroup.freezer", "cgroup.freeze", None),
+    (
+        "cgroup.io",
+        "io.stat",
+        Some("disk I/O is not reported, -rbw and -wbw are unavailable"),
+    ),
+    ("cgroup.cpu", "cpu.max", Some("-cpu-quota is unavailable")),
+];
+
+impl Capability {
+    fn new<T: ToString>(name: &'static str, status: CapabilityStatus, details: T) -> Self {
+        Self {
+            name,
+            status,
+            details: details.to_string(),
+        }
+    }
+
+    fn available(name: &'static str) -> Self {
+        Self::new(name, CapabilityStatus::Available, "")
+    }
+}
+
+impl fmt::Display for CapabilityStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            CapabilityStatus::Available => "Available",
+            CapabilityStatus::Degraded => "Degraded",
+            CapabilityStatus::Unavailable => "Unavailable",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Probes every feature the sandbox relies on. A task cgroup is created and removed on the way,
+/// namespaces are entered in child processes, so the calling process is left as is.
+pub fn check_capabilities() -> Vec<Capability> {
+    let mut caps = check_cgroups();
+    caps.push(check_kernel_memory());
+    caps.push(check_seccomp());
+    caps.push(check_affinity());
+    caps.push(check_proc_io());
+    caps.push(check_impersonation());
+    caps.extend(check_namespaces());
+    caps.push(check_perf());
+    caps.push(check_pidfd());
+    caps
+}
+
+fn check_cgroups() -> Vec<Capability> {
+    use self::CapabilityStatus::*;
+
+    let cgroups = Cgroups::create();
+    let mut caps = vec![match cgroups {
+        Ok(ref cgroups) if is_unified_hierarchy() => Capability::new(
+            "cgroups",
+            Available,
+            format!("unified hierarchy, {}", cgroups.path().display()),
+        ),
+        Ok(_) => Capability::new("cgroups", Available, "legacy hierarchy"),
+        Err(ref e) => Capability::new("cgroups", Unavailable, e),
+    }];
+
+    if !is_unified_hierarchy() {
+        for (name, subsystem, loss) in V1_CONTROLLERS.iter() {
+            let dir = Path::new("/sys/fs/cgroup").join(subsystem).join("sp");
+            // The cpu controller is often mounted together with cpuacct.
+            let comounted =
+                *subsystem == "cpu" && Path::new("/sys/fs/cgroup/cpuacct/sp/cpu.shares").exists();
+            let problem = if comounted {
+                None
+            } else if !dir.is_dir() {
+                Some(format!(
+                    "{} is missing, run 'sudo sp --setup'",
+                    dir.display()
+                ))
+            } else if access(&dir, AccessFlags::W_OK).is_err() {
+                Some(format!("{} is not writable", dir.display()))
+            } else {
+                None
+            };
+            caps.push(controller_capability(name, problem, *loss));
+        }
+        return caps;
+    }
+
+    let path = match cgroups {
+        Ok(ref cgroups) => cgroups.path(),
+        Err(ref e) => {
+            for (name, _, _) in V2_CONTROLLERS.iter() {
+                let problem = Some(format!("Cannot create a cgroup: {}", e));
+                caps.push(controller_capability(name, problem, None));
+            }
+            return caps;
+        }
+    };
+    for (name, file, loss) in V2_CONTROLLERS.iter() {
+        let controller = name.trim_start_matches("cgroup.");
+        let problem = Some(format!("The {} controller is not enabled", controller))
+            .filter(|_| !path.join(file).exists());
+        caps.push(controller_capability(name, problem, *loss));
+    }
+    caps
+}
+
+fn controller_capability(
+    name: &'static str,
+    problem: Option<String>,
+    loss: Option<&str>,
+) -> Capability {
+    match (problem, loss) {
+        (None, _) => Capability::available(name),
+        (Some(problem), Some(loss)) => Capability::new(
+            name,
+            CapabilityStatus::Degraded,
+            format!("{}, {}", problem, loss),
+        ),
+        (Some(problem), None) => Capability::new(name, CapabilityStatus::Unavailable, problem),
+    }
+}
+
+fn check_kernel_memory() -> Capability {
+    const NAME: &str = "kmem";
+    // Kernel memory is always accounted in the unified hierarchy.
+    if is_unified_hierarchy()
+        || Path::new("/sys/fs/cgroup/memory/sp/memory.kmem.max_usage_in_bytes").exists()
+    {
+        return Capability::available(NAME);
+    }
+    Capability::new(
+        NAME,
+        CapabilityStatus::Degraded,
+        "Kernel memory is not accounted separately, -kmem=0 is unavailable",
+    )
+}
+
+fn check_seccomp() -> Capability {
+    const NAME: &str = "seccomp";
+    let check_action = |action: u32| {
+        let result = unsafe { syscall(SYS_seccomp, SECCOMP_GET_ACTION_AVAIL, 0, &action) };
+        Errno::result(result)
+    };
+    if let Err(e) = check_action(SECCOMP_RET_KILL_PROCESS) {
+        return Capability::new(NAME, CapabilityStatus::Unavailable, e);
+    }
+    if check_action(SECCOMP_RET_USER_NOTIF).is_err() || !can_get_listener() {
+        return Capability::new(
+            NAME,
+            CapabilityStatus::Degraded,
+            "User notifications are unavailable, processes are killed by the kernel and policies with 'exec once' are rejected",
+        );
+    }
+    Capability::available(NAME)
+}
+
+fn check_affinity() -> Capability {
+    const NAME: &str = "sched_setaffinity";
+    let pid = Pid::from_raw(0);
+    match sched_getaffinity(pid).and_then(|cpus| sched_setaffinity(pid, &cpus)) {
+        Ok(_) => Capability::available(NAME),
+        Err(e) => Capability::new(
+            NAME,
+            CapabilityStatus::Unavailable,
+            format!("{}, -cpu is unavailable", e),
+        ),
+    }
+}
+
+fn check_proc_io() -> Capability {
+    const NAME: &str = "proc_io";
+    match Process::myself().and_then(|p| p.io()) {
+        Ok(_) => Capability::available(NAME),
+        Err(e) => Capability::new(
+            NAME,
+            CapabilityStatus::Unavailable,
+            format!(
+                "Cannot read /proc/self/io: {}, -wl and -rl have no effect",
+                e
+            ),
+        ),
+    }
+}
+
+fn check_impersonation() -> Capability {
+    const NAME: &str = "impersonation";
+    const CAP_SETGID: u64 = 1 << 6;
+    const CAP_SETUID: u64 = 1 << 7;
+    let capabilities = Process::myself()
+        .and_then(|p| p.status())
+        .map(|s| s.capeff)
+        .unwrap_or(0);
+    if geteuid().is_root() || capabilities & (CAP_SETUID | CAP_SETGID) == CAP_SETUID | CAP_SETGID {
+        return Capability::available(NAME);
+    }
+    Capability::new(
+        NAME,
+        CapabilityStatus::Unavailable,
+        "Requires root or CAP_SETUID and CAP_SETGID, -u is unavailable",
+    )
+}
+
+fn check_namespaces() -> Vec<Capability> {
+    let namespaces = [
+        ("namespace.user", CloneFlags::CLONE_NEWUSER),
+        ("namespace.mount", CloneFlags::CLONE_NEWNS),
+        ("namespace.pid", CloneFlags::CLONE_NEWPID),
+        ("namespace.net", CloneFlags::CLONE_NEWNET),
+    ];
+    let is_user_ns_available = probe_in_child(enter_user_namespace).is_ok();
+    namespaces
+        .iter()
+        .map(|(name, flags)| {
+            let error = match probe_in_child(|| unshare(*flags)) {
+                Ok(_) => return Capability::available(name),
+                Err(e) => e,
+            };
+            // Unprivileged users can create the other namespaces inside of a user namespace.
+            if *flags != CloneFlags::CLONE_NEWUSER
+                && is_user_ns_available
+                && probe_in_child(|| enter_user_namespace().and_then(|_| unshare(*flags))).is_ok()
+            {
+                return Capability::new(name, CapabilityStatus::Degraded, "Requires -rootless=1");
+            }
+            Capability::new(name, CapabilityStatus::Unavailable, error)
+        })
+        .collect()
+}
+
+fn check_perf() -> Capability {
+    const NAME: &str = "perf";
+    match PerfCounter::open(Pid::this(), true).and_then(|counter| counter.read()) {
+        Ok(CpuCounter::TaskClock(_)) => Capability::new(
+            NAME,
+            CapabilityStatus::Degraded,
+            "Hardware counters are unavailable, the task clock is counted instead and -il is unavailable",
+        ),
+        Ok(CpuCounter::Instructions(_)) => Capability::available(NAME),
+        Err(e) => Capability::new(
+            NAME,
+            CapabilityStatus::Unavailable,
+            format!("{}, -perf and -il are unavailable", e),
+        ),
+    }
+}
+
+fn check_pidfd() -> Capability {
+    const NAME: &str = "pidfd";
+    match PidFd::open(Pid::this()) {
+        Ok(_) => Capability::available(NAME),
+        Err(e) => Capability::new(
+            NAME,
+            CapabilityStatus::Degraded,
+            format!("{}, the exit of a program is polled", e),
+        ),
+    }
+}
+
+/// Runs the probe in a child process, so that the namespaces it enters don't affect the caller.
+fn probe_in_child<F: FnOnce() -> nix::Result<()>>(probe: F) -> nix::Result<()> {
+    match fork()? {
+        ForkResult::Child => {
+            let code = match probe() {
+                Ok(_) => 0,
+                Err(e) => e.as_errno().map_or(libc::EINVAL, |e| e as i32),
+            };
+            process::exit(code);
+        }
+        ForkResult::Parent { child } => match waitpid(child, None)? {
+            WaitStatus::Exited(_, 0) => Ok(()),
+            WaitStatus::Exited(_, code) => Err(nix::Error::from_errno(Errno::from_i32(code))),
+            _ => Err(nix::Error::from_errno(Errno::ECHILD)),
+        },
+    }
+}
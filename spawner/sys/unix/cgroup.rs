@@ -0,0 +1,689 @@
+use crate::process::{GroupTimers, OsLimit};
+use crate::{Error, Result};
+
+use nix::libc;
+use nix::sys::signal::Signal;
+use nix::unistd::{sysconf, Pid, SysconfVar};
+
+use cgroups_fs::{Cgroup, CgroupName};
+
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// The period the CPU quota of a group is replenished with, in microseconds.
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// The I/O of a group on block devices.
+#[derive(Copy, Clone, Default)]
+pub struct DiskIo {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub reads: u64,
+    pub writes: u64,
+}
+
+/// Control groups of a process group.
+pub enum Cgroups {
+    /// Legacy hierarchy, every controller is mounted separately.
+    V1 {
+        memory: Cgroup,
+        cpuacct: Cgroup,
+        pids: Cgroup,
+        freezer: Cgroup,
+        blkio: Cgroup,
+        /// Created only for the CPU quota, `None` if the cpu controller
+        /// is mounted together with cpuacct.
+        cpu: Option<Cgroup>,
+        name: String,
+    },
+    /// Unified hierarchy, all controllers share a single cgroup.
+    V2 { cgroup: Cgroup, path: PathBuf },
+}
+
+impl Cgroups {
+    pub fn create() -> Result<Self> {
+        let name = format!(
+            "task_{}",
+            (0..7)
+                .map(|_| thread_rng().sample(Alphanumeric))
+                .collect::<String>()
+        );
+        if is_unified_hierarchy() {
+            let parent = unified_parent()?;
+            Ok(Cgroups::V2 {
+                cgroup: create_cgroup(&name, &parent)?,
+                path: Path::new("/sys/fs/cgroup").join(parent).join(&name),
+            })
+        } else {
+            Ok(Cgroups::V1 {
+                memory: create_cgroup(&name, "memory/sp")?,
+                cpuacct: create_cgroup(&name, "cpuacct/sp")?,
+                pids: create_cgroup(&name, "pids/sp")?,
+                freezer: create_cgroup(&name, "freezer/sp")?,
+                blkio: create_cgroup(&name, "blkio/sp")?,
+                cpu: None,
+                name,
+            })
+        }
+    }
+
+    /// Opens the existing task cgroup `name` in the `parent` cgroup of the unified hierarchy,
+    /// or in `sp` cgroups of the legacy one.
+    pub fn open(name: &str, parent: &str) -> Self {
+        let cgroup_name = CgroupName::new(name);
+        if is_unified_hierarchy() {
+            Cgroups::V2 {
+                cgroup: Cgroup::new(&cgroup_name, parent),
+                path: Path::new("/sys/fs/cgroup").join(parent).join(name),
+            }
+        } else {
+            Cgroups::V1 {
+                memory: Cgroup::new(&cgroup_name, "memory/sp"),
+                cpuacct: Cgroup::new(&cgroup_name, "cpuacct/sp"),
+                pids: Cgroup::new(&cgroup_name, "pids/sp"),
+                freezer: Cgroup::new(&cgroup_name, "freezer/sp"),
+                blkio: Cgroup::new(&cgroup_name, "blkio/sp"),
+                cpu: Some(Cgroup::new(&cgroup_name, "cpu/sp"))
+                    .filter(|_| Path::new("/sys/fs/cgroup/cpu/sp").join(name).is_dir()),
+                name: name.to_string(),
+            }
+        }
+    }
+
+    /// Returns the directory of the group that holds its tasks.
+    pub fn path(&self) -> PathBuf {
+        match self {
+            Cgroups::V1 { name, .. } => Path::new("/sys/fs/cgroup/freezer/sp").join(name),
+            Cgroups::V2 { path, .. } => path.clone(),
+        }
+    }
+
+    /// Returns the directories of the group, the one that holds its tasks goes first.
+    pub fn dirs(&self) -> Vec<PathBuf> {
+        match self {
+            Cgroups::V1 { name, cpu, .. } => ["freezer", "memory", "cpuacct", "pids", "blkio"]
+                .iter()
+                .chain(cpu.as_ref().map(|_| &"cpu"))
+                .map(|subsystem| {
+                    Path::new("/sys/fs/cgroup")
+                        .join(subsystem)
+                        .join("sp")
+                        .join(name)
+                })
+                .collect(),
+            Cgroups::V2 { path, .. } => vec![path.clone()],
+        }
+    }
+
+    /// Creates the group and locks it. The parent cgroup is locked meanwhile, so that
+    /// the cleanup, which locks the parent exclusively, can't see the group unlocked.
+    pub fn create_locked() -> Result<(Self, File)> {
+        let parent = if is_unified_hierarchy() {
+            Path::new("/sys/fs/cgroup").join(unified_parent()?)
+        } else {
+            PathBuf::from("/sys/fs/cgroup/freezer/sp")
+        };
+        let _parent_lock = lock_dir(&parent, libc::LOCK_SH)?;
+        let cgroups = Self::create()?;
+        let lock = cgroups.lock()?;
+        Ok((cgroups, lock))
+    }
+
+    /// Locks the group, so that it's not taken for an orphan while the returned file is open.
+    /// The lock is released once the spawner exits, even if it crashes.
+    pub fn lock(&self) -> io::Result<File> {
+        lock_dir(&self.path(), libc::LOCK_EX | libc::LOCK_NB)
+    }
+
+    pub fn add_task(&self, pid: Pid) -> io::Result<()> {
+        match self {
+            Cgroups::V1 {
+                memory,
+                cpuacct,
+                pids,
+                freezer,
+                blkio,
+                cpu,
+                ..
+            } => memory
+                .add_task(pid)
+                .and(cpuacct.add_task(pid))
+                .and(pids.add_task(pid))
+                .and(freezer.add_task(pid))
+                .and(blkio.add_task(pid))
+                .and(cpu.as_ref().map_or(Ok(()), |cpu| cpu.add_task(pid))),
+            Cgroups::V2 { cgroup, .. } => cgroup.set_value("cgroup.procs", pid.as_raw()),
+        }
+    }
+
+    /// Returns ids of all threads in the group.
+    pub fn tasks(&self) -> io::Result<Vec<Pid>> {
+        match self {
+            Cgroups::V1 { freezer, .. } => freezer.get_tasks(),
+            Cgroups::V2 { cgroup, .. } => Ok(cgroup
+                .get_raw_value("cgroup.threads")?
+                .split_whitespace()
+                .filter_map(|tid| tid.parse().ok())
+                .map(Pid::from_raw)
+                .collect()),
+        }
+    }
+
+    /// Returns the peak memory usage. Without `include_kernel` the kernel memory is
+    /// subtracted from it, which is an estimate since the peaks may not coincide.
+    pub fn max_memory_usage(&self, include_kernel: bool) -> io::Result<u64> {
+        match self {
+            // Kernel memory is charged to `memory.usage_in_bytes` as well, adding
+            // `memory.kmem.max_usage_in_bytes` to it counts it twice.
+            Cgroups::V1 { memory, .. } => {
+                let usage = memory.get_value::<u64>("memory.max_usage_in_bytes")?;
+                if include_kernel {
+                    return Ok(usage);
+                }
+                let kmem = memory.get_value::<u64>("memory.kmem.max_usage_in_bytes")?;
+                Ok(usage.saturating_sub(kmem))
+            }
+            // Kernel memory is always accounted in the unified hierarchy.
+            // `memory.peak` is available since Linux 5.19.
+            Cgroups::V2 { cgroup, .. } => {
+                let usage = cgroup
+                    .get_value::<u64>("memory.peak")
+                    .or_else(|_| cgroup.get_value::<u64>("memory.current"))?;
+                if include_kernel {
+                    return Ok(usage);
+                }
+                // The `kernel` key is available since Linux 5.18.
+                let kmem = get_keyed_value(cgroup, "memory.stat", "kernel").or_else(|_| {
+                    Ok::<_, io::Error>(
+                        get_keyed_value(cgroup, "memory.stat", "kernel_stack")?
+                            + get_keyed_value(cgroup, "memory.stat", "slab")?,
+                    )
+                })?;
+                Ok(usage.saturating_sub(kmem))
+            }
+        }
+    }
+
+    pub fn timers(&self) -> io::Result<GroupTimers> {
+        match self {
+            Cgroups::V1 { cpuacct, .. } => Ok(GroupTimers {
+                total_user_time: Duration::from_nanos(cpuacct.get_value("cpuacct.usage_user")?),
+                total_kernel_time: Duration::from_nanos(cpuacct.get_value("cpuacct.usage_sys")?),
+                cpu_counter: None,
+            }),
+            Cgroups::V2 { cgroup, .. } => Ok(GroupTimers {
+                total_user_time: Duration::from_micros(get_keyed_value(
+                    cgroup,
+                    "cpu.stat",
+                    "user_usec",
+                )?),
+                total_kernel_time: Duration::from_micros(get_keyed_value(
+                    cgroup,
+                    "cpu.stat",
+                    "system_usec",
+                )?),
+                cpu_counter: None,
+            }),
+        }
+    }
+
+    /// Returns the I/O of the group on block devices. Writes are usually attributed
+    /// to the group only if they bypass the page cache on the legacy hierarchy.
+    pub fn disk_io(&self) -> io::Result<DiskIo> {
+        let mut disk_io = DiskIo::default();
+        match self {
+            Cgroups::V1 { blkio, .. } => {
+                // Lines are `<major>:<minor> <operation> <value>` and a final `Total <value>`.
+                let bytes = blkio.get_raw_value("blkio.throttle.io_service_bytes")?;
+                let ops = blkio.get_raw_value("blkio.throttle.io_serviced")?;
+                for (file, read, write) in [
+                    (bytes, &mut disk_io.bytes_read, &mut disk_io.bytes_written),
+                    (ops, &mut disk_io.reads, &mut disk_io.writes),
+                ] {
+                    for line in file.lines() {
+                        match line.split_whitespace().collect::<Vec<_>>()[..] {
+                            [_, "Read", v] => *read += v.parse::<u64>().unwrap_or(0),
+                            [_, "Write", v] => *write += v.parse::<u64>().unwrap_or(0),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            Cgroups::V2 { cgroup, .. } => {
+                // `io.stat` is missing unless the io controller is enabled.
+                let stat = match cgroup.get_raw_value("io.stat") {
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(disk_io),
+                    stat => stat?,
+                };
+                // Lines are `<major>:<minor> rbytes=<value> wbytes=<value> rios=<value> ...`.
+                for (key, value) in stat.split_whitespace().filter_map(|kv| kv.split_once('=')) {
+                    let value = value.parse::<u64>().unwrap_or(0);
+                    match key {
+                        "rbytes" => disk_io.bytes_read += value,
+                        "wbytes" => disk_io.bytes_written += value,
+                        "rios" => disk_io.reads += value,
+                        "wios" => disk_io.writes += value,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(disk_io)
+    }
+
+    pub fn set_os_limit(&mut self, limit: OsLimit, value: u64) -> io::Result<()> {
+        match (self, limit) {
+            // A cgroup of the cpu controller is scheduled as a whole, which changes how the CPU
+            // is shared with the supervisor, so it's only used for programs with a quota.
+            (
+                Cgroups::V1 {
+                    cpuacct, cpu, name, ..
+                },
+                OsLimit::CpuQuota,
+            ) => {
+                if cpu.is_none() && !Path::new("/sys/fs/cgroup/cpuacct/sp/cpu.shares").exists() {
+                    let cgroup = Cgroup::new(&CgroupName::new(name), "cpu/sp");
+                    cgroup.create()?;
+                    *cpu = Some(cgroup);
+                }
+                let cpu = cpu.as_ref().unwrap_or(cpuacct);
+                cpu.set_value("cpu.cfs_period_us", CPU_PERIOD_US)?;
+                cpu.set_value("cpu.cfs_quota_us", value)
+            }
+            // The cpu controller is enabled for task cgroups once, when their parent is set up.
+            (Cgroups::V2 { cgroup, path }, OsLimit::CpuQuota) => {
+                if !path.join("cpu.max").exists() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "The CPU quota requires the cpu controller to be enabled for task cgroups",
+                    ));
+                }
+                cgroup.set_raw_value("cpu.max", format!("{} {}", value, CPU_PERIOD_US))
+            }
+            (Cgroups::V1 { name, .. }, OsLimit::DiskReadBandwidth) => set_device_limits(
+                &Path::new("/sys/fs/cgroup/blkio/sp").join(name),
+                "blkio.throttle.read_bps_device",
+                "",
+                value,
+            ),
+            (Cgroups::V1 { name, .. }, OsLimit::DiskWriteBandwidth) => set_device_limits(
+                &Path::new("/sys/fs/cgroup/blkio/sp").join(name),
+                "blkio.throttle.write_bps_device",
+                "",
+                value,
+            ),
+            (Cgroups::V2 { path, .. }, OsLimit::DiskReadBandwidth) => {
+                set_device_limits(path, "io.max", "rbps=", value)
+            }
+            (Cgroups::V2 { path, .. }, OsLimit::DiskWriteBandwidth) => {
+                set_device_limits(path, "io.max", "wbps=", value)
+            }
+            (Cgroups::V1 { memory, .. }, OsLimit::Memory) => {
+                memory.set_value("memory.limit_in_bytes", value)
+            }
+            (Cgroups::V1 { pids, .. }, OsLimit::ActiveProcess) => pids.set_value("pids.max", value),
+            // The limit of memory and swap combined, which can't be lower than the memory limit.
+            // `memory.memsw.*` files are missing unless the kernel accounts swap.
+            (Cgroups::V1 { memory, .. }, OsLimit::Swap) => {
+                let mem_limit = memory.get_value::<u64>("memory.limit_in_bytes")?;
+                if is_memory_unlimited(mem_limit) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "The legacy cgroup hierarchy can only limit swap along with the memory of a group",
+                    ));
+                }
+                let limit = mem_limit.saturating_add(value).min(i64::MAX as u64);
+                memory.set_value("memory.memsw.limit_in_bytes", limit)
+            }
+            // There's no throttling in the legacy hierarchy, the soft limit
+            // makes the kernel reclaim the memory of the group first.
+            (Cgroups::V1 { memory, .. }, OsLimit::MemoryHigh) => {
+                memory.set_value("memory.soft_limit_in_bytes", value)
+            }
+            (Cgroups::V2 { cgroup, .. }, OsLimit::Memory) => cgroup.set_value("memory.max", value),
+            (Cgroups::V2 { cgroup, .. }, OsLimit::ActiveProcess) => {
+                cgroup.set_value("pids.max", value)
+            }
+            (Cgroups::V2 { cgroup, .. }, OsLimit::Swap) => {
+                cgroup.set_value("memory.swap.max", value)
+            }
+            (Cgroups::V2 { cgroup, .. }, OsLimit::MemoryHigh) => {
+                cgroup.set_value("memory.high", value)
+            }
+        }
+    }
+
+    /// Returns the number of times the memory usage has hit the limit.
+    /// Usually the kernel reclaims memory then and the allocation succeeds.
+    pub fn memory_limit_hits(&self) -> io::Result<u64> {
+        match self {
+            Cgroups::V1 { memory, .. } => memory.get_value::<u64>("memory.failcnt"),
+            Cgroups::V2 { cgroup, .. } => get_keyed_value(cgroup, "memory.events", "max"),
+        }
+    }
+
+    /// Returns the number of tasks killed by the OOM killer, 0 if the kernel doesn't
+    /// count them.
+    pub fn oom_kills(&self) -> io::Result<u64> {
+        Ok(self.oom_kill_counter()?.unwrap_or(0))
+    }
+
+    fn oom_kill_counter(&self) -> io::Result<Option<u64>> {
+        match self {
+            // `oom_kill` is available since Linux 4.13.
+            Cgroups::V1 { memory, .. } => {
+                match get_keyed_value(memory, "memory.oom_control", "oom_kill") {
+                    Ok(kills) => Ok(Some(kills)),
+                    Err(ref e) if e.kind() == io::ErrorKind::InvalidData => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+            Cgroups::V2 { cgroup, .. } => {
+                get_keyed_value(cgroup, "memory.events", "oom_kill").map(Some)
+            }
+        }
+    }
+
+    pub fn is_os_limit_hit(&self, limit: OsLimit) -> io::Result<bool> {
+        match (self, limit) {
+            // The limit is only exceeded if the kernel failed to reclaim enough memory.
+            // Without the OOM kill counter every hit of the limit is considered fatal.
+            (_, OsLimit::Memory) => match self.oom_kill_counter()? {
+                Some(kills) => Ok(kills > 0),
+                None => Ok(self.memory_limit_hits()? > 0),
+            },
+            (Cgroups::V1 { pids, .. }, OsLimit::ActiveProcess) => {
+                Ok(pids.get_raw_value("pids.events")? != "max 0\n")
+            }
+            (Cgroups::V2 { cgroup, .. }, OsLimit::ActiveProcess) => {
+                Ok(get_keyed_value(cgroup, "pids.events", "max")? > 0)
+            }
+            (Cgroups::V1 { memory, .. }, OsLimit::Swap) => {
+                Ok(memory.get_value::<u64>("memory.memsw.failcnt")? > 0)
+            }
+            (Cgroups::V2 { cgroup, .. }, OsLimit::Swap) => {
+                Ok(get_keyed_value(cgroup, "memory.swap.events", "max")? > 0)
+            }
+            // The soft limit of the legacy hierarchy has no counter.
+            (Cgroups::V1 { .. }, OsLimit::MemoryHigh) => Ok(false),
+            // Throttled I/O and CPU time are delayed and never fail.
+            (_, OsLimit::DiskReadBandwidth)
+            | (_, OsLimit::DiskWriteBandwidth)
+            | (_, OsLimit::CpuQuota) => Ok(false),
+            (Cgroups::V2 { cgroup, .. }, OsLimit::MemoryHigh) => {
+                Ok(get_keyed_value(cgroup, "memory.events", "high")? > 0)
+            }
+        }
+    }
+
+    /// Stops every task in the group and waits until all of them are stopped.
+    pub fn freeze(&self) -> io::Result<()> {
+        match self {
+            Cgroups::V1 { freezer, .. } => {
+                freezer.set_raw_value("freezer.state", "FROZEN")?;
+                while freezer.get_raw_value("freezer.state")?.trim() == "FREEZING" {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+            Cgroups::V2 { cgroup, .. } => {
+                cgroup.set_raw_value("cgroup.freeze", "1")?;
+                while get_keyed_value(cgroup, "cgroup.events", "frozen")? == 0 {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn thaw(&self) -> io::Result<()> {
+        match self {
+            Cgroups::V1 { freezer, .. } => freezer.set_raw_value("freezer.state", "THAWED"),
+            Cgroups::V2 { cgroup, .. } => cgroup.set_raw_value("cgroup.freeze", "0"),
+        }
+    }
+
+    /// Returns the files to watch for changes of the group state: OS limit hits and emptiness.
+    /// Only files of the unified hierarchy support notifications.
+    pub fn event_files(&self) -> Option<Vec<PathBuf>> {
+        match self {
+            Cgroups::V1 { .. } => None,
+            Cgroups::V2 { path, .. } => Some(
+                ["memory.events", "pids.events", "cgroup.events"]
+                    .iter()
+                    .map(|file| path.join(file))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Sends the signal to every task in the group.
+    pub fn send_signal(&self, signal: Signal) -> io::Result<()> {
+        match self {
+            Cgroups::V1 { freezer, .. } => freezer.send_signal_to_all_tasks(signal).map(|_| ()),
+            Cgroups::V2 { .. } => {
+                for pid in self.tasks()? {
+                    nix::sys::signal::kill(pid, signal).ok();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Kills every task in the group.
+    pub fn kill_all(&self) -> io::Result<()> {
+        match self {
+            Cgroups::V1 { freezer, .. } => {
+                self.freeze()?;
+                freezer.send_signal_to_all_tasks(Signal::SIGKILL)?;
+            }
+            Cgroups::V2 { cgroup, .. } => {
+                // `cgroup.kill` is available since Linux 5.14.
+                if cgroup.set_raw_value("cgroup.kill", "1").is_ok() {
+                    return Ok(());
+                }
+                self.freeze()?;
+                for pid in self.tasks()? {
+                    nix::sys::signal::kill(pid, Signal::SIGKILL).ok();
+                }
+            }
+        }
+        self.thaw()
+    }
+
+    /// Removes the cgroups of the group, which fails for the ones with tasks in them.
+    pub fn remove(&self) {
+        match self {
+            Cgroups::V1 {
+                memory,
+                cpuacct,
+                pids,
+                freezer,
+                blkio,
+                cpu,
+                ..
+            } => {
+                freezer.remove().ok();
+                memory.remove().ok();
+                cpuacct.remove().ok();
+                pids.remove().ok();
+                blkio.remove().ok();
+                if let Some(cpu) = cpu {
+                    cpu.remove().ok();
+                }
+            }
+            Cgroups::V2 { cgroup, .. } => {
+                cgroup.remove().ok();
+            }
+        }
+    }
+}
+
+impl Drop for Cgroups {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// Sets a limit of the form `<major>:<minor> <prefix><value>` in the cgroup `dir` for every
+/// block device that can be throttled.
+fn set_device_limits(dir: &Path, file: &str, prefix: &str, value: u64) -> io::Result<()> {
+    let path = dir.join(file);
+    if !path.exists() {
+        let controller = file.split('.').next().unwrap_or(file);
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Disk bandwidth can't be limited, {} is missing. Is the {} controller enabled?",
+                path.display(),
+                controller
+            ),
+        ));
+    }
+    for entry in fs::read_dir("/sys/block")? {
+        let dev = fs::read_to_string(entry?.path().join("dev"))?;
+        match fs::write(&path, format!("{} {}{}", dev.trim(), prefix, value)) {
+            // Devices without a request queue, e.g. unused device-mapper ones, are rejected.
+            Err(ref e)
+                if e.raw_os_error() == Some(libc::EINVAL)
+                    || e.raw_os_error() == Some(libc::ENODEV) => {}
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Applies the `flock` operation to the directory, which stays locked while the returned
+/// file is open.
+pub fn lock_dir(dir: &Path, operation: libc::c_int) -> io::Result<File> {
+    let dir = File::open(dir)?;
+    if unsafe { libc::flock(dir.as_raw_fd(), operation) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(dir)
+}
+
+/// Returns `true` if the system is booted with the unified hierarchy only.
+pub fn is_unified_hierarchy() -> bool {
+    Path::new("/sys/fs/cgroup/cgroup.controllers").exists()
+}
+
+/// The leaf the spawner moves itself into when it sets up a delegated cgroup subtree.
+pub const SUPERVISOR: &str = "supervisor";
+
+/// Returns the parent of task cgroups in the unified hierarchy. It is either `/sp`, created by
+/// `create_cgroups.sh`, or the cgroup of the spawner itself, provided that it is delegated
+/// to the current user, e.g. by `systemd-run --user --scope -p Delegate=yes`.
+pub fn unified_parent() -> Result<String> {
+    if Path::new("/sys/fs/cgroup/sp").is_dir() {
+        return Ok(String::from("sp"));
+    }
+    delegated_subtree()
+        .map_err(|e| Error::from(format!("Cannot use delegated cgroup subtree: {}", e)))
+}
+
+fn delegated_subtree() -> io::Result<String> {
+    let path = current_cgroup()?;
+    if Path::new(&path).ends_with(SUPERVISOR) {
+        // The spawner has already moved itself into a leaf.
+        let parent = path.len() - SUPERVISOR.len();
+        return Ok(path[..parent].trim_end_matches('/').to_string());
+    }
+
+    let parent = Cgroup::new(&CgroupName::new(""), &path);
+    let available = parent.get_raw_value("cgroup.controllers")?;
+    let missing = ["memory", "pids"]
+        .iter()
+        .filter(|c| !available.split_whitespace().any(|a| a == **c))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(io::Error::other(format!(
+            "Controllers are not delegated to /{}: {}",
+            path,
+            missing.join(", ")
+        )));
+    }
+
+    // Controllers can't be enabled for a cgroup with processes in it,
+    // so the spawner moves itself into a leaf of its own.
+    let supervisor = Cgroup::new(&CgroupName::new(SUPERVISOR), &path);
+    if let Err(e) = supervisor.create() {
+        if e.kind() != io::ErrorKind::AlreadyExists {
+            return Err(e);
+        }
+    }
+    supervisor.set_value("cgroup.procs", 0)?;
+    parent.set_raw_value("cgroup.subtree_control", "+memory +pids")?;
+    // The io and cpu controllers are optional, disk I/O is reported as zero
+    // without the former and the CPU quota is unavailable without the latter.
+    for controller in ["io", "cpu"].iter() {
+        if available.split_whitespace().any(|c| c == *controller) {
+            parent.set_raw_value("cgroup.subtree_control", format!("+{}", controller))?;
+        }
+    }
+    Ok(path)
+}
+
+/// Returns the path of the calling process' cgroup relative to the unified hierarchy root.
+fn current_cgroup() -> io::Result<String> {
+    fs::read_to_string("/proc/self/cgroup")?
+        .lines()
+        .filter_map(|line| line.strip_prefix("0::"))
+        .map(|path| path.trim_start_matches('/').to_string())
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "Process is not in the unified hierarchy",
+            )
+        })
+}
+
+fn create_cgroup(name: &str, subsystem: &str) -> Result<Cgroup> {
+    let cgroup = Cgroup::new(&CgroupName::new(name), subsystem);
+    cgroup.create().map_err(|e| {
+        Error::from(format!(
+            "Cannot create cgroup /{}/{}: {}",
+            subsystem, name, e
+        ))
+    })?;
+    Ok(cgroup)
+}
+
+/// Checks whether `memory.limit_in_bytes` holds the default value, which is the
+/// largest signed number rounded down to the page size.
+fn is_memory_unlimited(limit: u64) -> bool {
+    let page_size = sysconf(SysconfVar::PAGE_SIZE)
+        .ok()
+        .and_then(|size| size)
+        .unwrap_or(4096) as u64;
+    limit > i64::MAX as u64 - page_size
+}
+
+/// Reads a value from a flat keyed file such as `memory.events` or `cpu.stat`.
+fn get_keyed_value(cgroup: &Cgroup, file: &str, key: &str) -> io::Result<u64> {
+    cgroup
+        .get_raw_value(file)?
+        .lines()
+        .filter_map(|line| {
+            let mut kv = line.split_whitespace();
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) if k == key => v.parse().ok(),
+                _ => None,
+            }
+        })
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Key '{}' is missing in {}", key, file),
+            )
+        })
+}
@@ -0,0 +1,277 @@
+use crate::sys::unix::cgroup::Cgroups;
+use crate::sys::unix::pidfd::PidFd;
+use crate::Result;
+
+use nix::errno::Errno;
+use nix::libc::{self, itimerspec, timespec};
+use nix::sys::epoll::{
+    epoll_create1, epoll_ctl, epoll_wait, EpollCreateFlags, EpollEvent, EpollFlags, EpollOp,
+};
+use nix::sys::eventfd::{eventfd, EfdFlags};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use nix::unistd::{close, read, write, Pid};
+
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+use std::time::Duration;
+
+/// Notifications about a process and its group delivered through a single epoll instance.
+/// A pidfd becomes readable once the process exits, inotify reports modifications of the
+/// `*.events` files of a unified cgroup and a timerfd expires when `wait` times out.
+pub struct Events {
+    epoll: RawFd,
+    timer: RawFd,
+    pidfd: Option<PidFd>,
+    inotify: Option<Inotify>,
+    seccomp_listener: Option<RawFd>,
+    waker: Option<RawFd>,
+}
+
+/// An eventfd that becomes readable once `wake` is called, until the `Events`
+/// watching it handle the notification.
+pub struct Waker(RawFd);
+
+/// Waits for the notifications of several `Events` at once. An epoll instance is readable
+/// while any of the files it watches is, so the epoll of every `Events` is watched here.
+pub struct Poller {
+    epoll: RawFd,
+}
+
+const EXIT: u64 = 0;
+const TIMER: u64 = 1;
+const CGROUP: u64 = 2;
+const SECCOMP: u64 = 3;
+const WAKER: u64 = 4;
+
+impl Events {
+    pub fn new(pid: Pid, seccomp_listener: Option<RawFd>, cgroups: &Cgroups) -> Result<Self> {
+        let mut events = Self {
+            epoll: epoll_create1(EpollCreateFlags::EPOLL_CLOEXEC)?,
+            timer: -1,
+            pidfd: None,
+            inotify: None,
+            seccomp_listener: None,
+            waker: None,
+        };
+
+        let flags = libc::TFD_CLOEXEC | libc::TFD_NONBLOCK;
+        events.timer =
+            Errno::result(unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, flags) })?;
+        events.add(events.timer, TIMER)?;
+
+        // pidfd_open is available since Linux 5.3, the exit is polled on older kernels.
+        if let Ok(pidfd) = PidFd::open(pid) {
+            events.add(pidfd.as_raw_fd(), EXIT)?;
+            events.pidfd = Some(pidfd);
+        }
+
+        // Files of the legacy hierarchy don't support notifications.
+        if let Some(files) = cgroups.event_files() {
+            let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+            let watched = files
+                .iter()
+                .all(|file| inotify.add_watch(file, AddWatchFlags::IN_MODIFY).is_ok());
+            if watched {
+                events.add(inotify.as_raw_fd(), CGROUP)?;
+                events.inotify = Some(inotify);
+            } else {
+                let _ = close(inotify.as_raw_fd());
+            }
+        }
+
+        if let Some(fd) = seccomp_listener {
+            events.add(fd, SECCOMP)?;
+            events.seccomp_listener = Some(fd);
+        }
+        Ok(events)
+    }
+
+    /// Returns `true` if the exit of the process is yet to be notified.
+    pub fn is_exit_notified(&self) -> bool {
+        self.pidfd.is_some()
+    }
+
+    /// Returns `true` if changes of OS limit counters are notified.
+    pub fn is_os_limit_notified(&self) -> bool {
+        self.inotify.is_some()
+    }
+
+    /// Notifies about calls of `waker.wake`, the waker must outlive `self`.
+    pub fn watch(&mut self, waker: &Waker) -> Result<()> {
+        self.add(waker.0, WAKER)?;
+        self.waker = Some(waker.0);
+        Ok(())
+    }
+
+    /// Returns `true` if a waker is watched.
+    pub fn is_waker_notified(&self) -> bool {
+        self.waker.is_some()
+    }
+
+    /// Waits for a notification or until the timeout expires.
+    /// Returns `false` if the timeout has expired and nothing was notified.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<bool> {
+        self.set_timer(timeout)?;
+        self.handle_ready(-1)
+    }
+
+    /// Handles pending notifications without blocking.
+    pub fn poll(&mut self) -> Result<bool> {
+        self.handle_ready(0)
+    }
+
+    fn handle_ready(&mut self, timeout_ms: isize) -> Result<bool> {
+        let mut ready = [EpollEvent::empty(); 5];
+        let num_ready = epoll_wait_retry(self.epoll, &mut ready, timeout_ms)?;
+
+        let mut notified = false;
+        for event in ready[..num_ready].iter() {
+            match event.data() {
+                EXIT => {
+                    // The pidfd stays readable once the process has exited.
+                    if let Some(pidfd) = self.pidfd.take() {
+                        self.remove(pidfd.as_raw_fd())?;
+                    }
+                    notified = true;
+                }
+                TIMER => {
+                    let _ = read(self.timer, &mut [0; 8]);
+                }
+                CGROUP => {
+                    if let Some(ref inotify) = self.inotify {
+                        while inotify.read_events().is_ok_and(|e| !e.is_empty()) {}
+                    }
+                    notified = true;
+                }
+                SECCOMP => {
+                    // The listener hangs up once all processes using the filter are gone.
+                    let hangup = EpollFlags::EPOLLHUP | EpollFlags::EPOLLERR;
+                    if event.events().intersects(hangup) {
+                        if let Some(fd) = self.seccomp_listener.take() {
+                            self.remove(fd)?;
+                        }
+                    }
+                    notified = true;
+                }
+                WAKER => {
+                    if let Some(fd) = self.waker {
+                        let _ = read(fd, &mut [0; 8]);
+                    }
+                    notified = true;
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(notified)
+    }
+
+    fn set_timer(&self, timeout: Option<Duration>) -> Result<()> {
+        let zero = timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        let value = match timeout {
+            // A zero value disarms the timer.
+            Some(t) if t.as_nanos() == 0 => timespec {
+                tv_sec: 0,
+                tv_nsec: 1,
+            },
+            Some(t) => timespec {
+                tv_sec: t.as_secs() as libc::time_t,
+                tv_nsec: t.subsec_nanos() as libc::c_long,
+            },
+            None => zero,
+        };
+        let spec = itimerspec {
+            it_interval: zero,
+            it_value: value,
+        };
+        Errno::result(unsafe { libc::timerfd_settime(self.timer, 0, &spec, ptr::null_mut()) })?;
+        Ok(())
+    }
+
+    fn add(&self, fd: RawFd, token: u64) -> Result<()> {
+        let mut event = EpollEvent::new(EpollFlags::EPOLLIN, token);
+        epoll_ctl(self.epoll, EpollOp::EpollCtlAdd, fd, &mut event)?;
+        Ok(())
+    }
+
+    fn remove(&self, fd: RawFd) -> Result<()> {
+        epoll_ctl(self.epoll, EpollOp::EpollCtlDel, fd, None)?;
+        Ok(())
+    }
+}
+
+impl Poller {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            epoll: epoll_create1(EpollCreateFlags::EPOLL_CLOEXEC)?,
+        })
+    }
+
+    pub fn add(&mut self, events: &Events) -> Result<()> {
+        let mut event = EpollEvent::new(EpollFlags::EPOLLIN, events.epoll as u64);
+        epoll_ctl(self.epoll, EpollOp::EpollCtlAdd, events.epoll, &mut event)?;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, events: &Events) -> Result<()> {
+        epoll_ctl(self.epoll, EpollOp::EpollCtlDel, events.epoll, None)?;
+        Ok(())
+    }
+
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<()> {
+        let timeout_ms = match timeout {
+            Some(t) => t.as_micros().div_ceil(1000).min(isize::MAX as u128) as isize,
+            None => -1,
+        };
+        let mut ready = [EpollEvent::empty(); 16];
+        epoll_wait_retry(self.epoll, &mut ready, timeout_ms)?;
+        Ok(())
+    }
+}
+
+impl Waker {
+    pub fn new() -> Result<Self> {
+        let flags = EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK;
+        Ok(Self(eventfd(0, flags)?))
+    }
+
+    pub fn wake(&self) {
+        // The counter can't overflow before it's read, since a single wakeup is enough.
+        let _ = write(self.0, &1u64.to_ne_bytes());
+    }
+}
+
+impl Drop for Waker {
+    fn drop(&mut self) {
+        let _ = close(self.0);
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        let _ = close(self.epoll);
+    }
+}
+
+impl Drop for Events {
+    fn drop(&mut self) {
+        if let Some(ref inotify) = self.inotify {
+            let _ = close(inotify.as_raw_fd());
+        }
+        if self.timer != -1 {
+            let _ = close(self.timer);
+        }
+        let _ = close(self.epoll);
+    }
+}
+
+fn epoll_wait_retry(epoll: RawFd, ready: &mut [EpollEvent], timeout_ms: isize) -> Result<usize> {
+    loop {
+        match epoll_wait(epoll, ready, timeout_ms) {
+            Err(nix::Error::Sys(Errno::EINTR)) => continue,
+            result => return Ok(result?),
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use crate::sys::unix::cgroup::{self, is_unified_hierarchy, Cgroups, SUPERVISOR};
+use crate::{Error, Result};
+
+use nix::libc;
+use nix::unistd::{access, chown, AccessFlags, Gid, Uid};
+
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io;
+use std::mem;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Controllers of the legacy hierarchy task cgroups are created in. The `cpu` controller is
+/// only needed for the CPU quota and is often mounted together with `cpuacct`.
+const V1_SUBSYSTEMS: [&str; 6] = ["memory", "cpuacct", "cpu", "pids", "freezer", "blkio"];
+
+/// Controllers enabled for task cgroups of the unified hierarchy, `io` and `cpu` are optional.
+const V2_CONTROLLERS: [&str; 4] = ["memory", "pids", "io", "cpu"];
+
+/// The time the tasks of a group are given to die after being killed.
+pub const KILL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Creates the `sp` cgroups that task cgroups are created in and makes them owned by the user,
+/// so that the spawner can run without root privileges. Returns a description of every change.
+pub fn setup_cgroups(uid: u32, gid: u32) -> Result<Vec<String>> {
+    let (uid, gid) = (Uid::from_raw(uid), Gid::from_raw(gid));
+    let root = Path::new("/sys/fs/cgroup");
+    let mut changes = Vec::new();
+
+    if is_unified_hierarchy() {
+        let available = fs::read_to_string(root.join("cgroup.controllers"))?;
+        let controllers = V2_CONTROLLERS
+            .iter()
+            .cloned()
+            .filter(|c| available.split_whitespace().any(|a| a == *c))
+            .collect::<Vec<_>>();
+        if let Some(c) = ["memory", "pids"].iter().find(|c| !controllers.contains(c)) {
+            return Err(Error::from(format!(
+                "The {} controller is not available",
+                c
+            )));
+        }
+        let sp = root.join("sp");
+        enable_controllers(root, &controllers, &mut changes)?;
+        create_dir(&sp, &mut changes)?;
+        enable_controllers(&sp, &controllers, &mut changes)?;
+        set_owner(&sp, uid, gid, &mut changes)?;
+        return Ok(changes);
+    }
+
+    for subsystem in V1_SUBSYSTEMS.iter() {
+        let mount = root.join(subsystem);
+        if !mount.is_dir() {
+            // The CPU quota is unavailable then, which is reported once it's set.
+            if *subsystem == "cpu" {
+                continue;
+            }
+            return Err(Error::from(format!(
+                "The {} controller is not mounted",
+                subsystem
+            )));
+        }
+        let sp = mount.join("sp");
+        create_dir(&sp, &mut changes)?;
+        set_owner(&sp, uid, gid, &mut changes)?;
+    }
+    Ok(changes)
+}
+
+/// Kills the tasks of the cgroups left behind by spawners that have exited without removing
+/// them, e.g. after a crash, and removes these cgroups. Cgroups of running spawners are locked,
+/// so they are left alone. Returns a description of every removed cgroup.
+pub fn cleanup_cgroups() -> Result<Vec<String>> {
+    let root = Path::new("/sys/fs/cgroup");
+    // The parent of task cgroups, the directories they are in and the one to lock.
+    let parents = if is_unified_hierarchy() {
+        unified_parents(root)
+            .into_iter()
+            .map(|parent| {
+                let dir = root.join(&parent);
+                (parent, vec![dir.clone()], dir)
+            })
+            .collect()
+    } else {
+        let dirs = V1_SUBSYSTEMS
+            .iter()
+            .map(|subsystem| root.join(subsystem).join("sp"))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        vec![(String::new(), dirs, root.join("freezer/sp"))]
+    };
+
+    let mut changes = Vec::new();
+    for (parent, parent_dirs, lock_dir) in parents {
+        for (name, cgroups, lock) in lock_orphans(&parent, &parent_dirs, &lock_dir)? {
+            let num_tasks = match lock {
+                Some(_) => kill_tasks(&cgroups)?,
+                None => 0,
+            };
+            // Dropping the cgroups removes them.
+            drop(cgroups);
+            drop(lock);
+
+            let left = parent_dirs
+                .iter()
+                .map(|dir| dir.join(&name))
+                .filter(|dir| dir.exists())
+                .collect::<Vec<_>>();
+            if let Some(dir) = left.first() {
+                return Err(Error::from(format!("Cannot remove {}", dir.display())));
+            }
+            changes.push(format!(
+                "Removed the orphaned cgroup {}, killed {} tasks",
+                name, num_tasks
+            ));
+        }
+    }
+    Ok(changes)
+}
+
+/// Returns the parents of task cgroups in the unified hierarchy without setting any of them up:
+/// `sp` and every delegated subtree of the current user a spawner has moved itself into
+/// the leaf of. The latter may outlive the spawner, e.g. a systemd scope with orphaned tasks.
+fn unified_parents(root: &Path) -> Vec<String> {
+    let mut parents = Vec::new();
+    if root.join("sp").is_dir() {
+        parents.push(String::from("sp"));
+    }
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        // Cgroups may be removed meanwhile.
+        let entries = match fs::read_dir(root.join(&dir)) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !entry.file_type().is_ok_and(|t| t.is_dir()) || name.starts_with("task_") {
+                continue;
+            }
+            if name == SUPERVISOR {
+                if access(&root.join(&dir), AccessFlags::W_OK).is_ok() {
+                    parents.push(dir.to_string_lossy().into_owned());
+                }
+            } else {
+                dirs.push(dir.join(name));
+            }
+        }
+    }
+    parents
+}
+
+/// Locks the task cgroups in `parent_dirs` that aren't locked by running spawners. The parent
+/// is locked meanwhile, so that cgroups that are being created are left alone.
+fn lock_orphans(
+    parent: &str,
+    parent_dirs: &[PathBuf],
+    lock_dir: &Path,
+) -> Result<Vec<(String, Cgroups, Option<File>)>> {
+    let _parent_lock = cgroup::lock_dir(lock_dir, libc::LOCK_EX)?;
+
+    let mut names = BTreeSet::new();
+    for dir in parent_dirs.iter() {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with("task_") && entry.file_type()?.is_dir() {
+                names.insert(name);
+            }
+        }
+    }
+
+    let mut orphans = Vec::new();
+    for name in names {
+        let cgroups = Cgroups::open(&name, parent);
+        let lock = match cgroups.lock() {
+            Ok(lock) => Some(lock),
+            Err(ref e) if e.raw_os_error() == Some(libc::EWOULDBLOCK) => {
+                // Dropping the cgroups of a running spawner would remove them.
+                mem::forget(cgroups);
+                continue;
+            }
+            // The cgroup was created only partially, so no tasks were added to it.
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(Error::from(e)),
+        };
+        orphans.push((name, cgroups, lock));
+    }
+    Ok(orphans)
+}
+
+/// Kills every task in the group and waits for them to die. Returns the number of tasks killed.
+pub fn kill_tasks(cgroups: &Cgroups) -> Result<usize> {
+    let num_tasks = cgroups.tasks()?.len();
+    if num_tasks == 0 {
+        return Ok(0);
+    }
+    cgroups.kill_all()?;
+    let start = Instant::now();
+    while !cgroups.tasks()?.is_empty() {
+        if start.elapsed() > KILL_TIMEOUT {
+            return Err(Error::from(format!(
+                "Cannot kill tasks of {}",
+                cgroups.path().display()
+            )));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    Ok(num_tasks)
+}
+
+fn create_dir(dir: &Path, changes: &mut Vec<String>) -> Result<()> {
+    if !dir.is_dir() {
+        fs::create_dir(dir)?;
+        changes.push(format!("Created {}", dir.display()));
+    }
+    Ok(())
+}
+
+fn enable_controllers(dir: &Path, controllers: &[&str], changes: &mut Vec<String>) -> Result<()> {
+    let file = dir.join("cgroup.subtree_control");
+    let enabled = fs::read_to_string(&file)?;
+    let missing = controllers
+        .iter()
+        .filter(|c| !enabled.split_whitespace().any(|e| e == **c))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let value = missing
+        .iter()
+        .map(|c| format!("+{}", c))
+        .collect::<Vec<_>>()
+        .join(" ");
+    fs::write(&file, value)?;
+    changes.push(format!(
+        "Enabled {} controllers for {}",
+        missing
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        dir.display()
+    ));
+    Ok(())
+}
+
+/// Changes the owner of the directory and the files in it. Task cgroups inside it,
+/// if any, are left as is.
+fn set_owner(dir: &Path, uid: Uid, gid: Gid, changes: &mut Vec<String>) -> Result<()> {
+    let mut paths = vec![dir.to_path_buf()];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+
+    let mut changed = false;
+    for path in paths.iter() {
+        let meta = fs::metadata(path)?;
+        if meta.uid() != uid.as_raw() || meta.gid() != gid.as_raw() {
+            chown::<PathBuf>(path, Some(uid), Some(gid))?;
+            changed = true;
+        }
+    }
+    if changed {
+        changes.push(format!(
+            "Changed the owner of {} to {}:{}",
+            dir.display(),
+            uid,
+            gid
+        ));
+    }
+    Ok(())
+}
@@ -1,6 +1,10 @@
-use nix::libc::{__u16, __u32, __u64, __u8, c_int, c_ushort};
+use nix::libc::{
+    __s32, __s64, __u16, __u32, __u64, __u8, c_char, c_int, c_long, c_short, c_uint, c_ulong,
+    c_ushort, IFNAMSIZ,
+};
 
 #[repr(C)]
+#[derive(Copy, Clone)]
 pub struct sock_filter {
     pub code: __u16,
     pub jt: __u8,
@@ -15,6 +19,7 @@ pub struct sock_fprog {
 }
 
 #[repr(C)]
+#[derive(Copy, Clone)]
 pub struct seccomp_data {
     pub nr: c_int,
     pub arch: __u32,
@@ -22,15 +27,59 @@ pub struct seccomp_data {
     pub args: [__u64; 6],
 }
 
+#[repr(C)]
+pub struct seccomp_notif {
+    pub id: __u64,
+    pub pid: __u32,
+    pub flags: __u32,
+    pub data: seccomp_data,
+}
+
+#[repr(C)]
+pub struct seccomp_notif_resp {
+    pub id: __u64,
+    pub val: __s64,
+    pub error: __s32,
+    pub flags: __u32,
+}
+
+// Only the part of `struct ifreq` used for SIOCGIFFLAGS/SIOCSIFFLAGS,
+// padded to the size of the union.
+#[repr(C)]
+pub struct ifreq_flags {
+    pub ifr_name: [c_char; IFNAMSIZ],
+    pub ifr_flags: c_short,
+    pub pad: [u8; 22],
+}
+
 pub const AUDIT_ARCH_I386: __u32 = 0x4000_0003;
 pub const AUDIT_ARCH_X86_64: __u32 = 0xC000_003E;
 
+pub const SECCOMP_RET_KILL_PROCESS: __u32 = 0x8000_0000;
 pub const SECCOMP_RET_KILL: __u32 = 0x0000_0000;
+pub const SECCOMP_RET_TRAP: __u32 = 0x0003_0000;
+pub const SECCOMP_RET_ERRNO: __u32 = 0x0005_0000;
+pub const SECCOMP_RET_USER_NOTIF: __u32 = 0x7fc0_0000;
+pub const SECCOMP_RET_LOG: __u32 = 0x7ffc_0000;
 pub const SECCOMP_RET_ALLOW: __u32 = 0x7fff_0000;
+pub const SECCOMP_RET_ACTION_FULL: __u32 = 0xffff_0000;
+pub const SECCOMP_RET_DATA: __u32 = 0x0000_ffff;
 
 pub const SECCOMP_MODE_FILTER: c_int = 2;
 
+pub const SECCOMP_SET_MODE_FILTER: c_uint = 1;
+pub const SECCOMP_GET_ACTION_AVAIL: c_uint = 2;
+pub const SECCOMP_FILTER_FLAG_NEW_LISTENER: c_ulong = 1 << 3;
+
+// _IOWR('!', 0, struct seccomp_notif)
+pub const SECCOMP_IOCTL_NOTIF_RECV: c_ulong = 0xc050_2100;
+// _IOWR('!', 1, struct seccomp_notif_resp)
+pub const SECCOMP_IOCTL_NOTIF_SEND: c_ulong = 0xc018_2101;
+
+pub const SECCOMP_USER_NOTIF_FLAG_CONTINUE: __u32 = 1;
+
 pub const BPF_LD: __u16 = 0x00;
+pub const BPF_ALU: __u16 = 0x04;
 pub const BPF_JMP: __u16 = 0x05;
 pub const BPF_RET: __u16 = 0x06;
 
@@ -39,5 +88,46 @@ pub const BPF_W: __u16 = 0x00;
 pub const BPF_ABS: __u16 = 0x20;
 
 // alu/jmp fields.
+pub const BPF_AND: __u16 = 0x50;
 pub const BPF_JEQ: __u16 = 0x10;
+pub const BPF_JGT: __u16 = 0x20;
+pub const BPF_JGE: __u16 = 0x30;
 pub const BPF_K: __u16 = 0x00;
+
+// Same numbers on x86 and x86_64.
+pub const SYS_PIDFD_SEND_SIGNAL: c_long = 424;
+pub const SYS_PIDFD_OPEN: c_long = 434;
+pub const SYS_PIDFD_GETFD: c_long = 438;
+
+// The first published version of `struct perf_event_attr` (PERF_ATTR_SIZE_VER0),
+// the bit fields are packed into `flags`.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct perf_event_attr {
+    pub type_: __u32,
+    pub size: __u32,
+    pub config: __u64,
+    pub sample_period: __u64,
+    pub sample_type: __u64,
+    pub read_format: __u64,
+    pub flags: __u64,
+    pub wakeup_events: __u32,
+    pub bp_type: __u32,
+    pub config1: __u64,
+}
+
+pub const PERF_TYPE_HARDWARE: __u32 = 0;
+pub const PERF_TYPE_SOFTWARE: __u32 = 1;
+pub const PERF_COUNT_HW_INSTRUCTIONS: __u64 = 1;
+pub const PERF_COUNT_SW_TASK_CLOCK: __u64 = 1;
+
+pub const PERF_FORMAT_TOTAL_TIME_ENABLED: __u64 = 1 << 0;
+pub const PERF_FORMAT_TOTAL_TIME_RUNNING: __u64 = 1 << 1;
+
+pub const PERF_ATTR_FLAG_DISABLED: __u64 = 1 << 0;
+pub const PERF_ATTR_FLAG_INHERIT: __u64 = 1 << 1;
+pub const PERF_ATTR_FLAG_EXCLUDE_KERNEL: __u64 = 1 << 5;
+pub const PERF_ATTR_FLAG_EXCLUDE_HV: __u64 = 1 << 6;
+pub const PERF_ATTR_FLAG_ENABLE_ON_EXEC: __u64 = 1 << 12;
+
+pub const PERF_FLAG_FD_CLOEXEC: c_ulong = 1 << 3;
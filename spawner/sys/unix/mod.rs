@@ -1,8 +1,18 @@
+mod capabilities;
+mod cgroup;
 pub mod error;
+mod events;
+mod hierarchy;
+mod namespaces;
+mod perf;
+mod pidfd;
 pub mod pipe;
 pub mod process;
 pub mod process_ext;
+mod seccomp;
 mod shared_mem;
+mod syscall_table;
+mod watchdog;
 
 #[allow(dead_code)]
 mod missing_decls;
@@ -0,0 +1,397 @@
+use crate::sys::unix::missing_decls::ifreq_flags;
+
+use nix::errno::Errno;
+use nix::libc::{self, c_char, c_short, ioctl, EIO, IFF_UP, SIOCGIFFLAGS, SIOCSIFFLAGS};
+use nix::mount::{mount, umount2, MntFlags, MsFlags};
+use nix::sched::{unshare, CloneFlags};
+use nix::sys::socket::{
+    recvmsg, sendmsg, socket, AddressFamily, ControlMessage, ControlMessageOwned, MsgFlags,
+    SockFlag, SockType, UnixCredentials,
+};
+use nix::sys::statvfs::{statvfs, FsFlags};
+use nix::sys::uio::IoVec;
+use nix::unistd::{chdir, close, getegid, geteuid, getgid, getuid, pivot_root, Pid};
+
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+
+const NONE: Option<&'static str> = None;
+
+#[derive(Clone, Debug)]
+enum MountKind {
+    Bind { source: PathBuf, writable: bool },
+    Tmpfs { size: Option<u64> },
+    Procfs,
+}
+
+#[derive(Clone, Debug)]
+struct Mount {
+    target: PathBuf,
+    kind: MountKind,
+}
+
+/// Describes the filesystem visible to a process.
+///
+/// The view is set up in a private mount namespace, so none of the mounts
+/// are visible outside of the process tree. Mount targets are resolved
+/// relative to the new root directory, if it is set, and to the host root otherwise.
+/// Mounts are made from the outermost target to the innermost one, and missing targets
+/// are created only inside of the new root or a tmpfs of the view, otherwise they would
+/// be left behind on the host.
+#[derive(Clone, Debug, Default)]
+pub struct FsView {
+    root: Option<PathBuf>,
+    mounts: Vec<Mount>,
+}
+
+impl FsView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `dir` the root directory of a process. The root is mounted read-only,
+    /// use [`bind_writable`] or [`tmpfs`] to provide writable locations.
+    ///
+    /// [`bind_writable`]: #method.bind_writable
+    /// [`tmpfs`]: #method.tmpfs
+    pub fn root<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.root = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Makes host file or directory `source` available at `target` in read-only mode.
+    pub fn bind_read_only<S, T>(&mut self, source: S, target: T) -> &mut Self
+    where
+        S: AsRef<Path>,
+        T: AsRef<Path>,
+    {
+        self.bind(source, target, false)
+    }
+
+    /// Makes host file or directory `source` available at `target` in read-write mode.
+    pub fn bind_writable<S, T>(&mut self, source: S, target: T) -> &mut Self
+    where
+        S: AsRef<Path>,
+        T: AsRef<Path>,
+    {
+        self.bind(source, target, true)
+    }
+
+    /// Mounts an empty private tmpfs at `target`. The size of the filesystem
+    /// is limited to `size` bytes if specified.
+    pub fn tmpfs<T: AsRef<Path>>(&mut self, target: T, size: Option<u64>) -> &mut Self {
+        self.mounts.push(Mount {
+            target: target.as_ref().to_path_buf(),
+            kind: MountKind::Tmpfs { size },
+        });
+        self
+    }
+
+    /// Mounts a procfs instance at `target`. The instance shows only the processes
+    /// of the PID namespace the view is entered from.
+    pub fn procfs<T: AsRef<Path>>(&mut self, target: T) -> &mut Self {
+        self.mounts.push(Mount {
+            target: target.as_ref().to_path_buf(),
+            kind: MountKind::Procfs,
+        });
+        self
+    }
+
+    fn bind<S, T>(&mut self, source: S, target: T, writable: bool) -> &mut Self
+    where
+        S: AsRef<Path>,
+        T: AsRef<Path>,
+    {
+        self.mounts.push(Mount {
+            target: target.as_ref().to_path_buf(),
+            kind: MountKind::Bind {
+                source: source.as_ref().to_path_buf(),
+                writable,
+            },
+        });
+        self
+    }
+}
+
+/// Network isolation level of a process.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NetworkIsolation {
+    /// Only the loopback interface is available.
+    Loopback,
+    /// Only the loopback interface is present and it's down, so no address is reachable.
+    Full,
+}
+
+/// Moves the calling process into a new user namespace, where the effective user and group
+/// are mapped to themselves. The process gets full capabilities inside of the namespace,
+/// so the other namespaces can be created without root privileges. Unless the user is root,
+/// the capabilities are lost on exec.
+pub fn enter_user_namespace() -> nix::Result<()> {
+    let uid = geteuid();
+    let gid = getegid();
+    unshare(CloneFlags::CLONE_NEWUSER)?;
+    // An unprivileged process can map only its own ids, and only after setgroups is denied.
+    write_proc_file("/proc/self/setgroups", "deny")?;
+    write_proc_file("/proc/self/uid_map", &format!("{} {} 1", uid, uid))?;
+    write_proc_file("/proc/self/gid_map", &format!("{} {} 1", gid, gid))
+}
+
+/// Moves the calling process into a new network namespace.
+pub fn enter_network_namespace(isolation: NetworkIsolation) -> nix::Result<()> {
+    unshare(CloneFlags::CLONE_NEWNET)?;
+    if isolation == NetworkIsolation::Loopback {
+        // Loopback interface of a fresh namespace is down.
+        bring_loopback_up()?;
+    }
+    Ok(())
+}
+
+/// Moves the calling process into a new mount namespace and sets up the filesystem view.
+pub fn enter_fs_view(view: &FsView) -> nix::Result<()> {
+    unshare(CloneFlags::CLONE_NEWNS)?;
+    // Make sure that none of the mounts below propagate back to the host.
+    mount(NONE, "/", NONE, MsFlags::MS_REC | MsFlags::MS_PRIVATE, NONE)?;
+
+    let root = view.root.as_ref().map_or(Path::new("/"), |r| r.as_path());
+    let mut root_submounts = Vec::new();
+    if view.root.is_some() {
+        // The new root must be a mount point for pivot_root.
+        mount(
+            Some(root),
+            root,
+            NONE,
+            MsFlags::MS_BIND | MsFlags::MS_REC,
+            NONE,
+        )?;
+        root_submounts = mount_points_under(root)?;
+    }
+
+    let mut mounts = view.mounts.iter().collect::<Vec<_>>();
+    mounts.sort_by_key(|m| m.target.components().count());
+    for (i, m) in mounts.iter().enumerate() {
+        let target = root.join(m.target.strip_prefix("/").unwrap_or(&m.target));
+        let can_create = view.root.is_some()
+            || mounts[..i].iter().any(|prev| match prev.kind {
+                MountKind::Tmpfs { .. } => m.target.starts_with(&prev.target),
+                _ => false,
+            });
+        match m.kind {
+            MountKind::Bind {
+                ref source,
+                writable,
+            } => {
+                create_mount_point(&target, source.is_dir(), can_create)?;
+                mount(
+                    Some(source.as_path()),
+                    &target,
+                    NONE,
+                    MsFlags::MS_BIND | MsFlags::MS_REC,
+                    NONE,
+                )?;
+                if !writable {
+                    for submount in mount_points_under(&target)? {
+                        remount_read_only(&submount)?;
+                    }
+                }
+            }
+            MountKind::Tmpfs { size } => {
+                create_mount_point(&target, true, can_create)?;
+                let data = match size {
+                    Some(size) => format!("mode=1777,size={}", size),
+                    None => String::from("mode=1777"),
+                };
+                mount(
+                    Some("tmpfs"),
+                    &target,
+                    Some("tmpfs"),
+                    MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                    Some(data.as_str()),
+                )?;
+            }
+            MountKind::Procfs => {
+                create_mount_point(&target, true, can_create)?;
+                mount(
+                    Some("proc"),
+                    &target,
+                    Some("proc"),
+                    MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+                    NONE,
+                )?;
+            }
+        }
+    }
+
+    if view.root.is_some() {
+        // Mounts of the view may cover the ones of the host directory, these are left as is.
+        let view_targets = view
+            .mounts
+            .iter()
+            .map(|m| root.join(m.target.strip_prefix("/").unwrap_or(&m.target)))
+            .collect::<Vec<_>>();
+        for submount in root_submounts {
+            if !view_targets.iter().any(|t| submount.starts_with(t)) {
+                remount_read_only(&submount)?;
+            }
+        }
+        // Stack the old root under the new one and detach it.
+        chdir(root)?;
+        pivot_root(".", ".")?;
+        umount2(".", MntFlags::MNT_DETACH)?;
+        chdir("/")?;
+    }
+    Ok(())
+}
+
+/// Sends `pid` over a unix socket with `SO_PASSCRED` enabled on the receiving end.
+/// The kernel translates the pid into the PID namespace of the receiver.
+pub fn send_pid(sock: RawFd, pid: Pid) -> nix::Result<()> {
+    let creds = UnixCredentials::from(libc::ucred {
+        pid: pid.as_raw(),
+        uid: getuid().as_raw(),
+        gid: getgid().as_raw(),
+    });
+    sendmsg(
+        sock,
+        &[IoVec::from_slice(&[0])],
+        &[ControlMessage::ScmCredentials(&creds)],
+        MsgFlags::empty(),
+        None,
+    )
+    .map(|_| ())
+}
+
+/// Receives a pid sent by [`send_pid`]. Returns `None` if the other end is closed.
+///
+/// [`send_pid`]: fn.send_pid.html
+pub fn recv_pid(sock: RawFd) -> nix::Result<Option<Pid>> {
+    let mut buf = [0u8; 1];
+    let mut cmsg = nix::cmsg_space!(UnixCredentials);
+    let msg = recvmsg(
+        sock,
+        &[IoVec::from_mut_slice(&mut buf)],
+        Some(&mut cmsg),
+        MsgFlags::empty(),
+    )?;
+    if msg.bytes == 0 {
+        return Ok(None);
+    }
+    Ok(msg
+        .cmsgs()
+        .filter_map(|cmsg| match cmsg {
+            ControlMessageOwned::ScmCredentials(creds) => Some(Pid::from_raw(creds.pid())),
+            _ => None,
+        })
+        .next())
+}
+
+fn bring_loopback_up() -> nix::Result<()> {
+    let sock = socket(
+        AddressFamily::Inet,
+        SockType::Datagram,
+        SockFlag::SOCK_CLOEXEC,
+        None,
+    )?;
+    let mut req: ifreq_flags = unsafe { mem::zeroed() };
+    for (dst, src) in req.ifr_name.iter_mut().zip(b"lo") {
+        *dst = *src as c_char;
+    }
+    let result = Errno::result(unsafe { ioctl(sock, SIOCGIFFLAGS, &mut req) }).and_then(|_| {
+        req.ifr_flags |= IFF_UP as c_short;
+        Errno::result(unsafe { ioctl(sock, SIOCSIFFLAGS, &req) })
+    });
+    close(sock)?;
+    result.map(|_| ())
+}
+
+fn remount_read_only(target: &Path) -> nix::Result<()> {
+    // Locked flags of the underlying mount must be preserved, otherwise remount fails.
+    let fs_flags = statvfs(target)?.flags();
+    let mut flags = MsFlags::MS_BIND
+        | MsFlags::MS_REMOUNT
+        | MsFlags::MS_RDONLY
+        | MsFlags::MS_NOSUID
+        | MsFlags::MS_NODEV;
+    if fs_flags.contains(FsFlags::ST_NOEXEC) {
+        flags |= MsFlags::MS_NOEXEC;
+    }
+    if fs_flags.contains(FsFlags::ST_NOATIME) {
+        flags |= MsFlags::MS_NOATIME;
+    }
+    if fs_flags.contains(FsFlags::ST_NODIRATIME) {
+        flags |= MsFlags::MS_NODIRATIME;
+    }
+    mount(NONE, target, NONE, flags, NONE)
+}
+
+/// Returns `target` and the mount points below it, e.g. the ones a recursive bind has copied.
+fn mount_points_under(target: &Path) -> nix::Result<Vec<PathBuf>> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").map_err(io_to_nix_error)?;
+    let mut mount_points = mountinfo
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(|mount_point| PathBuf::from(unescape_mount_point(mount_point)))
+        .filter(|mount_point| mount_point.starts_with(target))
+        .collect::<Vec<_>>();
+    mount_points.dedup();
+    Ok(mount_points)
+}
+
+/// Spaces, tabs, newlines and backslashes are escaped as octal codes in mountinfo.
+fn unescape_mount_point(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('\\') {
+        result.push_str(&rest[..pos]);
+        let code = rest
+            .get(pos + 1..pos + 4)
+            .and_then(|code| u8::from_str_radix(code, 8).ok());
+        match code {
+            Some(code) => {
+                result.push(code as char);
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                result.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn create_mount_point(target: &Path, is_dir: bool, can_create: bool) -> nix::Result<()> {
+    if target.exists() {
+        return Ok(());
+    }
+    if !can_create {
+        return Err(nix::Error::from_errno(Errno::ENOENT));
+    }
+    let result = if is_dir {
+        fs::create_dir_all(target)
+    } else {
+        target
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| {
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(target)
+                    .map(|_| ())
+            })
+    };
+    result.map_err(io_to_nix_error)
+}
+
+fn write_proc_file(path: &str, data: &str) -> nix::Result<()> {
+    fs::write(path, data).map_err(io_to_nix_error)
+}
+
+fn io_to_nix_error(e: io::Error) -> nix::Error {
+    nix::Error::from_errno(Errno::from_i32(e.raw_os_error().unwrap_or(EIO)))
+}
@@ -0,0 +1,103 @@
+use crate::process::CpuCounter;
+use crate::sys::unix::missing_decls::{
+    perf_event_attr, PERF_ATTR_FLAG_DISABLED, PERF_ATTR_FLAG_ENABLE_ON_EXEC,
+    PERF_ATTR_FLAG_EXCLUDE_HV, PERF_ATTR_FLAG_EXCLUDE_KERNEL, PERF_ATTR_FLAG_INHERIT,
+    PERF_COUNT_HW_INSTRUCTIONS, PERF_COUNT_SW_TASK_CLOCK, PERF_FLAG_FD_CLOEXEC,
+    PERF_FORMAT_TOTAL_TIME_ENABLED, PERF_FORMAT_TOTAL_TIME_RUNNING, PERF_TYPE_HARDWARE,
+    PERF_TYPE_SOFTWARE,
+};
+
+use nix::errno::Errno;
+use nix::libc::{self, __u32, __u64, c_int};
+use nix::unistd::{close, read, Pid};
+
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+/// A performance counter of a process and the descendants it creates afterwards.
+/// Counting starts once the process calls exec.
+pub struct PerfCounter {
+    fd: RawFd,
+    task_clock: bool,
+}
+
+impl PerfCounter {
+    /// Counts the instructions retired by the process in user mode. With `fallback` its task
+    /// clock is counted if hardware counters are unavailable, e.g. in virtual machines.
+    pub fn open(pid: Pid, fallback: bool) -> nix::Result<Self> {
+        open_event(pid, PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS)
+            .map(|fd| Self {
+                fd,
+                task_clock: false,
+            })
+            .or_else(|e| {
+                if !fallback {
+                    return Err(e);
+                }
+                open_event(pid, PERF_TYPE_SOFTWARE, PERF_COUNT_SW_TASK_CLOCK).map(|fd| Self {
+                    fd,
+                    task_clock: true,
+                })
+            })
+    }
+
+    pub fn read(&self) -> nix::Result<CpuCounter> {
+        let mut buf = [0; 3 * mem::size_of::<u64>()];
+        if read(self.fd, &mut buf)? != buf.len() {
+            return Err(nix::Error::Sys(Errno::EIO));
+        }
+        let mut values = buf
+            .chunks(mem::size_of::<u64>())
+            .map(|c| u64::from_ne_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]));
+        let (value, enabled, running) = (
+            values.next().unwrap(),
+            values.next().unwrap(),
+            values.next().unwrap(),
+        );
+        // A hardware counter is extrapolated if it was multiplexed with other events.
+        let value = if running != 0 && running < enabled {
+            (value as u128 * enabled as u128 / running as u128) as u64
+        } else {
+            value
+        };
+        Ok(if self.task_clock {
+            CpuCounter::TaskClock(Duration::from_nanos(value))
+        } else {
+            CpuCounter::Instructions(value)
+        })
+    }
+}
+
+impl Drop for PerfCounter {
+    fn drop(&mut self) {
+        let _ = close(self.fd);
+    }
+}
+
+fn open_event(pid: Pid, type_: __u32, config: __u64) -> nix::Result<RawFd> {
+    let attr = perf_event_attr {
+        type_,
+        size: mem::size_of::<perf_event_attr>() as __u32,
+        config,
+        read_format: PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+        // Kernel mode is excluded, so that unprivileged users can count as well.
+        flags: PERF_ATTR_FLAG_DISABLED
+            | PERF_ATTR_FLAG_INHERIT
+            | PERF_ATTR_FLAG_EXCLUDE_KERNEL
+            | PERF_ATTR_FLAG_EXCLUDE_HV
+            | PERF_ATTR_FLAG_ENABLE_ON_EXEC,
+        ..Default::default()
+    };
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            &attr as *const perf_event_attr,
+            pid.as_raw(),
+            -1 as c_int,
+            -1 as c_int,
+            PERF_FLAG_FD_CLOEXEC,
+        )
+    };
+    Errno::result(fd).map(|fd| fd as RawFd)
+}
@@ -0,0 +1,51 @@
+use crate::sys::unix::missing_decls::{SYS_PIDFD_GETFD, SYS_PIDFD_OPEN, SYS_PIDFD_SEND_SIGNAL};
+
+use nix::errno::Errno;
+use nix::libc::{c_int, siginfo_t, syscall};
+use nix::sys::signal::Signal;
+use nix::unistd::{close, Pid};
+
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+
+/// A file descriptor referring to a process. Unlike a pid it is never reused,
+/// so signals can't be delivered to a wrong process. Available since Linux 5.3.
+pub struct PidFd(RawFd);
+
+impl PidFd {
+    pub fn open(pid: Pid) -> nix::Result<Self> {
+        let fd = unsafe { syscall(SYS_PIDFD_OPEN, pid.as_raw(), 0) };
+        Errno::result(fd).map(|fd| Self(fd as RawFd))
+    }
+
+    pub fn send_signal(&self, signal: Signal) -> nix::Result<()> {
+        let result = unsafe {
+            syscall(
+                SYS_PIDFD_SEND_SIGNAL,
+                self.0,
+                signal as c_int,
+                ptr::null::<siginfo_t>(),
+                0,
+            )
+        };
+        Errno::result(result).map(|_| ())
+    }
+
+    /// Duplicates file descriptor `fd` of the process. Available since Linux 5.6.
+    pub fn get_fd(&self, fd: RawFd) -> nix::Result<RawFd> {
+        let result = unsafe { syscall(SYS_PIDFD_GETFD, self.0, fd, 0) };
+        Errno::result(result).map(|fd| fd as RawFd)
+    }
+}
+
+impl AsRawFd for PidFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl Drop for PidFd {
+    fn drop(&mut self) {
+        let _ = close(self.0);
+    }
+}
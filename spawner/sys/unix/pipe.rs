@@ -1,7 +1,9 @@
 use crate::sys::IntoInner;
 use crate::{Error, Result};
 
+use nix::errno::Errno;
 use nix::fcntl::{fcntl, open, FcntlArg, FdFlag, OFlag};
+use nix::poll::{self, PollFd, PollFlags};
 use nix::sys::stat::Mode;
 use nix::unistd::{close, pipe, read, write};
 
@@ -26,12 +28,43 @@ pub fn create() -> Result<(ReadPipe, WritePipe)> {
     ))
 }
 
+/// Waits until some of the `read` pipes have data or are closed, or some of the `write`
+/// pipes can be written to. Returns whether each pipe is ready, the `read` pipes first.
+pub fn poll(read: &[&ReadPipe], write: &[&WritePipe]) -> io::Result<Vec<bool>> {
+    let mut fds = read
+        .iter()
+        .map(|p| PollFd::new(p.raw(), PollFlags::POLLIN))
+        .chain(
+            write
+                .iter()
+                .map(|p| PollFd::new(p.raw(), PollFlags::POLLOUT)),
+        )
+        .collect::<Vec<_>>();
+    loop {
+        match poll::poll(&mut fds, -1) {
+            Ok(_) => break,
+            Err(nix::Error::Sys(Errno::EINTR)) => continue,
+            Err(_) => return Err(io::Error::last_os_error()),
+        }
+    }
+    Ok(fds
+        .iter()
+        .map(|fd| fd.revents().is_some_and(|e| !e.is_empty()))
+        .collect())
+}
+
 impl PipeFd {
     fn new(fd: RawFd) -> Result<Self> {
         fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
         Ok(Self(fd))
     }
 
+    fn set_nonblocking(&self) -> Result<()> {
+        let flags = OFlag::from_bits_truncate(fcntl(self.0, FcntlArg::F_GETFL)?);
+        fcntl(self.0, FcntlArg::F_SETFL(flags | OFlag::O_NONBLOCK))?;
+        Ok(())
+    }
+
     pub fn raw(&self) -> RawFd {
         self.0
     }
@@ -59,6 +92,10 @@ impl ReadPipe {
         Self::open("/dev/null")
     }
 
+    pub fn set_nonblocking(&self) -> Result<()> {
+        self.0.set_nonblocking()
+    }
+
     fn raw(&self) -> RawFd {
         (self.0).0
     }
@@ -92,6 +129,10 @@ impl WritePipe {
         Self::open("/dev/null")
     }
 
+    pub fn set_nonblocking(&self) -> Result<()> {
+        self.0.set_nonblocking()
+    }
+
     fn raw(&self) -> RawFd {
         (self.0).0
     }
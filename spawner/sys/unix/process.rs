@@ -1,40 +1,51 @@
 use crate::process::{
-    ExitStatus, GroupIo, GroupMemory, GroupNetwork, GroupPidCounters, GroupTimers, OsLimit,
+    Crash, ExitStatus, GroupIo, GroupMemory, GroupNetwork, GroupPidCounters, GroupTimers, OsLimit,
+    Rusage,
 };
-use crate::sys::unix::missing_decls::{sock_fprog, SECCOMP_MODE_FILTER};
+use crate::sys::unix::cgroup::Cgroups;
+pub use crate::sys::unix::events::{Events, Poller, Waker};
+use crate::sys::unix::namespaces::{
+    enter_fs_view, enter_network_namespace, enter_user_namespace, recv_pid, send_pid, FsView,
+    NetworkIsolation,
+};
+use crate::sys::unix::perf::PerfCounter;
+use crate::sys::unix::pidfd::PidFd;
 use crate::sys::unix::pipe::{PipeFd, ReadPipe, WritePipe};
-use crate::sys::unix::process_ext::SyscallFilter;
+use crate::sys::unix::process_ext::{Rlimit, SyscallFilter};
+use crate::sys::unix::seccomp::{self, install_filter, SeccompListener};
 use crate::sys::unix::shared_mem::SharedMem;
-use crate::sys::{AsInnerMut, IntoInner};
-use crate::{Error, Result};
+use crate::sys::unix::syscall_table::syscall_name;
+use crate::sys::unix::watchdog::Watchdog;
+use crate::sys::{AsInner, AsInnerMut, IntoInner};
+use crate::{Error, Result, SecurityViolation, TerminationReason};
 
 use nix::errno::Errno;
+use nix::fcntl::OFlag;
 use nix::libc::{
-    c_ushort, getpwnam, prctl, PR_SET_NO_NEW_PRIVS, PR_SET_SECCOMP, STDERR_FILENO, STDIN_FILENO,
-    STDOUT_FILENO,
+    self, c_long, getpwnam, rlim_t, RLIM_INFINITY, STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO,
 };
-use nix::sched::{sched_setaffinity, CpuSet};
-use nix::sys::signal::{kill, raise, Signal};
+use nix::sched::{sched_setaffinity, unshare, CloneFlags, CpuSet};
+use nix::sys::signal::{kill, raise, signal, SigHandler, Signal};
+use nix::sys::socket::{setsockopt, socketpair, sockopt, AddressFamily, SockFlag, SockType};
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
 use nix::unistd::{
-    chdir, close, dup2, execve, execvpe, fork, setgroups, setresgid, setresuid, ForkResult, Gid,
-    Pid, Uid,
+    access, chdir, close, dup2, execve, fork, getppid, pipe2, read, setgroups, setresgid,
+    setresuid, AccessFlags, ForkResult, Gid, Pid, Uid,
 };
 
-use cgroups_fs::{Cgroup, CgroupName};
-
 use procfs::process::FDTarget;
 
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
-
 use std::collections::HashMap;
-use std::ffi::{CStr, CString};
+use std::convert::TryFrom;
+use std::env;
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::fs::File;
 use std::iter;
 use std::mem;
-use std::os::unix::io::RawFd;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::process;
-use std::thread;
+use std::sync::Arc;
 use std::time::Duration;
 
 pub struct Stdio {
@@ -59,6 +70,11 @@ pub struct ProcessInfo {
     username: Option<String>,
     filter: Option<SyscallFilter>,
     cpuset: Option<CpuSet>,
+    fs_view: Option<FsView>,
+    network_isolation: Option<NetworkIsolation>,
+    pid_namespace: bool,
+    user_namespace: bool,
+    rlimits: Vec<(Rlimit, Option<u64>)>,
 }
 
 #[derive(Copy, Clone)]
@@ -67,6 +83,11 @@ enum InitError {
     Other(nix::Error),
     Impersonate(nix::Error),
     Seccomp(nix::Error),
+    FsView(nix::Error),
+    Network(nix::Error),
+    PidNamespace(nix::Error),
+    UserNamespace(nix::Error),
+    Rlimit(nix::Error),
     CloseFd,
 }
 
@@ -80,6 +101,22 @@ enum ProcessStatus {
 pub struct Process {
     pid: Pid,
     status: ProcessStatus,
+    term_signal: Option<Signal>,
+    pid_ns: Option<PidNamespace>,
+    seccomp_listener: Option<Arc<SeccompListener>>,
+    // Whether the syscall filter kills processes by itself, since the listener is unavailable.
+    seccomp_fallback: bool,
+    // Whether the program has yet to pass the exec gate of its syscall filter.
+    exec_pending: bool,
+    security_violation: Option<SecurityViolation>,
+    rusage: Option<Rusage>,
+}
+
+/// A program spawned in a new PID namespace is not a child of ours, so its exit status
+/// is recorded by the namespace init. The keeper is our child that waits for the init.
+struct PidNamespace {
+    keeper: Pid,
+    program_status: SharedMem<Option<(WaitStatus, Option<Rusage>)>>,
 }
 
 pub struct ResourceUsage<'a> {
@@ -88,23 +125,44 @@ pub struct ResourceUsage<'a> {
     // Since we have information only about active tasks we need to memorize amount
     // of dead tasks and amount of bytes written by them.
     dead_tasks_info: DeadTasksInfo,
+    include_kernel_memory: bool,
 }
 
 pub struct Group {
-    memory: Cgroup,
-    cpuacct: Cgroup,
-    pids: Cgroup,
-    freezer: Cgroup,
+    cgroups: Cgroups,
+    pid_ns_inits: Vec<PidFd>,
+    cpu_counter_enabled: bool,
+    cpu_counter_required: bool,
+    cpu_counter: Option<PerfCounter>,
+    // Released after the cgroups are removed.
+    _lock: File,
+    _watchdog: Watchdog,
+    // Closed once the tasks are killed, since syscalls of the descendants that outlive
+    // a program fail with ENOSYS instead of being reported when the listener is closed.
+    seccomp_listeners: Vec<Arc<SeccompListener>>,
 }
 
 struct DeadTasksInfo {
     num_dead_tasks: usize,
+    total_bytes_read: u64,
     total_bytes_written: u64,
 }
 
+/// Bytes read and written by a task through any file, including pipes.
+#[derive(Copy, Clone, Default)]
+struct IoChars {
+    rchar: u64,
+    wchar: u64,
+    // The start time of the task, in clock ticks after boot.
+    start_time: u64,
+}
+
 struct ActiveTasks {
-    wchar_by_pid: HashMap<Pid, u64>,
+    chars_by_pid: HashMap<Pid, IoChars>,
     pid_by_inode: HashMap<u32, Pid>,
+    // The peak resident set size of a single task seen so far, if sampled.
+    sample_max_rss: bool,
+    max_rss: u64,
 }
 
 struct RawStdio {
@@ -118,6 +176,16 @@ struct User {
     gid: Gid,
 }
 
+impl Stdio {
+    fn into_raw(self) -> RawStdio {
+        RawStdio {
+            stdin: self.stdin.into_inner(),
+            stdout: self.stdout.into_inner(),
+            stderr: self.stderr.into_inner(),
+        }
+    }
+}
+
 impl ProcessInfo {
     pub fn new<T: AsRef<str>>(app: T) -> Self {
         Self {
@@ -131,6 +199,11 @@ impl ProcessInfo {
             username: None,
             filter: None,
             cpuset: None,
+            fs_view: None,
+            network_isolation: None,
+            pid_namespace: false,
+            user_namespace: false,
+            rlimits: Vec::new(),
         }
     }
 
@@ -200,25 +273,65 @@ impl ProcessInfo {
         self.cpuset = Some(cpuset);
         self
     }
+
+    pub fn fs_view(&mut self, view: FsView) -> &mut Self {
+        self.fs_view = Some(view);
+        self
+    }
+
+    pub fn network_isolation(&mut self, isolation: NetworkIsolation) -> &mut Self {
+        self.network_isolation = Some(isolation);
+        self
+    }
+
+    pub fn pid_namespace(&mut self, v: bool) -> &mut Self {
+        self.pid_namespace = v;
+        self
+    }
+
+    pub fn user_namespace(&mut self, v: bool) -> &mut Self {
+        self.user_namespace = v;
+        self
+    }
+
+    pub fn rlimit(&mut self, resource: Rlimit, value: Option<u64>) -> &mut Self {
+        self.rlimits.retain(|(r, _)| *r != resource);
+        self.rlimits.push((resource, value));
+        self
+    }
 }
 
 impl Process {
     pub fn exit_status(&mut self) -> Result<Option<ExitStatus>> {
+        // Children of the process may still be running.
+        self.check_seccomp_listener()?;
         if let ProcessStatus::Exited(ref status) = self.status {
             return Ok(Some(status.clone()));
         }
-
-        let exit_status = match waitpid(self.pid, Some(WaitPidFlag::WNOHANG))? {
-            WaitStatus::Exited(pid, code) => {
-                assert_eq!(pid, self.pid);
-                ExitStatus::Finished(code as u32)
+        let (wait_status, rusage) = match self.pid_ns {
+            Some(ref ns) => match ns.wait_status()? {
+                Some((status, rusage)) => (Some(status), rusage),
+                None => (None, None),
+            },
+            None => {
+                let (status, rusage) = wait4(Some(self.pid), Some(WaitPidFlag::WNOHANG))?;
+                (Some(status), Some(rusage))
             }
-            WaitStatus::Signaled(pid, signal, _) => {
-                assert_eq!(pid, self.pid);
-                ExitStatus::Crashed(format!("Process terminated by the '{}' signal", signal))
+        };
+        let exit_status = match wait_status {
+            Some(WaitStatus::Exited(_, code)) => ExitStatus::Finished(code as u32),
+            Some(WaitStatus::Signaled(_, signal, core_dumped)) => {
+                self.term_signal = Some(signal);
+                ExitStatus::Crashed(Crash {
+                    signal: Some(signal as i32),
+                    core_dumped,
+                    fault_code: None,
+                    cause: format!("Process terminated by the '{}' signal", signal),
+                })
             }
             _ => return Ok(None),
         };
+        self.rusage = rusage;
 
         // Process has exited. Check initialization result.
         let init_error =
@@ -230,22 +343,29 @@ impl Process {
                 _ => return Ok(Some(exit_status)),
             };
 
-        match init_error {
-            InitError::Other(e) => Err(Error::from(e)),
-            InitError::Impersonate(e) => {
-                Err(Error::from(format!("Failed to impersonate user: {}", e)))
-            }
-            InitError::Seccomp(e) => {
-                Err(Error::from(format!("Failed to initialize seccomp: {}", e)))
+        Err(Error::from(init_error))
+    }
+
+    pub fn rusage(&self) -> Option<Rusage> {
+        self.rusage
+    }
+
+    pub fn termination_reason(&self) -> Option<TerminationReason> {
+        if let Some(v) = self.security_violation {
+            return Some(TerminationReason::SecurityViolation(v));
+        }
+        match self.term_signal {
+            // Sent by the kernel when RLIMIT_FSIZE is exceeded.
+            Some(Signal::SIGXFSZ) => Some(TerminationReason::FileSizeLimitExceeded),
+            // The filter has killed the program, the syscall is known only to the kernel.
+            Some(Signal::SIGSYS) if self.seccomp_fallback => {
+                Some(TerminationReason::SecurityViolation(SecurityViolation {
+                    pid: self.pid.as_raw() as u32,
+                    syscall: None,
+                    syscall_name: None,
+                }))
             }
-            InitError::Group(e) => match e {
-                Some(e) => Err(Error::from(format!(
-                    "Failed to add process to cgroup: {}",
-                    e
-                ))),
-                None => Err(Error::from("Failed to add process to cgroup")),
-            },
-            InitError::CloseFd => Err(Error::from("Failed to close file descriptors")),
+            _ => None,
         }
     }
 
@@ -262,17 +382,63 @@ impl Process {
     }
 
     pub fn spawn(info: &mut ProcessInfo, stdio: Stdio) -> Result<Self> {
-        create_process(info, stdio, None).map(|(pid, init_result)| Self {
-            pid,
-            status: ProcessStatus::Alive(init_result),
-        })
+        create_process(info, stdio, None)
+    }
+
+    pub fn events(&self, group: &Group) -> Result<Events> {
+        let listener = self.seccomp_listener.as_ref().map(AsRawFd::as_raw_fd);
+        Events::new(self.pid, listener, &group.cgroups)
     }
 
     pub fn spawn_in_group(info: &mut ProcessInfo, stdio: Stdio, group: &mut Group) -> Result<Self> {
-        create_process(info, stdio, Some(group)).map(|(pid, init_result)| Self {
-            pid,
-            status: ProcessStatus::Alive(init_result),
-        })
+        create_process(info, stdio, Some(group))
+    }
+
+    fn check_seccomp_listener(&mut self) -> Result<()> {
+        let listener = match self.seccomp_listener {
+            Some(ref l) => l,
+            None => return Ok(()),
+        };
+        while let Some(n) = listener.try_recv()? {
+            if self.exec_pending && n.pid == self.pid && n.is_exec() {
+                self.exec_pending = false;
+                listener.allow(&n)?;
+                continue;
+            }
+            // The syscall stays blocked until the process dies, as with SECCOMP_RET_KILL_PROCESS.
+            let _ = kill(n.pid, Signal::SIGKILL);
+            if self.security_violation.is_none() {
+                self.security_violation = Some(SecurityViolation {
+                    pid: n.pid.as_raw() as u32,
+                    syscall: Some(n.syscall),
+                    syscall_name: syscall_name(n.syscall as c_long),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PidNamespace {
+    fn wait_status(&self) -> Result<Option<(WaitStatus, Option<Rusage>)>> {
+        // The init may hold the lock, and it can't wake us up since the mutex is process-private.
+        if let Ok(status) = self.program_status.try_lock() {
+            if status.is_some() {
+                return Ok(*status);
+            }
+        }
+        match waitpid(self.keeper, Some(WaitPidFlag::WNOHANG))? {
+            WaitStatus::StillAlive => Ok(None),
+            // The namespace is gone before the program status was recorded,
+            // e.g. the init was killed. The keeper mirrors the exit status of the init.
+            // An init killed while holding the lock leaves it locked for good.
+            status => Ok(self
+                .program_status
+                .try_lock()
+                .ok()
+                .and_then(|program_status| *program_status)
+                .or(Some((status, None)))),
+        }
     }
 }
 
@@ -282,28 +448,49 @@ impl<'a> ResourceUsage<'a> {
             group,
             active_tasks: ActiveTasks::new(),
             dead_tasks_info: DeadTasksInfo::new(),
+            include_kernel_memory: true,
         }
     }
 
     pub fn update(&mut self) -> Result<()> {
-        let dead_tasks_info = self.active_tasks.update(&self.group.freezer)?;
+        let dead_tasks_info = self.active_tasks.update(&self.group.cgroups)?;
         self.dead_tasks_info.num_dead_tasks += dead_tasks_info.num_dead_tasks;
+        self.dead_tasks_info.total_bytes_read += dead_tasks_info.total_bytes_read;
         self.dead_tasks_info.total_bytes_written += dead_tasks_info.total_bytes_written;
         Ok(())
     }
 
+    pub fn sample_process_memory(&mut self) {
+        self.active_tasks.sample_max_rss = true;
+    }
+
+    pub fn exclude_kernel_memory(&mut self) {
+        self.include_kernel_memory = false;
+    }
+
     pub fn memory(&self) -> Result<Option<GroupMemory>> {
-        let mem = &self.group.memory;
         Ok(Some(GroupMemory {
-            max_usage: mem.get_value::<u64>("memory.max_usage_in_bytes")?
-                + mem.get_value::<u64>("memory.kmem.max_usage_in_bytes")?,
+            max_usage: self
+                .group
+                .cgroups
+                .max_memory_usage(self.include_kernel_memory)?,
+            max_process_usage: self.active_tasks.max_rss,
+            limit_hits: self.group.cgroups.memory_limit_hits()?,
+            oom_kills: self.group.cgroups.oom_kills()?,
         }))
     }
 
     pub fn io(&self) -> Result<Option<GroupIo>> {
+        let disk_io = self.group.cgroups.disk_io()?;
         Ok(Some(GroupIo {
+            total_bytes_read: self.active_tasks.total_bytes_read()
+                + self.dead_tasks_info.total_bytes_read,
             total_bytes_written: self.active_tasks.total_bytes_written()
                 + self.dead_tasks_info.total_bytes_written,
+            disk_bytes_read: disk_io.bytes_read,
+            disk_bytes_written: disk_io.bytes_written,
+            disk_reads: disk_io.reads,
+            disk_writes: disk_io.writes,
         }))
     }
 
@@ -325,30 +512,35 @@ impl<'a> ResourceUsage<'a> {
     }
 
     pub fn timers(&self) -> Result<Option<GroupTimers>> {
-        let cpuacct = &self.group.cpuacct;
-        Ok(Some(GroupTimers {
-            total_user_time: Duration::from_nanos(cpuacct.get_value::<u64>("cpuacct.usage_user")?),
-            total_kernel_time: Duration::from_nanos(cpuacct.get_value::<u64>("cpuacct.usage_sys")?),
-        }))
+        let mut timers = self.group.cgroups.timers()?;
+        timers.cpu_counter = self
+            .group
+            .cpu_counter
+            .as_ref()
+            .map(PerfCounter::read)
+            .transpose()?;
+        Ok(Some(timers))
     }
 }
 
 impl Group {
     pub fn new() -> Result<Self> {
+        let (cgroups, lock) = Cgroups::create_locked()?;
+        let watchdog = Watchdog::spawn(&cgroups, &[lock.as_raw_fd()])?;
         Ok(Self {
-            memory: create_cgroup("memory/sp")?,
-            cpuacct: create_cgroup("cpuacct/sp")?,
-            pids: create_cgroup("pids/sp")?,
-            freezer: create_cgroup("freezer/sp")?,
+            cgroups,
+            pid_ns_inits: Vec::new(),
+            cpu_counter_enabled: false,
+            cpu_counter_required: false,
+            cpu_counter: None,
+            _lock: lock,
+            _watchdog: watchdog,
+            seccomp_listeners: Vec::new(),
         })
     }
 
     fn add_pid(&mut self, pid: Pid) -> std::io::Result<()> {
-        self.memory
-            .add_task(pid)
-            .and(self.cpuacct.add_task(pid))
-            .and(self.pids.add_task(pid))
-            .and(self.freezer.add_task(pid))
+        self.cgroups.add_task(pid)
     }
 
     pub fn add(&mut self, ps: &Process) -> Result<()> {
@@ -356,41 +548,68 @@ impl Group {
     }
 
     pub fn set_os_limit(&mut self, limit: OsLimit, value: u64) -> Result<bool> {
-        match limit {
-            OsLimit::Memory => {
-                self.memory.set_value("memory.limit_in_bytes", value)?;
-            }
-            OsLimit::ActiveProcess => {
-                self.pids.set_value("pids.max", value)?;
+        self.cgroups.set_os_limit(limit, value)?;
+        Ok(true)
+    }
+
+    pub fn enable_cpu_counter(&mut self, required: bool) {
+        self.cpu_counter_enabled = true;
+        self.cpu_counter_required |= required;
+    }
+
+    /// Attaches the CPU counter to the `program` stopped before exec, if it's enabled.
+    fn attach_cpu_counter(&mut self, program: Pid) -> Result<()> {
+        if self.cpu_counter_enabled && self.cpu_counter.is_none() {
+            // The task clock can't stand in for instructions of a required counter, since
+            // the limit would then mean something else depending on the host.
+            match PerfCounter::open(program, !self.cpu_counter_required) {
+                Ok(counter) => self.cpu_counter = Some(counter),
+                // Performance events may be forbidden, e.g. by `perf_event_paranoid`.
+                Err(e) if self.cpu_counter_required => {
+                    return Err(Error::from(format!(
+                        "Cannot count instructions, hardware counters may be unavailable: {}",
+                        e
+                    )))
+                }
+                Err(_) => {}
             }
         }
-        Ok(true)
+        Ok(())
     }
 
     pub fn is_os_limit_hit(&self, limit: OsLimit) -> Result<bool> {
-        match limit {
-            OsLimit::Memory => Ok(self.memory.get_value::<usize>("memory.failcnt")? > 0),
-            OsLimit::ActiveProcess => Ok(self.pids.get_raw_value("pids.events")? != "max 0\n"),
-        }
+        self.cgroups.is_os_limit_hit(limit).map_err(Error::from)
     }
 
     pub fn terminate(&self) -> Result<()> {
-        self.freezer.set_raw_value("freezer.state", "FROZEN")?;
-        while self.freezer.get_raw_value("freezer.state")? == "FREEZING" {
-            thread::sleep(Duration::from_millis(1));
+        if self.pid_ns_inits.is_empty() {
+            return self.cgroups.kill_all().map_err(Error::from);
         }
-        self.freezer.send_signal_to_all_tasks(Signal::SIGKILL)?;
-        self.freezer.set_raw_value("freezer.state", "THAWED")?;
-        Ok(())
+        // Killing the init of a PID namespace makes the kernel kill every process in it.
+        for init in self.pid_ns_inits.iter() {
+            match init.send_signal(Signal::SIGKILL) {
+                Ok(_) | Err(nix::Error::Sys(Errno::ESRCH)) => {}
+                Err(e) => return Err(Error::from(e)),
+            }
+        }
+        // Frozen tasks of the legacy freezer don't die until thawed.
+        self.resume()
+    }
+
+    pub fn send_signal(&self, signal: i32) -> Result<bool> {
+        let signal = Signal::try_from(signal)?;
+        self.cgroups.send_signal(signal)?;
+        // Frozen tasks handle the signal once thawed.
+        self.resume()?;
+        Ok(true)
     }
-}
 
-impl Drop for Group {
-    fn drop(&mut self) {
-        self.freezer.remove().ok();
-        self.memory.remove().ok();
-        self.cpuacct.remove().ok();
-        self.pids.remove().ok();
+    pub fn suspend(&self) -> Result<()> {
+        self.cgroups.freeze().map_err(Error::from)
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        self.cgroups.thaw().map_err(Error::from)
     }
 }
 
@@ -398,6 +617,7 @@ impl DeadTasksInfo {
     fn new() -> Self {
         Self {
             num_dead_tasks: 0,
+            total_bytes_read: 0,
             total_bytes_written: 0,
         }
     }
@@ -406,17 +626,23 @@ impl DeadTasksInfo {
 impl ActiveTasks {
     fn new() -> Self {
         Self {
-            wchar_by_pid: HashMap::new(),
+            chars_by_pid: HashMap::new(),
             pid_by_inode: HashMap::new(),
+            sample_max_rss: false,
+            max_rss: 0,
         }
     }
 
     fn count(&self) -> usize {
-        self.wchar_by_pid.len()
+        self.chars_by_pid.len()
+    }
+
+    fn total_bytes_read(&self) -> u64 {
+        self.chars_by_pid.values().map(|c| c.rchar).sum()
     }
 
     fn total_bytes_written(&self) -> u64 {
-        self.wchar_by_pid.values().sum()
+        self.chars_by_pid.values().map(|c| c.wchar).sum()
     }
 
     fn count_network_connections(&self) -> procfs::ProcResult<usize> {
@@ -436,10 +662,10 @@ impl ActiveTasks {
             .count())
     }
 
-    fn update(&mut self, freezer: &Cgroup) -> Result<DeadTasksInfo> {
+    fn update(&mut self, cgroups: &Cgroups) -> Result<DeadTasksInfo> {
         self.pid_by_inode.clear();
-        let new_wchar_by_pid = freezer
-            .get_tasks()?
+        let new_chars_by_pid = cgroups
+            .tasks()?
             .into_iter()
             .filter_map(|pid| procfs::process::Process::new(pid.as_raw()).ok())
             .map(|ps| {
@@ -453,34 +679,61 @@ impl ActiveTasks {
                         }));
                 }
 
-                (pid, ps.io().ok().map(|io| io.wchar))
+                // VmHWM is in kilobytes.
+                if self.sample_max_rss {
+                    if let Some(hwm) = ps.status().ok().and_then(|s| s.vmhwm) {
+                        self.max_rss = self.max_rss.max(hwm * 1024);
+                    }
+                }
+
+                let chars = ps.io().ok().map(|io| IoChars {
+                    rchar: io.rchar,
+                    wchar: io.wchar,
+                    start_time: ps.stat.starttime,
+                });
+                (pid, chars.unwrap_or_default())
             })
-            .collect::<HashMap<Pid, Option<u64>>>();
+            .collect::<HashMap<Pid, IoChars>>();
 
-        let old_wchar_by_pid = &mut self.wchar_by_pid;
-        let dead_tasks = old_wchar_by_pid
+        let old_chars_by_pid = &mut self.chars_by_pid;
+        let dead_tasks = old_chars_by_pid
             .iter_mut()
-            .filter_map(|(pid, wchar)| match new_wchar_by_pid.get(pid) {
-                Some(new_wchar) => {
-                    *wchar = std::cmp::max(*wchar, new_wchar.unwrap_or(0));
+            .filter_map(|(pid, chars)| match new_chars_by_pid.get(pid) {
+                Some(new_chars) => {
+                    chars.rchar = std::cmp::max(chars.rchar, new_chars.rchar);
+                    chars.wchar = std::cmp::max(chars.wchar, new_chars.wchar);
                     None
                 }
                 None => Some(*pid),
             })
             .collect::<Vec<Pid>>();
 
-        for (pid, wchar) in new_wchar_by_pid.iter() {
-            if old_wchar_by_pid.get(pid).is_none() {
-                old_wchar_by_pid.insert(*pid, wchar.unwrap_or(0));
+        for (pid, chars) in new_chars_by_pid.iter() {
+            if old_chars_by_pid.get(pid).is_none() {
+                old_chars_by_pid.insert(*pid, *chars);
             }
         }
 
+        let dead_chars = dead_tasks
+            .iter()
+            .map(|pid| {
+                let mut chars = old_chars_by_pid.remove(pid).unwrap();
+                // A task that has left the group keeps its counters until it's reaped, which
+                // is up to the supervisor for the main process.
+                let zombie = procfs::process::Process::new(pid.as_raw())
+                    .ok()
+                    .filter(|ps| ps.stat.state == 'Z' && ps.stat.starttime == chars.start_time);
+                if let Some(io) = zombie.and_then(|ps| ps.io().ok()) {
+                    chars.rchar = chars.rchar.max(io.rchar);
+                    chars.wchar = chars.wchar.max(io.wchar);
+                }
+                chars
+            })
+            .collect::<Vec<IoChars>>();
         Ok(DeadTasksInfo {
             num_dead_tasks: dead_tasks.len(),
-            total_bytes_written: dead_tasks
-                .into_iter()
-                .map(|pid| old_wchar_by_pid.remove(&pid).unwrap())
-                .sum(),
+            total_bytes_read: dead_chars.iter().map(|c| c.rchar).sum(),
+            total_bytes_written: dead_chars.iter().map(|c| c.wchar).sum(),
         })
     }
 }
@@ -507,22 +760,6 @@ impl User {
     }
 }
 
-fn create_cgroup(subsystem: &'static str) -> Result<Cgroup> {
-    let mut rng = thread_rng();
-    let name = format!(
-        "task_{}",
-        (0..7).map(|_| rng.sample(Alphanumeric)).collect::<String>()
-    );
-    let cgroup = Cgroup::new(&CgroupName::new(&name), subsystem);
-    cgroup.create().map_err(|e| {
-        Error::from(format!(
-            "Cannot create cgroup /{}/{}: {}",
-            subsystem, name, e
-        ))
-    })?;
-    Ok(cgroup)
-}
-
 fn to_cstr<S: Into<Vec<u8>>>(s: S) -> Result<CString> {
     CString::new(s).map_err(|e| Error::from(e.to_string()))
 }
@@ -566,29 +803,120 @@ fn init_stdio(stdio: RawStdio) -> nix::Result<()> {
     Ok(())
 }
 
-fn init_seccomp(filter: &mut SyscallFilter) -> nix::Result<()> {
-    if unsafe { prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } == -1 {
-        return Err(nix::Error::last());
+impl From<InitError> for Error {
+    fn from(e: InitError) -> Self {
+        match e {
+            InitError::Other(e) => Error::from(e),
+            InitError::Impersonate(e) => Error::from(format!("Failed to impersonate user: {}", e)),
+            InitError::Seccomp(e) => Error::from(format!("Failed to initialize seccomp: {}", e)),
+            InitError::FsView(e) => namespace_error("Failed to set up filesystem view", e),
+            InitError::Network(e) => namespace_error("Failed to isolate network", e),
+            InitError::PidNamespace(e) => namespace_error("Failed to create PID namespace", e),
+            InitError::UserNamespace(e) => {
+                Error::from(format!("Failed to create user namespace: {}", e))
+            }
+            InitError::Rlimit(e) => Error::from(format!("Failed to set resource limit: {}", e)),
+            InitError::Group(e) => match e {
+                Some(e) => Error::from(format!("Failed to add process to cgroup: {}", e)),
+                None => Error::from("Failed to add process to cgroup"),
+            },
+            InitError::CloseFd => Error::from("Failed to close file descriptors"),
+        }
+    }
+}
+
+fn namespace_error(msg: &str, e: nix::Error) -> Error {
+    match e {
+        nix::Error::Sys(Errno::EPERM) => Error::from(format!(
+            "{}: {}, namespaces require root privileges or a user namespace",
+            msg, e
+        )),
+        _ => Error::from(format!("{}: {}", msg, e)),
     }
-    let inner = filter.as_inner_mut();
-    let mut prog = sock_fprog {
-        len: inner.len() as c_ushort,
-        filter: inner.as_mut_ptr(),
-    };
-    if unsafe { prctl(PR_SET_SECCOMP, SECCOMP_MODE_FILTER, &mut prog) } == -1 {
-        return Err(nix::Error::last());
+}
+
+/// Makes the kernel kill the calling process once its parent exits, so that programs don't
+/// outlive a killed spawner.
+fn set_parent_death_signal() -> nix::Result<()> {
+    Errno::result(unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) }).map(|_| ())
+}
+
+/// Same as `set_parent_death_signal`, but fails if the parent isn't the `expected` one,
+/// i.e. it has exited already.
+fn die_with_parent(expected: Pid) -> nix::Result<()> {
+    set_parent_death_signal()?;
+    if getppid() != expected {
+        return Err(nix::Error::Sys(Errno::ESRCH));
     }
     Ok(())
 }
 
+/// Same as `die_with_parent` for the init of a PID namespace. Its parent lives outside of
+/// the namespace, so `getppid` returns 0. Instead, the parent holds the write end of a
+/// non-blocking `keeper_pipe`, which reads EOF once the parent has exited.
+fn die_with_keeper(keeper_pipe: (RawFd, RawFd)) -> nix::Result<()> {
+    let (read_end, write_end) = keeper_pipe;
+    let _ = close(write_end);
+    let result = set_parent_death_signal().and_then(|_| match read(read_end, &mut [0]) {
+        Ok(0) => Err(nix::Error::Sys(Errno::ESRCH)),
+        _ => Ok(()),
+    });
+    let _ = close(read_end);
+    result
+}
+
 fn init_child_process(
+    info: &ProcessInfo,
     stdio: RawStdio,
-    working_dir: Option<&str>,
-    filter: Option<&mut SyscallFilter>,
     group: Option<&mut Group>,
-    usr: Option<&User>,
-    cpuset: Option<&CpuSet>,
+    keep_fds: &[RawFd],
+    spawner: Pid,
 ) -> InitResult {
+    die_with_parent(spawner).map_err(InitError::Other)?;
+    add_to_group(group)?;
+
+    // Even though we set FD_CLOEXEC flag on all pipes, some child processes
+    // still inherit pipes of their siblings.
+    // Close all open file descriptors to fix this.
+    let mut ignore = vec![stdio.stdin.raw(), stdio.stdout.raw(), stdio.stderr.raw()];
+    ignore.extend_from_slice(keep_fds);
+    close_all_fds(&ignore)?;
+
+    init_stdio(stdio).map_err(InitError::Other)?;
+
+    // Other namespaces must be owned by the user namespace, so it goes first.
+    if info.user_namespace {
+        enter_user_namespace().map_err(InitError::UserNamespace)?;
+    }
+
+    info.network_isolation
+        .map(enter_network_namespace)
+        .transpose()
+        .map_err(InitError::Network)?;
+
+    for &(resource, value) in info.rlimits.iter() {
+        set_rlimit(resource, value).map_err(InitError::Rlimit)?;
+    }
+    Ok(())
+}
+
+fn set_rlimit(resource: Rlimit, value: Option<u64>) -> nix::Result<()> {
+    let resource = match resource {
+        Rlimit::Stack => libc::RLIMIT_STACK,
+        Rlimit::FileSize => libc::RLIMIT_FSIZE,
+        Rlimit::OpenFiles => libc::RLIMIT_NOFILE,
+        Rlimit::CoreSize => libc::RLIMIT_CORE,
+        Rlimit::AddressSpace => libc::RLIMIT_AS,
+    };
+    let value = value.map_or(RLIM_INFINITY, |v| v as rlim_t);
+    let limit = libc::rlimit {
+        rlim_cur: value,
+        rlim_max: value,
+    };
+    Errno::result(unsafe { libc::setrlimit(resource, &limit) }).map(|_| ())
+}
+
+fn add_to_group(group: Option<&mut Group>) -> InitResult {
     group
         .map(|g| g.add_pid(Pid::this()))
         .transpose()
@@ -598,16 +926,25 @@ fn init_child_process(
                     .map(|v| nix::Error::from_errno(Errno::from_i32(v))),
             )
         })?;
+    Ok(())
+}
 
-    // Even though we set FD_CLOEXEC flag on all pipes, some child processes
-    // still inherit pipes of their siblings.
-    // Close all open file descriptors to fix this.
-    close_all_fds(&[stdio.stdin.raw(), stdio.stdout.raw(), stdio.stderr.raw()])?;
-
-    init_stdio(stdio)
-        .and_then(|_| working_dir.map(chdir).transpose())
+/// Initializes the program itself right before exec. Paths are resolved
+/// relative to the filesystem view, so it must be entered first. The seccomp listener fd
+/// is stored in `listener`, so that the spawner can take it while the program is stopped.
+fn init_program(
+    info: &mut ProcessInfo,
+    usr: Option<&User>,
+    listener: &SharedMem<Option<RawFd>>,
+    parent: Pid,
+) -> std::result::Result<CString, InitError> {
+    info.working_dir
+        .as_deref()
+        .map(chdir)
+        .transpose()
         .and_then(|_| {
-            cpuset
+            info.cpuset
+                .as_ref()
                 .map(|x| sched_setaffinity(Pid::this(), x))
                 .transpose()
         })
@@ -616,37 +953,182 @@ fn init_child_process(
     usr.map(User::impersonate)
         .transpose()
         .map_err(InitError::Impersonate)?;
+    // Changing credentials resets the parent death signal.
+    if usr.is_some() {
+        die_with_parent(parent).map_err(InitError::Other)?;
+    }
 
-    filter
-        .map(init_seccomp)
-        .transpose()
-        .map_err(InitError::Seccomp)?;
+    // Resolve the program before the syscall filter is installed,
+    // so that it can be started with a single exec.
+    let app = if info.search_in_path {
+        find_in_path(&info.app).ok_or(InitError::Other(nix::Error::Sys(Errno::ENOENT)))?
+    } else {
+        CString::new(info.app.as_str())
+            .map_err(|_| InitError::Other(nix::Error::Sys(Errno::EINVAL)))?
+    };
 
-    Ok(())
+    if let Some(filter) = info.filter.as_mut() {
+        *listener.lock().unwrap() =
+            install_filter(filter.as_inner_mut()).map_err(InitError::Seccomp)?;
+    }
+    Ok(app)
 }
 
-fn exec_app(app: &CStr, args: &[&CStr], env: &[&CStr], search_in_path: bool) -> nix::Result<()> {
-    raise(Signal::SIGSTOP)?;
-    if search_in_path {
-        execvpe(app, args, env)?;
-    } else {
-        execve(app, args, env)?;
+/// Finds `app` in `PATH` the same way `execvp` does.
+fn find_in_path(app: &str) -> Option<CString> {
+    if app.contains('/') {
+        return CString::new(app).ok();
+    }
+    let path = env::var_os("PATH").unwrap_or_else(|| OsString::from("/bin:/usr/bin"));
+    env::split_paths(&path)
+        .map(|dir| dir.join(OsStr::from_bytes(app.as_bytes())))
+        .find(|p| p.is_file() && access(p, AccessFlags::X_OK).is_ok())
+        .and_then(|p| CString::new(p.into_os_string().into_vec()).ok())
+}
+
+fn close_stdio() {
+    for fd in [STDIN_FILENO, STDOUT_FILENO, STDERR_FILENO].iter() {
+        let _ = close(*fd);
+    }
+}
+
+/// Same as `waitpid`, but also returns the resource usage of the child.
+fn wait4(pid: Option<Pid>, options: Option<WaitPidFlag>) -> nix::Result<(WaitStatus, Rusage)> {
+    let mut status = 0;
+    let mut usage: libc::rusage = unsafe { mem::zeroed() };
+    let pid = unsafe {
+        libc::wait4(
+            pid.map_or(-1, Pid::as_raw),
+            &mut status,
+            options.map_or(0, |o| o.bits()),
+            &mut usage,
+        )
+    };
+    let status = match Errno::result(pid)? {
+        0 => WaitStatus::StillAlive,
+        pid => WaitStatus::from_raw(Pid::from_raw(pid), status)?,
+    };
+
+    let duration = |t: libc::timeval| {
+        Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+    };
+    Ok((
+        status,
+        Rusage {
+            user_time: duration(usage.ru_utime),
+            kernel_time: duration(usage.ru_stime),
+            // Linux reports the resident set size in kilobytes.
+            max_rss: usage.ru_maxrss as u64 * 1024,
+            minor_page_faults: usage.ru_minflt as u64,
+            major_page_faults: usage.ru_majflt as u64,
+            voluntary_context_switches: usage.ru_nvcsw as u64,
+            involuntary_context_switches: usage.ru_nivcsw as u64,
+        },
+    ))
+}
+
+/// Runs in the keeper: waits for `pid` and exits the same way.
+fn mirror_exit(pid: Pid) -> ! {
+    loop {
+        match waitpid(pid, None) {
+            Ok(WaitStatus::Exited(_, code)) => process::exit(code),
+            Ok(WaitStatus::Signaled(_, sig, _)) => {
+                unsafe {
+                    let _ = signal(sig, SigHandler::SigDfl);
+                }
+                let _ = raise(sig);
+                process::exit(128 + sig as i32);
+            }
+            Err(nix::Error::Sys(Errno::EINTR)) | Ok(_) => {}
+            Err(_) => process::exit(0),
+        }
+    }
+}
+
+/// Runs in the init of a new PID namespace: sets up the filesystem view, spawns the program,
+/// reports pids of both the init and the program to the spawner and then reaps zombies
+/// until the namespace is empty. Only the program is added to the group, so that the keeper
+/// and the init are not accounted for.
+fn run_pid_ns_init<F>(
+    info: &mut ProcessInfo,
+    usr: Option<&User>,
+    sock: RawFd,
+    init_result: &SharedMem<InitResult>,
+    listener: &SharedMem<Option<RawFd>>,
+    program_status: &SharedMem<Option<(WaitStatus, Option<Rusage>)>>,
+    exec: F,
+) where
+    F: Fn(&CStr) -> InitResult,
+{
+    // A fresh procfs instance shows only processes of the new namespace.
+    let mut view = info.fs_view.clone().unwrap_or_default();
+    view.procfs("/proc");
+    if let Err(e) = enter_fs_view(&view) {
+        *init_result.lock().unwrap() = Err(InitError::FsView(e));
+        return;
+    }
+
+    let init = Pid::this();
+    let program = match send_pid(sock, init).and_then(|_| fork()) {
+        Ok(ForkResult::Parent { child }) => child,
+        Ok(ForkResult::Child) => {
+            *init_result.lock().unwrap() =
+                init_program(info, usr, listener, init).and_then(|app| exec(&app));
+            process::exit(0);
+        }
+        Err(e) => {
+            *init_result.lock().unwrap() = Err(InitError::PidNamespace(e));
+            return;
+        }
+    };
+
+    // Don't keep pipes of the program open.
+    close_stdio();
+    match waitpid(program, Some(WaitPidFlag::WSTOPPED)) {
+        Ok(WaitStatus::Stopped(..)) => {
+            let _ = send_pid(sock, program);
+        }
+        Ok(status) => *program_status.lock().unwrap() = Some((status, None)),
+        Err(_) => {}
     }
+    let _ = close(sock);
+
+    loop {
+        match wait4(None, None) {
+            Ok((status, rusage)) => {
+                if status.pid() == Some(program) {
+                    *program_status.lock().unwrap() = Some((status, Some(rusage)));
+                }
+            }
+            Err(nix::Error::Sys(Errno::EINTR)) => {}
+            Err(_) => return,
+        }
+    }
+}
+
+fn exec_app(app: &CStr, args: &[&CStr], env: &[&CStr]) -> nix::Result<()> {
+    raise(Signal::SIGSTOP)?;
+    execve(app, args, env)?;
     Ok(())
 }
 
 fn create_process(
     info: &mut ProcessInfo,
     stdio: Stdio,
-    group: Option<&mut Group>,
-) -> Result<(Pid, SharedMem<InitResult>)> {
+    mut group: Option<&mut Group>,
+) -> Result<Process> {
+    if has_exec_gate(info) && !seccomp::can_get_listener() {
+        return Err(Error::from(
+            "Restricting exec requires seccomp user notifications (Linux 5.6)",
+        ));
+    }
     let usr = info
         .username
         .as_ref()
         .map(|s| User::new(s.as_str()))
         .transpose()?;
     let init_result = SharedMem::alloc(Ok(()))?;
-    let app = to_cstr(info.app.as_str())?;
+    let listener = SharedMem::alloc(None)?;
     let args = create_args(info)?;
     let args_ref = (0..args.len())
         .map(|i| args[i].as_c_str())
@@ -655,31 +1137,224 @@ fn create_process(
     let env_ref = (0..env.len())
         .map(|i| env[i].as_c_str())
         .collect::<Vec<_>>();
+    let exec = |app: &CStr| exec_app(app, &args_ref, &env_ref).map_err(InitError::Other);
+
+    if info.pid_namespace {
+        return create_process_in_pid_ns(
+            info,
+            stdio,
+            group,
+            usr.as_ref(),
+            init_result,
+            listener,
+            exec,
+        );
+    }
 
+    let spawner = Pid::this();
     if let ForkResult::Parent { child, .. } = fork()? {
         // Wait for initialization to complete.
-        waitpid(child, Some(WaitPidFlag::WSTOPPED))?;
-        if !info.suspended {
-            kill(child, Signal::SIGCONT)?;
+        let seccomp_listener = match waitpid(child, Some(WaitPidFlag::WSTOPPED))? {
+            WaitStatus::Stopped(..) => {
+                take_seccomp_listener(child, &listener).inspect_err(|_| {
+                    let _ = kill(child, Signal::SIGKILL);
+                    let _ = waitpid(child, None);
+                })?
+            }
+            // The child has exited while initializing, so it's reaped already.
+            status => {
+                return Err(match *init_result.lock().unwrap() {
+                    Err(e) => Error::from(e),
+                    Ok(_) => Error::from(format!("Process has exited before exec: {:?}", status)),
+                })
+            }
+        };
+        if let Some(group) = group.as_deref_mut() {
+            group.attach_cpu_counter(child).inspect_err(|_| {
+                let _ = kill(child, Signal::SIGKILL);
+                let _ = waitpid(child, None);
+            })?;
+            group.seccomp_listeners.extend(seccomp_listener.clone());
         }
-        return Ok((child, init_result));
+        resume_program(info, child, group.as_deref())?;
+        return Ok(Process {
+            pid: child,
+            status: ProcessStatus::Alive(init_result),
+            term_signal: None,
+            pid_ns: None,
+            seccomp_fallback: info.filter.is_some() && seccomp_listener.is_none(),
+            exec_pending: seccomp_listener.is_some() && has_exec_gate(info),
+            seccomp_listener,
+            security_violation: None,
+            rusage: None,
+        });
     }
 
-    *init_result.lock().unwrap() = init_child_process(
-        RawStdio {
-            stdin: stdio.stdin.into_inner(),
-            stdout: stdio.stdout.into_inner(),
-            stderr: stdio.stderr.into_inner(),
+    *init_result.lock().unwrap() = init_child_process(info, stdio.into_raw(), group, &[], spawner)
+        .and_then(|_| {
+            info.fs_view
+                .as_ref()
+                .map(enter_fs_view)
+                .transpose()
+                .map_err(InitError::FsView)
+        })
+        .and_then(|_| init_program(info, usr.as_ref(), &listener, spawner))
+        .and_then(|app| exec(&app));
+
+    process::exit(0);
+}
+
+/// Lets the `program` stopped before exec proceed. A suspended program is frozen along with
+/// its group instead, so that resuming the group resumes it as well.
+fn resume_program(info: &ProcessInfo, program: Pid, group: Option<&Group>) -> Result<()> {
+    if info.suspended {
+        match group {
+            Some(group) => group.suspend()?,
+            None => return Ok(()),
+        }
+    }
+    kill(program, Signal::SIGCONT).map_err(Error::from)
+}
+
+fn has_exec_gate(info: &ProcessInfo) -> bool {
+    info.filter
+        .as_ref()
+        .is_some_and(|f| seccomp::has_exec_gate(f.as_inner()))
+}
+
+/// Takes the seccomp listener of the `program` stopped before exec.
+fn take_seccomp_listener(
+    program: Pid,
+    listener: &SharedMem<Option<RawFd>>,
+) -> Result<Option<Arc<SeccompListener>>> {
+    let fd = *listener.lock().unwrap();
+    fd.map(|fd| SeccompListener::from_process(program, fd).map(Arc::new))
+        .transpose()
+        .map_err(|e| Error::from(format!("Failed to get seccomp listener: {}", e)))
+}
+
+fn create_process_in_pid_ns<F>(
+    info: &mut ProcessInfo,
+    stdio: Stdio,
+    mut group: Option<&mut Group>,
+    usr: Option<&User>,
+    init_result: SharedMem<InitResult>,
+    listener: SharedMem<Option<RawFd>>,
+    exec: F,
+) -> Result<Process>
+where
+    F: Fn(&CStr) -> InitResult,
+{
+    let (sock, child_sock) = socketpair(
+        AddressFamily::Unix,
+        SockType::SeqPacket,
+        None,
+        SockFlag::SOCK_CLOEXEC,
+    )?;
+    let program_status = SharedMem::alloc(None)?;
+    let spawner = Pid::this();
+    let fork_result = setsockopt(sock, sockopt::PassCred, &true).and_then(|_| fork());
+
+    match fork_result {
+        Ok(ForkResult::Parent { child: keeper }) => {
+            let _ = close(child_sock);
+            // The init reports its own pid and then the pid of the program once the program
+            // is stopped before exec. The socket is closed earlier if initialization fails.
+            let init = recv_pid(sock);
+            let program = match init {
+                Ok(Some(_)) => recv_pid(sock),
+                _ => Ok(None),
+            };
+            let _ = close(sock);
+            let (init, program) = (init?, program?);
+
+            // The init is alive at this point, since the program is still stopped.
+            if let (Some(group), Some(init), Some(program)) = (group.as_deref_mut(), init, program)
+            {
+                if let Ok(init) = PidFd::open(init) {
+                    group.pid_ns_inits.push(init);
+                }
+                // The program can't add itself to the group, since cgroup filesystem
+                // may be hidden by the filesystem view or owned by another user namespace.
+                if let Err(e) = group.add_pid(program) {
+                    let _ = kill(program, Signal::SIGKILL);
+                    let _ = waitpid(keeper, None);
+                    return Err(Error::from(e));
+                }
+            }
+            let seccomp_listener = match program {
+                Some(program) => take_seccomp_listener(program, &listener).inspect_err(|_| {
+                    let _ = kill(program, Signal::SIGKILL);
+                    let _ = waitpid(keeper, None);
+                })?,
+                None => None,
+            };
+            if let Some(program) = program {
+                if let Some(group) = group.as_deref_mut() {
+                    group.attach_cpu_counter(program).inspect_err(|_| {
+                        let _ = kill(program, Signal::SIGKILL);
+                        let _ = waitpid(keeper, None);
+                    })?;
+                    group.seccomp_listeners.extend(seccomp_listener.clone());
+                }
+                resume_program(info, program, group.as_deref())?;
+            }
+            return Ok(Process {
+                pid: program.unwrap_or(keeper),
+                status: ProcessStatus::Alive(init_result),
+                term_signal: None,
+                pid_ns: Some(PidNamespace {
+                    keeper,
+                    program_status,
+                }),
+                seccomp_fallback: info.filter.is_some() && seccomp_listener.is_none(),
+                exec_pending: seccomp_listener.is_some() && has_exec_gate(info),
+                seccomp_listener,
+                security_violation: None,
+                rusage: None,
+            });
+        }
+        Ok(ForkResult::Child) => {}
+        Err(e) => {
+            let _ = close(sock);
+            let _ = close(child_sock);
+            return Err(Error::from(e));
+        }
+    }
+
+    // Processes forked after unshare(CLONE_NEWPID) are placed into the new namespace,
+    // the first one becomes its init.
+    let result = init_child_process(info, stdio.into_raw(), None, &[child_sock], spawner)
+        .and_then(|_| unshare(CloneFlags::CLONE_NEWPID).map_err(InitError::PidNamespace))
+        .and_then(|_| pipe2(OFlag::O_CLOEXEC | OFlag::O_NONBLOCK).map_err(InitError::Other))
+        .and_then(|keeper_pipe| {
+            fork()
+                .map(|fork_result| (fork_result, keeper_pipe))
+                .map_err(InitError::PidNamespace)
+        });
+
+    match result {
+        Ok((ForkResult::Parent { child }, (read_end, _))) => {
+            close_stdio();
+            let _ = close(child_sock);
+            let _ = close(read_end);
+            mirror_exit(child);
+        }
+        // The kernel kills every process in the namespace once its init exits.
+        Ok((ForkResult::Child, keeper_pipe)) => match die_with_keeper(keeper_pipe) {
+            Ok(_) => run_pid_ns_init(
+                info,
+                usr,
+                child_sock,
+                &init_result,
+                &listener,
+                &program_status,
+                exec,
+            ),
+            Err(e) => *init_result.lock().unwrap() = Err(InitError::Other(e)),
         },
-        info.working_dir.as_deref(),
-        info.filter.as_mut(),
-        group,
-        usr.as_ref(),
-        info.cpuset.as_ref(),
-    )
-    .and_then(|_| {
-        exec_app(&app, &args_ref, &env_ref, info.search_in_path).map_err(InitError::Other)
-    });
+        Err(e) => *init_result.lock().unwrap() = Err(e),
+    }
 
     process::exit(0);
 }
@@ -1,11 +1,16 @@
 use crate::process::ProcessInfo;
 use crate::sys::unix::missing_decls::{
-    self, sock_filter, BPF_ABS, BPF_JEQ, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W, SECCOMP_RET_ALLOW,
+    sock_filter, BPF_ABS, BPF_JEQ, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W, SECCOMP_RET_ALLOW,
     SECCOMP_RET_KILL,
 };
-use crate::sys::AsInnerMut;
+use crate::sys::unix::seccomp::{bpf_jump, bpf_stmt, ARCH_NR};
+use crate::sys::{AsInner, AsInnerMut, FromInner};
 
-use nix::libc::{__u16, __u32, __u8};
+pub use crate::sys::unix::capabilities::{check_capabilities, Capability, CapabilityStatus};
+pub use crate::sys::unix::hierarchy::{cleanup_cgroups, setup_cgroups};
+pub use crate::sys::unix::namespaces::{FsView, NetworkIsolation};
+pub use crate::sys::unix::seccomp::{ArgCmp, ArgCondition, SeccompAction, SeccompPolicy};
+use nix::libc::__u32;
 pub use nix::sched::CpuSet;
 
 // https://outflux.net/teach-seccomp
@@ -13,17 +18,32 @@ pub struct SyscallFilter(Vec<sock_filter>);
 
 pub struct SyscallFilterBuilder(Vec<sock_filter>);
 
+/// Resource limits enforced by the kernel on every process of a program, see `setrlimit(2)`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rlimit {
+    /// The maximum size of the stack, in bytes.
+    Stack,
+    /// The maximum size of a file that can be created, in bytes.
+    FileSize,
+    /// The maximum number of open file descriptors.
+    OpenFiles,
+    /// The maximum size of a core dump, in bytes.
+    CoreSize,
+    /// The maximum size of the virtual address space, in bytes.
+    AddressSpace,
+}
+
 pub trait ProcessInfoExt {
     fn syscall_filter(&mut self, filter: SyscallFilter) -> &mut Self;
     fn cpuset(&mut self, cpuset: CpuSet) -> &mut Self;
+    fn fs_view(&mut self, view: FsView) -> &mut Self;
+    fn network_isolation(&mut self, isolation: NetworkIsolation) -> &mut Self;
+    fn pid_namespace(&mut self, v: bool) -> &mut Self;
+    fn user_namespace(&mut self, v: bool) -> &mut Self;
+    /// Sets both soft and hard values of the `resource` limit, `None` means unlimited.
+    fn rlimit(&mut self, resource: Rlimit, value: Option<u64>) -> &mut Self;
 }
 
-#[cfg(target_arch = "x86")]
-const ARCH_NR: __u32 = missing_decls::AUDIT_ARCH_I386;
-
-#[cfg(target_arch = "x86_64")]
-const ARCH_NR: __u32 = missing_decls::AUDIT_ARCH_X86_64;
-
 impl SyscallFilterBuilder {
     pub fn block_all() -> Self {
         let arch_offset = 4; // offsetof(struct seccomp_data, arch)
@@ -52,12 +72,24 @@ impl SyscallFilterBuilder {
     }
 }
 
+impl AsInner<Vec<sock_filter>> for SyscallFilter {
+    fn as_inner(&self) -> &Vec<sock_filter> {
+        &self.0
+    }
+}
+
 impl AsInnerMut<Vec<sock_filter>> for SyscallFilter {
     fn as_inner_mut(&mut self) -> &mut Vec<sock_filter> {
         &mut self.0
     }
 }
 
+impl FromInner<Vec<sock_filter>> for SyscallFilter {
+    fn from_inner(inner: Vec<sock_filter>) -> Self {
+        Self(inner)
+    }
+}
+
 impl ProcessInfoExt for ProcessInfo {
     fn syscall_filter(&mut self, filter: SyscallFilter) -> &mut Self {
         self.as_inner_mut().syscall_filter(filter);
@@ -68,17 +100,29 @@ impl ProcessInfoExt for ProcessInfo {
         self.as_inner_mut().cpuset(cpuset);
         self
     }
-}
 
-fn bpf_stmt(code: __u16, k: __u32) -> sock_filter {
-    sock_filter {
-        code,
-        jt: 0,
-        jf: 0,
-        k,
+    fn fs_view(&mut self, view: FsView) -> &mut Self {
+        self.as_inner_mut().fs_view(view);
+        self
+    }
+
+    fn network_isolation(&mut self, isolation: NetworkIsolation) -> &mut Self {
+        self.as_inner_mut().network_isolation(isolation);
+        self
+    }
+
+    fn pid_namespace(&mut self, v: bool) -> &mut Self {
+        self.as_inner_mut().pid_namespace(v);
+        self
+    }
+
+    fn user_namespace(&mut self, v: bool) -> &mut Self {
+        self.as_inner_mut().user_namespace(v);
+        self
     }
-}
 
-fn bpf_jump(code: __u16, k: __u32, jt: __u8, jf: __u8) -> sock_filter {
-    sock_filter { code, jt, jf, k }
+    fn rlimit(&mut self, resource: Rlimit, value: Option<u64>) -> &mut Self {
+        self.as_inner_mut().rlimit(resource, value);
+        self
+    }
 }
@@ -0,0 +1,645 @@
+use crate::sys::unix::missing_decls::{
+    self, seccomp_notif, seccomp_notif_resp, sock_filter, sock_fprog, BPF_ABS, BPF_ALU, BPF_AND,
+    BPF_JEQ, BPF_JGE, BPF_JGT, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W,
+    SECCOMP_FILTER_FLAG_NEW_LISTENER, SECCOMP_IOCTL_NOTIF_RECV, SECCOMP_IOCTL_NOTIF_SEND,
+    SECCOMP_MODE_FILTER, SECCOMP_RET_ACTION_FULL, SECCOMP_RET_ALLOW, SECCOMP_RET_DATA,
+    SECCOMP_RET_ERRNO, SECCOMP_RET_KILL_PROCESS, SECCOMP_RET_LOG, SECCOMP_RET_TRAP,
+    SECCOMP_RET_USER_NOTIF, SECCOMP_SET_MODE_FILTER, SECCOMP_USER_NOTIF_FLAG_CONTINUE,
+    SYS_PIDFD_GETFD,
+};
+use crate::sys::unix::pidfd::PidFd;
+use crate::sys::unix::process_ext::SyscallFilter;
+use crate::sys::unix::syscall_table::syscall_number;
+use crate::sys::FromInner;
+use crate::{Error, Result};
+
+use nix::errno::Errno;
+use nix::libc::{
+    self, __u16, __u32, __u8, c_int, c_long, c_ushort, ioctl, pid_t, prctl, syscall, SYS_seccomp,
+    PR_SET_NO_NEW_PRIVS, PR_SET_SECCOMP,
+};
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::unistd::{close, Pid};
+
+use std::fs;
+use std::mem;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+
+#[cfg(target_arch = "x86")]
+pub const ARCH_NR: __u32 = missing_decls::AUDIT_ARCH_I386;
+
+#[cfg(target_arch = "x86_64")]
+pub const ARCH_NR: __u32 = missing_decls::AUDIT_ARCH_X86_64;
+
+// Offsets of the fields of `struct seccomp_data`.
+const NR_OFFSET: __u32 = 0;
+const ARCH_OFFSET: __u32 = 4;
+const ARGS_OFFSET: __u32 = 16;
+
+// The maximum number of instructions in a filter, see BPF_MAXINSNS.
+const MAX_INSNS: usize = 4096;
+
+// The kernel ignores the data of SECCOMP_RET_USER_NOTIF, so it's used to tell the exec gate
+// apart from kill actions in case notifications are not supported.
+const EXEC_GATE: __u32 = SECCOMP_RET_USER_NOTIF | 1;
+
+/// The action taken when a syscall matches a rule of a [`SeccompPolicy`].
+///
+/// [`SeccompPolicy`]: struct.SeccompPolicy.html
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SeccompAction {
+    Allow,
+    /// Fails the syscall with the given errno without executing it.
+    Errno(u16),
+    /// Sends `SIGSYS` to the calling thread.
+    Trap,
+    /// Allows the syscall after logging it to the audit log.
+    Log,
+    /// Kills the whole process. If the kernel supports it, the syscall is reported
+    /// to the [`SeccompListener`] which is responsible for killing the process.
+    ///
+    /// [`SeccompListener`]: struct.SeccompListener.html
+    Kill,
+}
+
+/// Comparison of a syscall argument with a value. Arguments are compared as unsigned.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ArgCmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Argument masked with the given value is equal to the value of a condition.
+    MaskedEq(u64),
+}
+
+/// Condition on the argument `index` of a syscall.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ArgCondition {
+    pub index: u8,
+    pub cmp: ArgCmp,
+    pub value: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    name: String,
+    nr: c_long,
+    conditions: Vec<ArgCondition>,
+    action: SeccompAction,
+}
+
+/// Describes which syscalls a process is allowed to make.
+///
+/// Rules are checked in the order they were added, the first matching rule
+/// determines the action. If none of the rules match, the default action is taken.
+///
+/// # Policy files
+///
+/// Each line of a policy file is either a comment starting with `#`,
+/// `default <action>`, `exec once` (see [`exec_once`]) or a rule:
+///
+/// ```text
+/// <action> <syscall>... [if <condition> [and <condition>]...]
+/// ```
+///
+/// where action is one of `allow`, `errno(<code>)`, `trap`, `log` or `kill`,
+/// and condition is `arg<index> [& <mask>] <op> <value>` with op being one of
+/// `==`, `!=`, `<`, `<=`, `>`, `>=`. Errno codes are numbers or names such as `EPERM`.
+/// For example:
+///
+/// ```text
+/// default kill
+/// allow read write close exit_group
+/// # O_RDONLY only.
+/// allow openat if arg2 & 3 == 0
+/// # AF_UNIX only.
+/// allow socket if arg0 == 1
+/// errno(EACCES) open
+/// ```
+///
+/// [`exec_once`]: #method.exec_once
+#[derive(Clone, Debug)]
+pub struct SeccompPolicy {
+    default_action: SeccompAction,
+    rules: Vec<Rule>,
+    exec_once: bool,
+}
+
+/// A syscall stopped by a filter until the listener decides what to do with it.
+#[derive(Copy, Clone, Debug)]
+pub struct Notification {
+    id: u64,
+    pub pid: Pid,
+    pub syscall: c_int,
+}
+
+/// Receives notifications about syscalls made by processes that installed
+/// a filter with [`install_filter`].
+///
+/// [`install_filter`]: fn.install_filter.html
+pub struct SeccompListener(RawFd);
+
+impl ArgCondition {
+    pub fn new(index: u8, cmp: ArgCmp, value: u64) -> Self {
+        Self { index, cmp, value }
+    }
+}
+
+impl SeccompPolicy {
+    pub fn new(default_action: SeccompAction) -> Self {
+        Self {
+            default_action,
+            rules: Vec::new(),
+            exec_once: false,
+        }
+    }
+
+    /// Allows only the exec of the program itself regardless of the rules, later `execve`
+    /// and `execveat` calls made by the program or its children kill them. Such a filter
+    /// can't be installed if the kernel doesn't support seccomp notifications (Linux 5.6).
+    pub fn exec_once(&mut self, v: bool) -> &mut Self {
+        self.exec_once = v;
+        self
+    }
+
+    /// Takes `action` on syscall `name` made with arguments satisfying all of the `conditions`.
+    pub fn rule(
+        &mut self,
+        name: &str,
+        conditions: &[ArgCondition],
+        action: SeccompAction,
+    ) -> Result<&mut Self> {
+        let nr = syscall_number(name)
+            .ok_or_else(|| Error::from(format!("Unknown syscall '{}'", name)))?;
+        if let Some(c) = conditions.iter().find(|c| c.index > 5) {
+            return Err(Error::from(format!(
+                "Syscall '{}' doesn't have argument {}",
+                name, c.index
+            )));
+        }
+        self.rules.push(Rule {
+            name: name.to_string(),
+            nr,
+            conditions: conditions.to_vec(),
+            action,
+        });
+        Ok(self)
+    }
+
+    /// Takes `action` on syscall `name` regardless of its arguments.
+    pub fn add(&mut self, name: &str, action: SeccompAction) -> Result<&mut Self> {
+        self.rule(name, &[], action)
+    }
+
+    /// Parses a policy in the format described above.
+    pub fn parse(policy: &str) -> Result<Self> {
+        let mut result = Self::new(SeccompAction::Kill);
+        for (i, line) in policy.lines().enumerate() {
+            result.parse_line(line).map_err(|e| {
+                Error::from(format!("Invalid seccomp policy at line {}: {}", i + 1, e))
+            })?;
+        }
+        Ok(result)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let policy = fs::read_to_string(path).map_err(|e| {
+            Error::from(format!(
+                "Cannot read seccomp policy {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Self::parse(&policy)
+    }
+
+    /// Compiles the policy into a BPF program.
+    pub fn build(&self) -> Result<SyscallFilter> {
+        let mut prog = vec![
+            // Validate architecture.
+            bpf_stmt(BPF_LD + BPF_W + BPF_ABS, ARCH_OFFSET),
+            bpf_jump(BPF_JMP + BPF_JEQ + BPF_K, ARCH_NR, 1, 0),
+            bpf_stmt(BPF_RET + BPF_K, SECCOMP_RET_KILL_PROCESS),
+        ];
+        #[cfg(target_arch = "x86_64")]
+        prog.extend_from_slice(&[
+            // x32 syscalls share the architecture with x86_64, but have different numbers.
+            bpf_stmt(BPF_LD + BPF_W + BPF_ABS, NR_OFFSET),
+            bpf_jump(BPF_JMP + BPF_JGE + BPF_K, X32_SYSCALL_BIT, 0, 1),
+            bpf_stmt(BPF_RET + BPF_K, SECCOMP_RET_KILL_PROCESS),
+        ]);
+        if self.exec_once {
+            prog.extend_from_slice(&[
+                bpf_stmt(BPF_LD + BPF_W + BPF_ABS, NR_OFFSET),
+                bpf_jump(BPF_JMP + BPF_JEQ + BPF_K, libc::SYS_execve as __u32, 1, 0),
+                bpf_jump(BPF_JMP + BPF_JEQ + BPF_K, libc::SYS_execveat as __u32, 0, 1),
+                bpf_stmt(BPF_RET + BPF_K, EXEC_GATE),
+            ]);
+        }
+        for rule in self.rules.iter() {
+            prog.extend(compile_rule(rule)?);
+        }
+        prog.push(bpf_stmt(BPF_RET + BPF_K, action_value(self.default_action)));
+
+        if prog.len() > MAX_INSNS {
+            return Err(Error::from("Seccomp policy is too large"));
+        }
+        Ok(SyscallFilter::from_inner(prog))
+    }
+
+    fn parse_line(&mut self, line: &str) -> std::result::Result<(), String> {
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let first = match tokens.next() {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        if first == "exec" {
+            return match (tokens.next(), tokens.next()) {
+                (Some("once"), None) => {
+                    self.exec_once(true);
+                    Ok(())
+                }
+                _ => Err(String::from("Expected 'exec once'")),
+            };
+        }
+        if first == "default" {
+            let action = tokens
+                .next()
+                .ok_or_else(|| String::from("Missing default action"))?;
+            self.default_action = parse_action(action)?;
+            return match tokens.next() {
+                Some(t) => Err(format!("Unexpected '{}'", t)),
+                None => Ok(()),
+            };
+        }
+
+        let action = parse_action(first)?;
+        let mut names = Vec::new();
+        for token in tokens.by_ref() {
+            if token == "if" {
+                break;
+            }
+            names.push(token);
+        }
+        if names.is_empty() {
+            return Err(String::from("Missing syscall name"));
+        }
+        let conditions = parse_conditions(&tokens.collect::<Vec<_>>())?;
+        for name in names {
+            self.rule(name, &conditions, action)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+impl SeccompListener {
+    /// Duplicates listener `fd` of the process `pid`.
+    pub fn from_process(pid: Pid, fd: RawFd) -> nix::Result<Self> {
+        PidFd::open(pid)?.get_fd(fd).map(Self)
+    }
+
+    /// Returns the pending notification, if any.
+    pub fn try_recv(&self) -> nix::Result<Option<Notification>> {
+        let mut fds = [PollFd::new(self.0, PollFlags::POLLIN)];
+        poll(&mut fds, 0)?;
+        // POLLHUP alone means that there are no processes using the filter left.
+        if !fds[0]
+            .revents()
+            .is_some_and(|r| r.contains(PollFlags::POLLIN))
+        {
+            return Ok(None);
+        }
+
+        let mut notif: seccomp_notif = unsafe { mem::zeroed() };
+        match Errno::result(unsafe { ioctl(self.0, SECCOMP_IOCTL_NOTIF_RECV, &mut notif) }) {
+            Ok(_) => Ok(Some(Notification {
+                id: notif.id,
+                pid: Pid::from_raw(notif.pid as pid_t),
+                syscall: notif.data.nr,
+            })),
+            // The process was killed before we got the notification.
+            Err(nix::Error::Sys(Errno::ENOENT)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Lets the process make the syscall it was stopped at.
+    pub fn allow(&self, n: &Notification) -> nix::Result<()> {
+        let resp = seccomp_notif_resp {
+            id: n.id,
+            val: 0,
+            error: 0,
+            flags: SECCOMP_USER_NOTIF_FLAG_CONTINUE,
+        };
+        match Errno::result(unsafe { ioctl(self.0, SECCOMP_IOCTL_NOTIF_SEND, &resp) }) {
+            Ok(_) | Err(nix::Error::Sys(Errno::ENOENT)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Notification {
+    pub fn is_exec(&self) -> bool {
+        let nr = c_long::from(self.syscall);
+        nr == libc::SYS_execve || nr == libc::SYS_execveat
+    }
+}
+
+impl AsRawFd for SeccompListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl Drop for SeccompListener {
+    fn drop(&mut self) {
+        let _ = close(self.0);
+    }
+}
+
+/// Installs `filter` for the calling thread. If the kernel allows the supervisor to get
+/// the listener of the filter, kill actions are turned into notifications and the listener
+/// fd is returned. Otherwise processes are killed by the kernel, and a filter restricting
+/// exec is rejected with `ENOSYS`, since the kernel alone can't allow only the first exec.
+pub fn install_filter(filter: &mut [sock_filter]) -> nix::Result<Option<RawFd>> {
+    if unsafe { prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } == -1 {
+        return Err(nix::Error::last());
+    }
+
+    let is_notify = |insn: &sock_filter| {
+        insn.code == BPF_RET + BPF_K && insn.k & SECCOMP_RET_ACTION_FULL == SECCOMP_RET_USER_NOTIF
+    };
+    let mut prog = sock_fprog {
+        len: filter.len() as c_ushort,
+        filter: filter.as_mut_ptr(),
+    };
+    if filter.iter().any(is_notify) && can_get_listener() {
+        let result = unsafe {
+            syscall(
+                SYS_seccomp,
+                SECCOMP_SET_MODE_FILTER,
+                SECCOMP_FILTER_FLAG_NEW_LISTENER,
+                &mut prog,
+            )
+        };
+        match Errno::result(result) {
+            Ok(fd) => return Ok(Some(fd as RawFd)),
+            Err(nix::Error::Sys(Errno::EINVAL)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    if has_exec_gate(filter) {
+        return Err(nix::Error::Sys(Errno::ENOSYS));
+    }
+    for insn in filter.iter_mut().filter(|insn| is_notify(insn)) {
+        insn.k = SECCOMP_RET_KILL_PROCESS;
+    }
+    if unsafe { prctl(PR_SET_SECCOMP, SECCOMP_MODE_FILTER, &mut prog) } == -1 {
+        return Err(nix::Error::last());
+    }
+    Ok(None)
+}
+
+/// Checks whether `filter` allows only the exec of the program itself.
+pub fn has_exec_gate(filter: &[sock_filter]) -> bool {
+    filter
+        .iter()
+        .any(|insn| insn.code == BPF_RET + BPF_K && insn.k == EXEC_GATE)
+}
+
+/// Checks whether the supervisor can get the listener of a filter, see `install_filter`.
+pub fn can_get_listener() -> bool {
+    let result = unsafe { syscall(SYS_PIDFD_GETFD, -1, -1, 0) };
+    Errno::result(result) != Err(nix::Error::Sys(Errno::ENOSYS))
+}
+
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: __u32 = 0x4000_0000;
+
+/// Jump target inside a rule.
+#[derive(Copy, Clone)]
+enum Target {
+    Next,
+    Skip(u8),
+    /// The first instruction after the rule.
+    Mismatch,
+}
+
+fn compile_rule(rule: &Rule) -> Result<Vec<sock_filter>> {
+    use self::Target::*;
+
+    let mut insns = vec![
+        (bpf_stmt(BPF_LD + BPF_W + BPF_ABS, NR_OFFSET), Next, Next),
+        (
+            bpf_jump(BPF_JMP + BPF_JEQ + BPF_K, rule.nr as __u32, 0, 0),
+            Next,
+            Mismatch,
+        ),
+    ];
+    for c in rule.conditions.iter() {
+        // Arguments are 64-bit little-endian, compare the high word first.
+        let lo_offset = ARGS_OFFSET + 8 * c.index as __u32;
+        let hi_offset = lo_offset + 4;
+        let lo = c.value as __u32;
+        let hi = (c.value >> 32) as __u32;
+        let load_hi = (bpf_stmt(BPF_LD + BPF_W + BPF_ABS, hi_offset), Next, Next);
+        let load_lo = (bpf_stmt(BPF_LD + BPF_W + BPF_ABS, lo_offset), Next, Next);
+        let jeq = BPF_JMP + BPF_JEQ + BPF_K;
+        let jgt = BPF_JMP + BPF_JGT + BPF_K;
+        let jge = BPF_JMP + BPF_JGE + BPF_K;
+        match c.cmp {
+            ArgCmp::Eq => insns.extend_from_slice(&[
+                load_hi,
+                (bpf_jump(jeq, hi, 0, 0), Next, Mismatch),
+                load_lo,
+                (bpf_jump(jeq, lo, 0, 0), Next, Mismatch),
+            ]),
+            ArgCmp::Ne => insns.extend_from_slice(&[
+                load_hi,
+                (bpf_jump(jeq, hi, 0, 0), Next, Skip(2)),
+                load_lo,
+                (bpf_jump(jeq, lo, 0, 0), Mismatch, Next),
+            ]),
+            ArgCmp::MaskedEq(mask) => insns.extend_from_slice(&[
+                load_hi,
+                (
+                    bpf_stmt(BPF_ALU + BPF_AND + BPF_K, (mask >> 32) as __u32),
+                    Next,
+                    Next,
+                ),
+                (bpf_jump(jeq, hi, 0, 0), Next, Mismatch),
+                load_lo,
+                (
+                    bpf_stmt(BPF_ALU + BPF_AND + BPF_K, mask as __u32),
+                    Next,
+                    Next,
+                ),
+                (bpf_jump(jeq, lo, 0, 0), Next, Mismatch),
+            ]),
+            ArgCmp::Gt | ArgCmp::Ge => insns.extend_from_slice(&[
+                load_hi,
+                (bpf_jump(jgt, hi, 0, 0), Skip(3), Next),
+                (bpf_jump(jeq, hi, 0, 0), Next, Mismatch),
+                load_lo,
+                (
+                    bpf_jump(if c.cmp == ArgCmp::Gt { jgt } else { jge }, lo, 0, 0),
+                    Next,
+                    Mismatch,
+                ),
+            ]),
+            ArgCmp::Lt | ArgCmp::Le => insns.extend_from_slice(&[
+                load_hi,
+                (bpf_jump(jge, hi, 0, 0), Next, Skip(3)),
+                (bpf_jump(jeq, hi, 0, 0), Next, Mismatch),
+                load_lo,
+                (
+                    bpf_jump(if c.cmp == ArgCmp::Lt { jge } else { jgt }, lo, 0, 0),
+                    Mismatch,
+                    Next,
+                ),
+            ]),
+        }
+    }
+    insns.push((
+        bpf_stmt(BPF_RET + BPF_K, action_value(rule.action)),
+        Next,
+        Next,
+    ));
+
+    let len = insns.len();
+    insns
+        .into_iter()
+        .enumerate()
+        .map(|(i, (mut insn, jt, jf))| {
+            let resolve = |target| match target {
+                Next => Some(0),
+                Skip(n) => Some(n),
+                Mismatch => {
+                    let offset = len - i - 1;
+                    if offset > usize::from(u8::MAX) {
+                        None
+                    } else {
+                        Some(offset as __u8)
+                    }
+                }
+            };
+            match (resolve(jt), resolve(jf)) {
+                (Some(jt), Some(jf)) => {
+                    insn.jt = jt;
+                    insn.jf = jf;
+                    Ok(insn)
+                }
+                _ => Err(Error::from(format!(
+                    "Too many conditions for syscall '{}'",
+                    rule.name
+                ))),
+            }
+        })
+        .collect()
+}
+
+fn action_value(action: SeccompAction) -> __u32 {
+    match action {
+        SeccompAction::Allow => SECCOMP_RET_ALLOW,
+        SeccompAction::Errno(e) => SECCOMP_RET_ERRNO | (e as __u32 & SECCOMP_RET_DATA),
+        SeccompAction::Trap => SECCOMP_RET_TRAP,
+        SeccompAction::Log => SECCOMP_RET_LOG,
+        SeccompAction::Kill => SECCOMP_RET_USER_NOTIF,
+    }
+}
+
+fn parse_action(s: &str) -> std::result::Result<SeccompAction, String> {
+    match s {
+        "allow" => return Ok(SeccompAction::Allow),
+        "trap" => return Ok(SeccompAction::Trap),
+        "log" => return Ok(SeccompAction::Log),
+        "kill" => return Ok(SeccompAction::Kill),
+        _ => {}
+    }
+    let errno = s
+        .strip_prefix("errno(")
+        .and_then(|s| s.strip_suffix(')'))
+        .ok_or_else(|| format!("Unknown action '{}'", s))?;
+    parse_errno(errno)
+        .map(SeccompAction::Errno)
+        .ok_or_else(|| format!("Unknown errno '{}'", errno))
+}
+
+fn parse_errno(s: &str) -> Option<u16> {
+    if let Ok(v) = s.parse::<u16>() {
+        return Some(v);
+    }
+    (1..=Errno::EHWPOISON as i32)
+        .map(Errno::from_i32)
+        .find(|e| format!("{:?}", e) == s)
+        .map(|e| e as u16)
+}
+
+fn parse_conditions(tokens: &[&str]) -> std::result::Result<Vec<ArgCondition>, String> {
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+    tokens
+        .split(|t| *t == "and")
+        .map(|c| match c {
+            [arg, op, value] => Ok(ArgCondition::new(
+                parse_arg_index(arg)?,
+                parse_cmp(op)?,
+                parse_number(value)?,
+            )),
+            [arg, "&", mask, "==", value] => Ok(ArgCondition::new(
+                parse_arg_index(arg)?,
+                ArgCmp::MaskedEq(parse_number(mask)?),
+                parse_number(value)?,
+            )),
+            _ => Err(format!("Invalid condition '{}'", c.join(" "))),
+        })
+        .collect()
+}
+
+fn parse_arg_index(s: &str) -> std::result::Result<u8, String> {
+    s.strip_prefix("arg")
+        .and_then(|i| i.parse::<u8>().ok())
+        .ok_or_else(|| format!("Invalid argument '{}'", s))
+}
+
+fn parse_cmp(s: &str) -> std::result::Result<ArgCmp, String> {
+    match s {
+        "==" => Ok(ArgCmp::Eq),
+        "!=" => Ok(ArgCmp::Ne),
+        "<" => Ok(ArgCmp::Lt),
+        "<=" => Ok(ArgCmp::Le),
+        ">" => Ok(ArgCmp::Gt),
+        ">=" => Ok(ArgCmp::Ge),
+        _ => Err(format!("Unknown comparison '{}'", s)),
+    }
+}
+
+fn parse_number(s: &str) -> std::result::Result<u64, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse::<u64>(),
+    }
+    .map_err(|_| format!("Invalid number '{}'", s))
+}
+
+pub fn bpf_stmt(code: __u16, k: __u32) -> sock_filter {
+    sock_filter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+pub fn bpf_jump(code: __u16, k: __u32, jt: __u8, jf: __u8) -> sock_filter {
+    sock_filter { code, jt, jf, k }
+}
@@ -0,0 +1,167 @@
+use crate::sys::unix::cgroup::Cgroups;
+use crate::sys::unix::hierarchy::KILL_TIMEOUT;
+use crate::{Error, Result};
+
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::libc;
+use nix::sys::signal::{signal, SigHandler, Signal};
+use nix::sys::wait::waitpid;
+use nix::unistd::{close, fork, pipe2, read, ForkResult, Pid};
+
+use std::ffi::{CStr, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A process that kills the tasks of a group and removes its cgroups once the spawner is done
+/// with them, even if the spawner is killed or crashes before the group is dropped. It shares
+/// the lock of the group, so the cgroups aren't taken for orphans until it's done.
+pub struct Watchdog {
+    pid: Pid,
+    /// The watchdog wakes up once this end of the pipe is closed, e.g. when the spawner exits.
+    pipe: RawFd,
+}
+
+/// Files of a group, prepared before the fork: the watchdog is forked from a multithreaded
+/// process, so it must not allocate.
+struct GroupFiles {
+    /// `cgroup.kill` of the unified hierarchy, available since Linux 5.14.
+    kill: Option<CString>,
+    procs: CString,
+    dirs: Vec<CString>,
+}
+
+impl Watchdog {
+    /// Every file except `keep_fds` is closed in the watchdog, so that it doesn't hold
+    /// the pipes of programs open.
+    pub fn spawn(cgroups: &Cgroups, keep_fds: &[RawFd]) -> Result<Self> {
+        let files = GroupFiles::new(cgroups)?;
+        let (read_end, write_end) = pipe2(OFlag::O_CLOEXEC)?;
+        let fds = procfs::process::Process::myself()
+            .and_then(|ps| ps.fd())
+            .map_err(|e| Error::from(e.to_string()))?
+            .into_iter()
+            .map(|fd_info| fd_info.fd as RawFd)
+            .filter(|fd| *fd != read_end && !keep_fds.contains(fd))
+            .collect::<Vec<_>>();
+        match fork() {
+            Ok(ForkResult::Parent { child }) => {
+                let _ = close(read_end);
+                Ok(Self {
+                    pid: child,
+                    pipe: write_end,
+                })
+            }
+            Ok(ForkResult::Child) => {
+                for fd in fds.iter() {
+                    let _ = close(*fd);
+                }
+                watch(&files, read_end);
+                unsafe { libc::_exit(0) }
+            }
+            Err(e) => {
+                let _ = close(read_end);
+                let _ = close(write_end);
+                Err(Error::from(e))
+            }
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        let _ = close(self.pipe);
+        let _ = waitpid(self.pid, None);
+    }
+}
+
+impl GroupFiles {
+    fn new(cgroups: &Cgroups) -> Result<Self> {
+        let c_path = |path: &Path| {
+            CString::new(path.as_os_str().as_bytes()).map_err(|e| Error::from(e.to_string()))
+        };
+        let dirs = cgroups.dirs();
+        let kill = dirs[0].join("cgroup.kill");
+        Ok(Self {
+            kill: Some(kill)
+                .filter(|f| f.exists())
+                .as_deref()
+                .map(c_path)
+                .transpose()?,
+            procs: c_path(&dirs[0].join("cgroup.procs"))?,
+            dirs: dirs.iter().map(|d| c_path(d)).collect::<Result<_>>()?,
+        })
+    }
+}
+
+fn watch(files: &GroupFiles, pipe: RawFd) {
+    // Signals from the terminal are sent to the whole process group, the spawner handles them.
+    for sig in [Signal::SIGINT, Signal::SIGQUIT, Signal::SIGHUP].iter() {
+        unsafe {
+            let _ = signal(*sig, SigHandler::SigIgn);
+        }
+    }
+
+    // Nothing is written to the pipe, so the read returns once the write end is closed.
+    while let Err(nix::Error::Sys(Errno::EINTR)) = read(pipe, &mut [0]) {}
+
+    // Normally the cgroups are removed by now, unless some tasks were still exiting.
+    if unsafe { libc::access(files.dirs[0].as_ptr(), libc::F_OK) } != 0 {
+        return;
+    }
+    let killed = files.kill.as_ref().is_some_and(|f| write_file(f, b"1"));
+    let start = Instant::now();
+    // Without `cgroup.kill` tasks forked meanwhile are killed on the next iterations.
+    while kill_procs(&files.procs, !killed) && start.elapsed() < KILL_TIMEOUT {
+        thread::sleep(Duration::from_millis(10));
+    }
+    for dir in files.dirs.iter() {
+        unsafe {
+            libc::rmdir(dir.as_ptr());
+        }
+    }
+}
+
+fn write_file(path: &CStr, value: &[u8]) -> bool {
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+    if fd == -1 {
+        return false;
+    }
+    let written = unsafe { libc::write(fd, value.as_ptr() as *const libc::c_void, value.len()) };
+    let _ = close(fd);
+    written == value.len() as isize
+}
+
+/// Reads pids from `procs` and kills them if `kill` is set. Returns whether there were any.
+fn kill_procs(procs: &CStr, kill: bool) -> bool {
+    let fd = unsafe { libc::open(procs.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC) };
+    if fd == -1 {
+        return false;
+    }
+    let mut buf = [0_u8; 4096];
+    let mut pid: libc::pid_t = 0;
+    let mut found = false;
+    while let Ok(len) = read(fd, &mut buf) {
+        if len == 0 {
+            break;
+        }
+        for &c in buf[..len].iter() {
+            if c.is_ascii_digit() {
+                pid = pid * 10 + libc::pid_t::from(c - b'0');
+            } else if pid != 0 {
+                found = true;
+                if kill {
+                    unsafe {
+                        libc::kill(pid, libc::SIGKILL);
+                    }
+                }
+                pid = 0;
+            }
+        }
+    }
+    let _ = close(fd);
+    found
+}
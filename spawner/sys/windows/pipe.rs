@@ -47,6 +47,14 @@ pub fn create() -> Result<(ReadPipe, WritePipe)> {
     ))
 }
 
+/// Anonymous pipes don't support waiting for readiness.
+pub fn poll(_read: &[&ReadPipe], _write: &[&WritePipe]) -> io::Result<Vec<bool>> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Polling pipes is not supported",
+    ))
+}
+
 impl ReadPipe {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         open(path, GENERIC_READ, OPEN_EXISTING, false).map(Self)
@@ -59,6 +67,10 @@ impl ReadPipe {
     pub fn null() -> Result<Self> {
         Self::open("nul")
     }
+
+    pub fn set_nonblocking(&self) -> Result<()> {
+        Err(Error::from("Nonblocking pipes are not supported"))
+    }
 }
 
 impl IntoInner<Handle> for ReadPipe {
@@ -96,6 +108,10 @@ impl WritePipe {
     pub fn null() -> Result<Self> {
         open("nul", GENERIC_WRITE, OPEN_EXISTING, false).map(Self)
     }
+
+    pub fn set_nonblocking(&self) -> Result<()> {
+        Err(Error::from("Nonblocking pipes are not supported"))
+    }
 }
 
 impl IntoInner<Handle> for WritePipe {
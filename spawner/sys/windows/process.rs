@@ -1,5 +1,6 @@
 use crate::process::{
-    ExitStatus, GroupIo, GroupMemory, GroupNetwork, GroupPidCounters, GroupTimers, OsLimit,
+    Crash, ExitStatus, GroupIo, GroupMemory, GroupNetwork, GroupPidCounters, GroupTimers, OsLimit,
+    Rusage,
 };
 use crate::sys::windows::helpers::{
     cvt, to_utf16, Endpoints, EnvBlock, Handle, JobNotifications, PidList, RawStdio, StartupInfo,
@@ -8,37 +9,44 @@ use crate::sys::windows::helpers::{
 use crate::sys::windows::pipe::{ReadPipe, WritePipe};
 use crate::sys::windows::process_ext::UiRestrictions;
 use crate::sys::IntoInner;
-use crate::{Error, Result};
+use crate::{Error, Result, TerminationReason};
 
-use winapi::shared::minwindef::{DWORD, LPVOID, TRUE};
+use winapi::shared::basetsd::ULONG_PTR;
+use winapi::shared::minwindef::{DWORD, FALSE, LPVOID, TRUE};
+use winapi::shared::winerror::WAIT_TIMEOUT;
 use winapi::um::errhandlingapi::SetErrorMode;
+use winapi::um::handleapi::INVALID_HANDLE_VALUE;
 use winapi::um::jobapi2::{
     AssignProcessToJobObject, CreateJobObjectW, QueryInformationJobObject, SetInformationJobObject,
     TerminateJobObject,
 };
 use winapi::um::minwinbase::STILL_ACTIVE;
 use winapi::um::processthreadsapi::{
-    CreateProcessAsUserW, CreateProcessW, GetExitCodeProcess, ResumeThread, SuspendThread,
-    TerminateProcess, PROCESS_INFORMATION,
+    CreateProcessAsUserW, CreateProcessW, GetExitCodeProcess, OpenThread, ResumeThread,
+    SuspendThread, TerminateProcess, PROCESS_INFORMATION,
+};
+use winapi::um::synchapi::{WaitForMultipleObjects, WaitForSingleObject};
+use winapi::um::tlhelp32::{
+    CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
 };
 use winapi::um::winbase::{
-    CREATE_SUSPENDED, CREATE_UNICODE_ENVIRONMENT, EXTENDED_STARTUPINFO_PRESENT,
-    SEM_FAILCRITICALERRORS, SEM_NOGPFAULTERRORBOX,
+    CREATE_SUSPENDED, CREATE_UNICODE_ENVIRONMENT, EXTENDED_STARTUPINFO_PRESENT, INFINITE,
+    SEM_FAILCRITICALERRORS, SEM_NOGPFAULTERRORBOX, WAIT_OBJECT_0,
 };
 use winapi::um::winnt::{
     JobObjectBasicAccountingInformation, JobObjectBasicAndIoAccountingInformation,
-    JobObjectBasicUIRestrictions, JobObjectExtendedLimitInformation, JOBOBJECTINFOCLASS,
+    JobObjectBasicUIRestrictions, JobObjectExtendedLimitInformation, HANDLE, JOBOBJECTINFOCLASS,
     JOBOBJECT_BASIC_ACCOUNTING_INFORMATION, JOBOBJECT_BASIC_AND_IO_ACCOUNTING_INFORMATION,
     JOBOBJECT_BASIC_UI_RESTRICTIONS, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
-    JOB_OBJECT_LIMIT_ACTIVE_PROCESS, JOB_OBJECT_LIMIT_JOB_MEMORY, STATUS_ACCESS_VIOLATION,
-    STATUS_ARRAY_BOUNDS_EXCEEDED, STATUS_BREAKPOINT, STATUS_CONTROL_C_EXIT,
-    STATUS_DATATYPE_MISALIGNMENT, STATUS_FLOAT_DENORMAL_OPERAND, STATUS_FLOAT_INEXACT_RESULT,
-    STATUS_FLOAT_INVALID_OPERATION, STATUS_FLOAT_MULTIPLE_FAULTS, STATUS_FLOAT_MULTIPLE_TRAPS,
-    STATUS_FLOAT_OVERFLOW, STATUS_FLOAT_STACK_CHECK, STATUS_FLOAT_UNDERFLOW,
-    STATUS_GUARD_PAGE_VIOLATION, STATUS_ILLEGAL_INSTRUCTION, STATUS_INTEGER_DIVIDE_BY_ZERO,
-    STATUS_INTEGER_OVERFLOW, STATUS_INVALID_DISPOSITION, STATUS_IN_PAGE_ERROR,
-    STATUS_NONCONTINUABLE_EXCEPTION, STATUS_PRIVILEGED_INSTRUCTION, STATUS_REG_NAT_CONSUMPTION,
-    STATUS_SINGLE_STEP, STATUS_STACK_OVERFLOW,
+    JOB_OBJECT_LIMIT_ACTIVE_PROCESS, JOB_OBJECT_LIMIT_JOB_MEMORY, MAXIMUM_WAIT_OBJECTS,
+    STATUS_ACCESS_VIOLATION, STATUS_ARRAY_BOUNDS_EXCEEDED, STATUS_BREAKPOINT,
+    STATUS_CONTROL_C_EXIT, STATUS_DATATYPE_MISALIGNMENT, STATUS_FLOAT_DENORMAL_OPERAND,
+    STATUS_FLOAT_INEXACT_RESULT, STATUS_FLOAT_INVALID_OPERATION, STATUS_FLOAT_MULTIPLE_FAULTS,
+    STATUS_FLOAT_MULTIPLE_TRAPS, STATUS_FLOAT_OVERFLOW, STATUS_FLOAT_STACK_CHECK,
+    STATUS_FLOAT_UNDERFLOW, STATUS_GUARD_PAGE_VIOLATION, STATUS_ILLEGAL_INSTRUCTION,
+    STATUS_INTEGER_DIVIDE_BY_ZERO, STATUS_INTEGER_OVERFLOW, STATUS_INVALID_DISPOSITION,
+    STATUS_IN_PAGE_ERROR, STATUS_NONCONTINUABLE_EXCEPTION, STATUS_PRIVILEGED_INSTRUCTION,
+    STATUS_REG_NAT_CONSUMPTION, STATUS_SINGLE_STEP, STATUS_STACK_OVERFLOW, THREAD_SUSPEND_RESUME,
 };
 
 use std::cell::RefCell;
@@ -46,6 +54,7 @@ use std::collections::HashMap;
 use std::fmt::{self, Write};
 use std::mem::{size_of_val, zeroed};
 use std::ptr;
+use std::thread;
 use std::time::Duration;
 use std::u32;
 
@@ -81,6 +90,22 @@ pub struct Process {
 
 unsafe impl Send for Process {}
 
+unsafe impl Send for Poller {}
+
+/// The exit of a process is the only notification, OS limits of a job are polled.
+pub struct Events {
+    process: HANDLE,
+    exited: bool,
+}
+
+/// Wakeups aren't notified, `Events` don't watch wakers.
+pub struct Waker;
+
+/// Waits for the exit of several processes at once.
+pub struct Poller {
+    processes: Vec<HANDLE>,
+}
+
 pub struct ResourceUsage<'a> {
     group: &'a Group,
     pid_list: RefCell<PidList>,
@@ -218,7 +243,12 @@ impl Process {
                     TerminateProcess(self.handle.raw(), 0);
                 }
                 Some(match crash_cause(exit_code) {
-                    Some(cause) => ExitStatus::Crashed(cause.to_string()),
+                    Some(cause) => ExitStatus::Crashed(Crash {
+                        signal: None,
+                        core_dumped: false,
+                        fault_code: Some(exit_code),
+                        cause: cause.to_string(),
+                    }),
                     None => ExitStatus::Finished(exit_code),
                 })
             }
@@ -245,6 +275,21 @@ impl Process {
         }
     }
 
+    pub fn rusage(&self) -> Option<Rusage> {
+        None
+    }
+
+    pub fn termination_reason(&self) -> Option<TerminationReason> {
+        None
+    }
+
+    pub fn events(&self, _group: &Group) -> Result<Events> {
+        Ok(Events {
+            process: self.handle.raw(),
+            exited: false,
+        })
+    }
+
     pub fn terminate(&self) -> Result<()> {
         unsafe {
             cvt(TerminateProcess(self.handle.raw(), 0))?;
@@ -369,6 +414,106 @@ macro_rules! count_endpoints {
     };
 }
 
+impl Events {
+    pub fn is_exit_notified(&self) -> bool {
+        !self.exited
+    }
+
+    pub fn is_os_limit_notified(&self) -> bool {
+        false
+    }
+
+    pub fn watch(&mut self, _waker: &Waker) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn is_waker_notified(&self) -> bool {
+        false
+    }
+
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<bool> {
+        if self.exited {
+            thread::sleep(timeout.unwrap_or_default());
+            return Ok(false);
+        }
+        self.wait_for_exit(timeout_ms(timeout))
+    }
+
+    pub fn poll(&mut self) -> Result<bool> {
+        if self.exited {
+            return Ok(false);
+        }
+        self.wait_for_exit(0)
+    }
+
+    fn wait_for_exit(&mut self, timeout_ms: DWORD) -> Result<bool> {
+        match unsafe { WaitForSingleObject(self.process, timeout_ms) } {
+            WAIT_OBJECT_0 => {
+                self.exited = true;
+                Ok(true)
+            }
+            WAIT_TIMEOUT => Ok(false),
+            _ => Err(Error::last_os_error()),
+        }
+    }
+}
+
+impl Waker {
+    pub fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    pub fn wake(&self) {}
+}
+
+impl Poller {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            processes: Vec::new(),
+        })
+    }
+
+    pub fn add(&mut self, events: &Events) -> Result<()> {
+        if !events.exited {
+            self.processes.push(events.process);
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, events: &Events) -> Result<()> {
+        self.processes.retain(|&p| p != events.process);
+        Ok(())
+    }
+
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<()> {
+        if self.processes.is_empty() {
+            thread::sleep(timeout.unwrap_or_default());
+            return Ok(());
+        }
+
+        // Processes past the limit of WaitForMultipleObjects are polled.
+        let count = self.processes.len().min(MAXIMUM_WAIT_OBJECTS as usize);
+        let timeout_ms = if count < self.processes.len() {
+            timeout_ms(timeout).min(1)
+        } else {
+            timeout_ms(timeout)
+        };
+        let result = unsafe {
+            WaitForMultipleObjects(count as DWORD, self.processes.as_ptr(), FALSE, timeout_ms)
+        };
+        if result >= WAIT_OBJECT_0 && result < WAIT_OBJECT_0 + count as DWORD {
+            // An exited process stays signaled, its exit is handled by `Events::poll`.
+            self.processes
+                .swap_remove((result - WAIT_OBJECT_0) as usize);
+            Ok(())
+        } else if result == WAIT_TIMEOUT {
+            Ok(())
+        } else {
+            Err(Error::last_os_error())
+        }
+    }
+}
+
 impl<'a> ResourceUsage<'a> {
     pub fn new(group: &'a Group) -> Self {
         Self {
@@ -382,6 +527,10 @@ impl<'a> ResourceUsage<'a> {
         Ok(())
     }
 
+    pub fn sample_process_memory(&mut self) {}
+
+    pub fn exclude_kernel_memory(&mut self) {}
+
     pub fn timers(&self) -> Result<Option<GroupTimers>> {
         self.group.basic_info().map(|info| {
             // Total user time in 100-nanosecond ticks.
@@ -392,22 +541,31 @@ impl<'a> ResourceUsage<'a> {
             Some(GroupTimers {
                 total_user_time: Duration::from_nanos(total_user_time * 100),
                 total_kernel_time: Duration::from_nanos(total_kernel_time * 100),
+                cpu_counter: None,
             })
         })
     }
 
     pub fn memory(&self) -> Result<Option<GroupMemory>> {
-        self.group.ext_limit_info().map(|info| {
-            Some(GroupMemory {
-                max_usage: info.PeakJobMemoryUsed as u64,
-            })
-        })
+        let info = self.group.ext_limit_info()?;
+        Ok(Some(GroupMemory {
+            max_usage: info.PeakJobMemoryUsed as u64,
+            max_process_usage: info.PeakProcessMemoryUsed as u64,
+            // Allocations beyond the limit fail, processes aren't killed.
+            limit_hits: self.group.is_os_limit_hit(OsLimit::Memory)? as u64,
+            oom_kills: 0,
+        }))
     }
 
     pub fn io(&self) -> Result<Option<GroupIo>> {
         self.group.basic_and_io_info().map(|info| {
             Some(GroupIo {
+                total_bytes_read: info.IoInfo.ReadTransferCount,
                 total_bytes_written: info.IoInfo.WriteTransferCount,
+                disk_bytes_read: 0,
+                disk_bytes_written: 0,
+                disk_reads: 0,
+                disk_writes: 0,
             })
         })
     }
@@ -484,6 +642,11 @@ impl Group {
                 ext_limit_info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_ACTIVE_PROCESS;
                 ext_limit_info.BasicLimitInformation.ActiveProcessLimit = value as DWORD;
             }
+            OsLimit::Swap
+            | OsLimit::MemoryHigh
+            | OsLimit::DiskReadBandwidth
+            | OsLimit::DiskWriteBandwidth
+            | OsLimit::CpuQuota => return Ok(false),
         }
 
         unsafe {
@@ -498,11 +661,18 @@ impl Group {
         Ok(true)
     }
 
+    pub fn enable_cpu_counter(&mut self, _required: bool) {}
+
     pub fn is_os_limit_hit(&self, limit: OsLimit) -> Result<bool> {
         let mut notifications = self.notifications.borrow_mut();
         match limit {
             OsLimit::Memory => notifications.is_memory_limit_hit(),
             OsLimit::ActiveProcess => notifications.is_active_process_limit_hit(),
+            OsLimit::Swap
+            | OsLimit::MemoryHigh
+            | OsLimit::DiskReadBandwidth
+            | OsLimit::DiskWriteBandwidth
+            | OsLimit::CpuQuota => Ok(false),
         }
     }
 
@@ -511,6 +681,48 @@ impl Group {
         Ok(())
     }
 
+    pub fn send_signal(&self, _signal: i32) -> Result<bool> {
+        Ok(false)
+    }
+
+    pub fn suspend(&self) -> Result<()> {
+        self.for_each_thread(|thread| unsafe { SuspendThread(thread) })
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        self.for_each_thread(|thread| unsafe { ResumeThread(thread) })
+    }
+
+    fn for_each_thread<F>(&self, f: F) -> Result<()>
+    where
+        F: Fn(HANDLE) -> DWORD,
+    {
+        let mut pid_list = PidList::new();
+        let pids = pid_list.update(&self.job)?;
+        let snapshot = match unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) } {
+            INVALID_HANDLE_VALUE => return Err(Error::last_os_error()),
+            handle => Handle::new(handle),
+        };
+
+        let mut entry: THREADENTRY32 = unsafe { zeroed() };
+        entry.dwSize = size_of_val(&entry) as DWORD;
+        let mut has_entry = unsafe { Thread32First(snapshot.raw(), &mut entry) } == TRUE;
+        while has_entry {
+            if pids.contains(&(entry.th32OwnerProcessID as ULONG_PTR)) {
+                // The thread may have exited since the snapshot was taken.
+                let thread =
+                    unsafe { OpenThread(THREAD_SUSPEND_RESUME, FALSE, entry.th32ThreadID) };
+                if let Ok(thread) = cvt(thread).map(Handle::new) {
+                    if f(thread.raw()) == u32::MAX {
+                        return Err(Error::last_os_error());
+                    }
+                }
+            }
+            has_entry = unsafe { Thread32Next(snapshot.raw(), &mut entry) } == TRUE;
+        }
+        Ok(())
+    }
+
     fn query_info<T>(&self, class: JOBOBJECTINFOCLASS) -> Result<T> {
         unsafe {
             let mut info = zeroed::<T>();
@@ -596,6 +808,14 @@ fn create_env(info: &ProcessInfo, user: Option<&User>) -> Result<Vec<u16>> {
     Ok(result)
 }
 
+/// Converts the timeout to milliseconds rounded up, `None` means no timeout.
+fn timeout_ms(timeout: Option<Duration>) -> DWORD {
+    match timeout {
+        Some(t) => t.as_micros().div_ceil(1000).min(INFINITE as u128 - 1) as DWORD,
+        None => INFINITE,
+    }
+}
+
 fn crash_cause(exit_code: DWORD) -> Option<&'static str> {
     match exit_code {
         STATUS_ACCESS_VIOLATION => Some("AccessViolation"),
@@ -18,6 +18,47 @@ pub enum Environment {
     UserDefault,
 }
 
+/// The memory usage the memory limit is enforced on and reported as the peak memory.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MemoryMetric {
+    /// The memory usage of the whole group of processes.
+    Group,
+    /// The peak resident set size of a single process.
+    Rss,
+}
+
+/// The signal an executable is sent before it's killed.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TermSignal {
+    Int,
+    Term,
+    Hup,
+    Quit,
+    Usr1,
+    Usr2,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Network {
+    Host,
+    Loopback,
+    Disabled,
+}
+
+/// A named set of restrictions enabled with the `-s` option.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SecurityProfile {
+    Disabled,
+    /// Programs that only use standard streams. Same as the security level 1.
+    Strict,
+    /// Programs in compiled languages that may use files and threads.
+    Native,
+    Jvm,
+    Python,
+    /// Compilers and other programs that spawn processes.
+    Compiler,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct RedirectFlags {
     pub flush: bool,
@@ -46,6 +87,35 @@ pub struct RedirectList {
     pub default_flags: RedirectFlags,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct BindMount {
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum CpuAffinity {
+    Cpus(Vec<usize>),
+    /// A free CPU leased for the time of the run.
+    Auto,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RlimitResource {
+    Stack,
+    FileSize,
+    OpenFiles,
+    CoreSize,
+    AddressSpace,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Rlimit {
+    pub resource: RlimitResource,
+    /// `None` means unlimited.
+    pub value: Option<u64>,
+}
+
 pub type StdinRedirectList = RedirectList;
 pub type StdoutRedirectList = RedirectList;
 pub type StderrRedirectList = RedirectList;
@@ -65,6 +135,39 @@ pub struct Command {
     )]
     pub time_limit: Option<Duration>,
 
+    #[opt(
+        name = "-cpu-quota",
+        env = "SP_CPU_QUOTA",
+        desc = "Limit the CPU usage of an executable to the given share of a single CPU (unix only)",
+        value_desc = "<number>[%]",
+        parser = "PercentValueParser"
+    )]
+    pub cpu_quota: Option<f64>,
+
+    #[opt(
+        name = "-time-scale",
+        env = "SP_TIME_SCALE",
+        desc = "Multiply the CPU time of an executable by the given factor before checking and reporting it (default: 1)",
+        value_desc = "<number>"
+    )]
+    pub time_scale: f64,
+
+    #[opt(
+        name = "-il",
+        env = "SP_INSTRUCTION_LIMIT",
+        desc = "Set the limit on user-mode instructions retired by an executable, requires hardware counters (unix only)",
+        value_desc = "<number>"
+    )]
+    pub instruction_limit: Option<u64>,
+
+    #[opt(
+        name = "-perf",
+        env = "SP_PERF",
+        desc = "Report the instructions retired by an executable, or its task clock if hardware counters are unavailable (default: 0, unix only)",
+        value_desc = "{0|1}"
+    )]
+    pub perf: bool,
+
     #[opt(
         name = "-d",
         env = "SP_DEADLINE",
@@ -90,6 +193,39 @@ pub struct Command {
     )]
     pub memory_limit: Option<f64>,
 
+    #[opt(
+        name = "-mm",
+        env = "SP_MEMORY_METRIC",
+        desc = "Enforce the memory limit on the whole group or on the peak memory of a single process (default: group)",
+        value_desc = "{group|rss}"
+    )]
+    pub memory_metric: MemoryMetric,
+
+    #[opt(
+        name = "-mh",
+        env = "SP_MEMORY_HIGH",
+        desc = "Set the memory usage above which an executable is throttled (unix only)",
+        value_desc = "<number>[unit]",
+        parser = "MemValueParser"
+    )]
+    pub memory_high: Option<f64>,
+
+    #[opt(
+        name = "-swap",
+        env = "SP_SWAP",
+        desc = "Allow an executable to use swap, 0 requires -ml on the legacy cgroup hierarchy (default: 1, unix only)",
+        value_desc = "{0|1}"
+    )]
+    pub swap: bool,
+
+    #[opt(
+        name = "-kmem",
+        env = "SP_KERNEL_MEMORY",
+        desc = "Include the kernel memory in the memory usage, 0 gives an estimate (default: 1, unix only)",
+        value_desc = "{0|1}"
+    )]
+    pub kernel_memory: bool,
+
     #[opt(
         name = "-wl",
         env = "SP_WRITE_LIMIT",
@@ -99,6 +235,33 @@ pub struct Command {
     )]
     pub write_limit: Option<f64>,
 
+    #[opt(
+        name = "-rl",
+        env = "SP_READ_LIMIT",
+        desc = "Set the read limit for an executable",
+        value_desc = "<number>[unit]",
+        parser = "MemValueParser"
+    )]
+    pub read_limit: Option<f64>,
+
+    #[opt(
+        name = "-rbw",
+        env = "SP_READ_BANDWIDTH",
+        desc = "Throttle disk reads of an executable to the given amount per second (unix only)",
+        value_desc = "<number>[unit]",
+        parser = "MemValueParser"
+    )]
+    pub read_bandwidth: Option<f64>,
+
+    #[opt(
+        name = "-wbw",
+        env = "SP_WRITE_BANDWIDTH",
+        desc = "Throttle disk writes of an executable to the given amount per second (unix only)",
+        value_desc = "<number>[unit]",
+        parser = "MemValueParser"
+    )]
+    pub write_bandwidth: Option<f64>,
+
     #[opt(
         name = "-lr",
         env = "SP_LOAD_RATIO",
@@ -129,10 +292,26 @@ pub struct Command {
     )]
     pub active_connection_count: Option<usize>,
 
+    #[opt(
+        name = "-term-signal",
+        env = "SP_TERM_SIGNAL",
+        desc = "Send the signal to an executable that is terminated and kill it after the grace period (unix only)",
+        value_desc = "{int|term|hup|quit|usr1|usr2}"
+    )]
+    pub term_signal: Option<TermSignal>,
+
+    #[opt(
+        name = "-grace-period",
+        env = "SP_GRACE_PERIOD",
+        desc = "The time an executable is given to exit after the termination signal (default: 1s)",
+        value_desc = "<number>[unit]"
+    )]
+    pub grace_period: Duration,
+
     #[opt(
         names("-mi", "--monitorInterval"),
         env = "SP_MONITOR_INTERVAL",
-        desc = "The sleep interval for a monitoring thread (default: 0.001s)",
+        desc = "The interval of polling the resource usage that the OS doesn't notify about (default: 0.001s)",
         value_desc = "<number>[unit]"
     )]
     pub monitor_interval: Duration,
@@ -140,10 +319,10 @@ pub struct Command {
     #[opt(
         name = "-s",
         env = "SP_SECURITY_LEVEL",
-        desc = "Set the security level to 0 or 1",
-        value_desc = "{0|1}"
+        desc = "Set the security level to 0 or 1, or use a named security profile",
+        value_desc = "{0|1|strict|native|jvm|python|compiler}"
     )]
-    pub secure: bool,
+    pub secure: SecurityProfile,
 
     #[opt(
         name = "-sw",
@@ -169,6 +348,82 @@ pub struct Command {
     )]
     pub working_directory: Option<String>,
 
+    #[opt(
+        name = "-root",
+        env = "SP_ROOT",
+        desc = "Change the root directory of an executable (unix only)",
+        value_desc = "<dir>"
+    )]
+    pub root: Option<String>,
+
+    #[opt(
+        name = "-bind-ro",
+        desc = "Make <src> available at <dst> in read-only mode (unix only)",
+        value_desc = "<src>[:<dst>]"
+    )]
+    pub read_only_binds: Vec<BindMount>,
+
+    #[opt(
+        name = "-bind-rw",
+        desc = "Make <src> available at <dst> in read-write mode (unix only)",
+        value_desc = "<src>[:<dst>]"
+    )]
+    pub writable_binds: Vec<BindMount>,
+
+    #[opt(
+        name = "-tmpfs",
+        desc = "Mount an empty private tmpfs at <dir> (unix only)",
+        value_desc = "<dir>"
+    )]
+    pub tmpfs: Vec<String>,
+
+    #[opt(
+        name = "-net",
+        env = "SP_NETWORK",
+        desc = "Set network access for an executable (default: host, unix only)",
+        value_desc = "{host|loopback|none}"
+    )]
+    pub network: Network,
+
+    #[opt(
+        name = "-pid-ns",
+        env = "SP_PID_NAMESPACE",
+        desc = "Run an executable in a new PID namespace (unix only)",
+        value_desc = "{0|1}"
+    )]
+    pub pid_namespace: bool,
+
+    #[opt(
+        name = "-rootless",
+        env = "SP_ROOTLESS",
+        desc = "Isolate an executable with a user namespace, no root privileges required (unix only)",
+        value_desc = "{0|1}"
+    )]
+    pub rootless: bool,
+
+    #[opt(
+        name = "-seccomp",
+        env = "SP_SECCOMP_POLICY",
+        desc = "Restrict syscalls of an executable with the policy from <file> (unix only)",
+        value_desc = "<file>"
+    )]
+    pub seccomp_policy: Option<String>,
+
+    #[opt(
+        name = "-rlimit",
+        desc = "Set a resource limit for every process of an executable: stack, fsize, nofile, core or as (unix only)",
+        value_desc = "<resource>:{<number>[unit]|unlimited}"
+    )]
+    pub rlimits: Vec<Rlimit>,
+
+    #[opt(
+        name = "-cpu",
+        env = "SP_CPU",
+        desc = "Run an executable on the given CPUs or on a free CPU not used by other runs (default: 0, unix only)",
+        value_desc = "{auto|<cpu>[-<cpu>][,...]}"
+    )]
+    pub cpu_affinity: Option<CpuAffinity>,
+
     #[opt(
         name = "-hr",
         env = "SP_HIDE_REPORT",
@@ -306,6 +561,31 @@ pub struct Command {
     )]
     pub wait_for_children: bool,
 
+    #[flag(
+        name = "--multiplex",
+        env = "SP_MULTIPLEX",
+        desc = "Supervise all executables in one thread and serve their pipes in another"
+    )]
+    pub multiplex: bool,
+
+    #[flag(
+        name = "--setup",
+        desc = "Create the cgroups owned by the user who runs sudo, then exit"
+    )]
+    pub setup: bool,
+
+    #[flag(
+        name = "--cleanup",
+        desc = "Remove cgroups left behind by crashed spawners, then exit"
+    )]
+    pub cleanup: bool,
+
+    #[flag(
+        name = "--check",
+        desc = "Print in JSON which sandbox features work on this host, then exit"
+    )]
+    pub check: bool,
+
     pub argv: Vec<String>,
 }
 
@@ -313,19 +593,42 @@ impl Default for Command {
     fn default() -> Self {
         Self {
             time_limit: None,
+            cpu_quota: None,
+            time_scale: 1.0,
+            instruction_limit: None,
+            perf: false,
             wall_clock_time_limit: None,
             idle_time_limit: None,
             memory_limit: None,
+            memory_metric: MemoryMetric::Group,
+            memory_high: None,
+            swap: true,
+            kernel_memory: true,
             write_limit: None,
+            read_limit: None,
+            read_bandwidth: None,
+            write_bandwidth: None,
             load_ratio: 5.0,
             process_count: None,
             active_process_count: None,
             active_connection_count: None,
+            term_signal: None,
+            grace_period: Duration::from_secs(1),
             monitor_interval: Duration::from_millis(1),
-            secure: false,
+            secure: SecurityProfile::Disabled,
             show_window: false,
             debug: false,
             working_directory: None,
+            root: None,
+            read_only_binds: Vec::new(),
+            writable_binds: Vec::new(),
+            tmpfs: Vec::new(),
+            network: Network::Host,
+            pid_namespace: false,
+            rootless: false,
+            rlimits: Vec::new(),
+            cpu_affinity: None,
+            seccomp_policy: None,
             hide_report: false,
             hide_output: false,
             delegated: false,
@@ -343,6 +646,10 @@ impl Default for Command {
             shared_memory: None,
             use_json: false,
             wait_for_children: false,
+            multiplex: false,
+            setup: false,
+            cleanup: false,
+            check: false,
             argv: Vec::new(),
         }
     }
@@ -365,6 +672,11 @@ impl Command {
         Ok(opts)
     }
 
+    /// Returns `true` if the command maintains the sandbox instead of running executables.
+    pub fn is_maintenance(&self) -> bool {
+        self.setup || self.cleanup || self.check
+    }
+
     pub fn print_help() {
         let mut help = Self::help();
         help.overview = Some(format!("Spawner sandbox v{}", VERSION));
@@ -408,6 +720,32 @@ impl Default for RedirectList {
     }
 }
 
+impl Display for SecurityProfile {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SecurityProfile::Disabled => "none",
+            SecurityProfile::Strict => "strict",
+            SecurityProfile::Native => "native",
+            SecurityProfile::Jvm => "jvm",
+            SecurityProfile::Python => "python",
+            SecurityProfile::Compiler => "compiler",
+        })
+    }
+}
+
+impl Display for TermSignal {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TermSignal::Int => "int",
+            TermSignal::Term => "term",
+            TermSignal::Hup => "hup",
+            TermSignal::Quit => "quit",
+            TermSignal::Usr1 => "usr1",
+            TermSignal::Usr2 => "usr2",
+        })
+    }
+}
+
 impl Display for RedirectFlags {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(
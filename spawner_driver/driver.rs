@@ -1,17 +1,19 @@
-use crate::cmd::{Command, Environment, RedirectFlags, RedirectKind, RedirectList};
+use crate::cmd::{Command, Environment, MemoryMetric, RedirectFlags, RedirectKind, RedirectList};
 use crate::misc::mb2b;
 use crate::protocol_entities::{Agent, AgentIdx, Controller};
 use crate::protocol_handlers::{AgentStdout, ControllerStdout};
 use crate::report::Report;
 use crate::sys::{
-    init_os_specific_process_extensions, open_input_file, open_output_file, ConsoleReader,
+    check_capabilities, cleanup_cgroups, init_os_specific_process_extensions, open_input_file,
+    open_output_file, setup_cgroups, signal_number, AssignedCpus, ConsoleReader, InterruptHandler,
 };
 
 use spawner::dataflow::{DestinationId, Graph, SourceId};
 use spawner::pipe::{self, ReadPipe, WritePipe};
 use spawner::process::{Group, ProcessInfo};
 use spawner::{
-    Error, IdleTimeLimit, Program, ProgramMessage, ResourceLimits, Result, Session, StdioMapping,
+    self, Error, IdleTimeLimit, MessageReceiver, MessageSender, Program, ResourceLimits, Result,
+    Session, StdioMapping, TerminationPolicy,
 };
 
 use spawner_opts::CmdLineOptions;
@@ -29,15 +31,19 @@ use std::fmt;
 use std::fs;
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{channel, Receiver};
 
 pub struct Warnings(RefCell<HashSet<String>>);
 
 pub struct Driver {
     sess: Session,
     cmds: Vec<Command>,
+    cpus: Vec<AssignedCpus>,
+    senders: Vec<MessageSender>,
     warnings: Warnings,
     stdio: DriverStdio,
+    setup: bool,
+    cleanup: bool,
+    check: bool,
 }
 
 /// All redirects to *std are redirected here.
@@ -90,23 +96,38 @@ impl Driver {
         U: AsRef<str>,
     {
         let warnings = Warnings::new();
-        let cmds = parse_argv(argv)?;
+        let mut cmds = parse_argv(argv)?;
+        let setup = cmds.iter().any(|cmd| cmd.setup);
+        let cleanup = cmds.iter().any(|cmd| cmd.cleanup);
+        let check = cmds.iter().any(|cmd| cmd.check);
+        if setup || cleanup || check {
+            if cmds.iter().any(|cmd| !cmd.argv.is_empty()) {
+                return Err(Error::from(
+                    "'--setup', '--cleanup' and '--check' cannot be used with executables",
+                ));
+            }
+            cmds.clear();
+        }
         check_cmds(&cmds, &warnings)?;
 
         let mut sess = Session::new();
         let mut senders = Vec::new();
+        let mut cpus = Vec::new();
         let roles = create_roles(&cmds);
         let mappings = cmds
             .iter()
             .zip(roles.iter())
             .map(|(cmd, role)| {
-                let channel = channel();
-                senders.push(channel.0.clone());
-                create_program(cmd, channel.1, *role, &warnings).and_then(|p| sess.add_program(p))
+                let (sender, receiver) = spawner::message_channel()?;
+                senders.push(sender);
+                let (program, assigned) = create_program(cmd, receiver, *role, &warnings)?;
+                cpus.push(assigned);
+                sess.add_program(program)
             })
             .collect::<Result<Vec<_>>>()?;
 
         let stdio = StdioLinker::new(&mut sess, &mappings, &warnings).link(&cmds)?;
+        sess.multiplex_supervisors(cmds.iter().any(|cmd| cmd.multiplex));
 
         if let Some(controller) = cmds.iter().position(|cmd| cmd.controller) {
             // Initialize protocol entities.
@@ -133,13 +154,25 @@ impl Driver {
         Ok(Self {
             sess,
             cmds,
+            cpus,
+            senders,
             warnings,
             stdio,
+            setup,
+            cleanup,
+            check,
         })
     }
 
     pub fn run(self) -> Result<Vec<Report>> {
         eprint!("{}", self.warnings);
+        if self.setup || self.cleanup || self.check {
+            maintain(self.setup, self.cleanup, self.check)?;
+            return Ok(Vec::new());
+        }
+
+        // Interrupted programs are terminated, so that their reports are still written.
+        let _interrupt_handler = InterruptHandler::register(self.senders)?;
 
         let cmds = self.cmds;
         let run = self.sess.run()?;
@@ -156,7 +189,14 @@ impl Driver {
             .wait()
             .into_iter()
             .zip(cmds.iter())
-            .map(|(r, c)| Report::new(c, r))
+            .zip(self.cpus.iter())
+            .map(|((r, c), assigned)| {
+                let mut report = Report::new(c, r);
+                if c.cpu_affinity.is_some() {
+                    report.cpus = assigned.cpus.clone();
+                }
+                report
+            })
             .collect::<Vec<_>>();
         if reports.is_empty() {
             Command::print_help();
@@ -318,7 +358,9 @@ where
         cmd.argv.extend_from_slice(&argv[pos..sep_pos]);
         pos = sep_pos + 1;
 
-        if cmd.argv.is_empty() {
+        if cmd.argv.is_empty() && cmd.is_maintenance() {
+            cmds.push(cmd);
+        } else if cmd.argv.is_empty() {
             default_cmd = cmd;
         } else {
             default_cmd.separator = cmd.separator.clone();
@@ -385,6 +427,31 @@ fn print_reports(cmds: &[Command], reports: &[Report]) -> std::io::Result<()> {
     Ok(())
 }
 
+fn maintain(setup: bool, cleanup: bool, check: bool) -> Result<()> {
+    if setup {
+        let changes = setup_cgroups()?;
+        if changes.is_empty() {
+            println!("The cgroups are already set up");
+        }
+        for change in changes {
+            println!("{}", change);
+        }
+    }
+    if cleanup {
+        let changes = cleanup_cgroups()?;
+        if changes.is_empty() {
+            println!("No orphaned cgroups found");
+        }
+        for change in changes {
+            println!("{}", change);
+        }
+    }
+    if check {
+        println!("{}", check_capabilities()?.pretty(4));
+    }
+    Ok(())
+}
+
 fn check_cmds(cmds: &[Command], warnings: &Warnings) -> Result<()> {
     if cmds.iter().filter(|cmd| cmd.controller).count() > 1 {
         return Err(Error::from("There can be at most one controller"));
@@ -448,14 +515,14 @@ fn init_entity_handler(entity: Role, graph: &mut Graph, controller: &Controller,
 
 fn create_program(
     cmd: &Command,
-    receiver: Receiver<ProgramMessage>,
+    receiver: MessageReceiver,
     role: Role,
     warnings: &Warnings,
-) -> Result<Program> {
+) -> Result<(Program, AssignedCpus)> {
     let mut info = create_process_info(cmd, role);
     let mut group = Group::new()?;
-    init_os_specific_process_extensions(cmd, &mut info, &mut group, warnings).map(|_| {
-        Program::new_with(info, |p| {
+    init_os_specific_process_extensions(cmd, &mut info, &mut group, warnings).map(|cpus| {
+        let program = Program::new_with(info, |p| {
             p.group(group)
                 .monitor_interval(cmd.monitor_interval)
                 .resource_limits(ResourceLimits {
@@ -465,15 +532,34 @@ fn create_program(
                         cpu_load_threshold: cmd.load_ratio / 100.0,
                     }),
                     total_user_time: cmd.time_limit,
+                    cpu_quota: cmd.cpu_quota.map(|percent| percent / 100.0),
+                    time_scale: cmd.time_scale,
+                    cpu_counter: cmd.perf,
+                    instructions: cmd.instruction_limit,
                     max_memory_usage: cmd.memory_limit.map(mb2b),
+                    memory_metric: match cmd.memory_metric {
+                        MemoryMetric::Group => spawner::MemoryMetric::Group,
+                        MemoryMetric::Rss => spawner::MemoryMetric::Process,
+                    },
+                    memory_high: cmd.memory_high.map(mb2b),
+                    disable_swap: !cmd.swap,
+                    exclude_kernel_memory: !cmd.kernel_memory,
                     total_bytes_written: cmd.write_limit.map(mb2b),
+                    total_bytes_read: cmd.read_limit.map(mb2b),
+                    disk_read_bandwidth: cmd.read_bandwidth.map(mb2b),
+                    disk_write_bandwidth: cmd.write_bandwidth.map(mb2b),
                     total_processes_created: cmd.process_count,
                     active_processes: cmd.active_process_count,
                     active_network_connections: cmd.active_connection_count,
+                    termination_policy: cmd.term_signal.map(|signal| TerminationPolicy {
+                        signal: signal_number(signal),
+                        grace_period: cmd.grace_period,
+                    }),
                 })
                 .wait_for_children(cmd.wait_for_children)
                 .msg_receiver(receiver);
-        })
+        });
+        (program, cpus)
     })
 }
 
@@ -1,23 +1,22 @@
 use spawner::dataflow::{DestinationId, SourceId};
-use spawner::{Error, ProgramMessage, Result, StdioMapping};
+use spawner::{Error, MessageSender, ProgramMessage, Result, StdioMapping};
 
 use std::char;
 use std::str;
-use std::sync::mpsc::Sender;
 
 #[derive(Copy, Clone, PartialEq)]
 pub struct AgentIdx(pub usize);
 
 #[derive(Clone)]
 pub struct Controller {
-    sender: Sender<ProgramMessage>,
+    sender: MessageSender,
     mapping: StdioMapping,
 }
 
 #[derive(Clone)]
 pub struct Agent {
     idx: AgentIdx,
-    sender: Sender<ProgramMessage>,
+    sender: MessageSender,
     mapping: StdioMapping,
 }
 
@@ -34,7 +33,7 @@ pub struct Message<'a> {
 }
 
 impl Controller {
-    pub fn new(sender: Sender<ProgramMessage>, mapping: StdioMapping) -> Self {
+    pub fn new(sender: MessageSender, mapping: StdioMapping) -> Self {
         Self { sender, mapping }
     }
 
@@ -61,7 +60,7 @@ impl Controller {
 }
 
 impl Agent {
-    pub fn new(idx: AgentIdx, sender: Sender<ProgramMessage>, mapping: StdioMapping) -> Self {
+    pub fn new(idx: AgentIdx, sender: MessageSender, mapping: StdioMapping) -> Self {
         Self {
             idx,
             sender,
@@ -1,19 +1,22 @@
 use crate::protocol_entities::{Agent, AgentIdx, Controller, Message, MessageKind};
 
 use spawner::dataflow::{Connection, DestinationId, SourceReader};
-use spawner::pipe::ReadPipe;
 use spawner::{Error, Result};
 
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader};
 
 pub struct ControllerStdout {
     controller: Controller,
     agents: Vec<Agent>,
     agent_by_stdin_id: HashMap<DestinationId, AgentIdx>,
+    msg_buf: MessageBuf,
 }
 
-pub struct AgentStdout(Agent);
+pub struct AgentStdout {
+    agent: Agent,
+    msg_buf: MessageBuf,
+    msg_prefix: String,
+}
 
 struct MessageBuf {
     buf: Vec<u8>,
@@ -31,6 +34,7 @@ impl ControllerStdout {
             controller,
             agents,
             agent_by_stdin_id,
+            msg_buf: MessageBuf::new(),
         }
     }
 
@@ -77,32 +81,22 @@ impl ControllerStdout {
             }
         }
     }
+}
 
-    fn read_stdout(&mut self, stdout: &mut ReadPipe, connections: &mut [Connection]) -> Result<()> {
-        let mut stdout_reader = BufReader::new(stdout);
-        let mut msg_buf = MessageBuf::new();
-        let mut data_len = 0;
-        loop {
-            stdout_reader.consume(data_len);
-            let data = stdout_reader.fill_buf().unwrap_or(&[]);
-            data_len = data.len();
-            if data_len == 0 {
-                return Ok(());
-            }
-
-            let mut next_msg_data = msg_buf.write(data)?;
-            while msg_buf.is_msg_ready() {
-                self.handle_msg(msg_buf.as_msg()?, connections)?;
-                msg_buf.clear();
-                next_msg_data = msg_buf.write(next_msg_data)?;
-            }
+impl SourceReader for ControllerStdout {
+    fn read(&mut self, data: &[u8], connections: &mut [Connection]) -> Result<()> {
+        let mut next_msg_data = self.msg_buf.write(data)?;
+        while self.msg_buf.is_msg_ready() {
+            let msg = self.msg_buf.as_msg()?;
+            self.handle_msg(msg, connections)?;
+            self.msg_buf.clear();
+            next_msg_data = self.msg_buf.write(next_msg_data)?;
         }
+        Ok(())
     }
-}
 
-impl SourceReader for ControllerStdout {
-    fn read(&mut self, stdout: &mut ReadPipe, connections: &mut [Connection]) -> Result<()> {
-        if let Err(e) = self.read_stdout(stdout, connections) {
+    fn finish(&mut self, result: Result<()>, _connections: &mut [Connection]) -> Result<()> {
+        if let Err(e) = result {
             // Controller sent an invalide message. Terminate everything.
             self.agents.iter().for_each(Agent::terminate);
             self.controller.terminate();
@@ -117,50 +111,40 @@ impl SourceReader for ControllerStdout {
 
 impl AgentStdout {
     pub fn new(agent: Agent) -> Self {
-        Self(agent)
-    }
-
-    fn read_stdout(&mut self, stdout: &mut ReadPipe, connections: &mut [Connection]) -> Result<()> {
-        let mut stdout_reader = BufReader::new(stdout);
+        let msg_prefix = format!("{}#", agent.idx().0 + 1);
         let mut msg_buf = MessageBuf::new();
-        let msg_prefix = format!("{}#", self.0.idx().0 + 1);
         msg_buf.write(msg_prefix.as_bytes()).unwrap();
-        let mut data_len = 0;
-
-        loop {
-            stdout_reader.consume(data_len);
-            let data = stdout_reader.fill_buf().unwrap_or(&[]);
-            data_len = data.len();
-            if data_len == 0 {
-                return Ok(());
-            }
-
-            let mut next_msg_data = msg_buf.write(data)?;
-            while msg_buf.is_msg_ready() {
-                self.0.suspend();
+        Self {
+            agent,
+            msg_buf,
+            msg_prefix,
+        }
+    }
+}
 
-                for c in connections.iter_mut() {
-                    c.send(msg_buf.as_slice());
-                }
+impl SourceReader for AgentStdout {
+    fn read(&mut self, data: &[u8], connections: &mut [Connection]) -> Result<()> {
+        let mut next_msg_data = self.msg_buf.write(data)?;
+        while self.msg_buf.is_msg_ready() {
+            self.agent.suspend();
 
-                msg_buf.clear();
-                msg_buf.write(msg_prefix.as_bytes()).unwrap();
-                next_msg_data = msg_buf.write(next_msg_data)?;
+            for c in connections.iter_mut() {
+                c.send(self.msg_buf.as_slice());
             }
+
+            self.msg_buf.clear();
+            self.msg_buf.write(self.msg_prefix.as_bytes()).unwrap();
+            next_msg_data = self.msg_buf.write(next_msg_data)?;
         }
+        Ok(())
     }
-}
 
-impl SourceReader for AgentStdout {
-    fn read(&mut self, stdout: &mut ReadPipe, connections: &mut [Connection]) -> Result<()> {
-        let r = self.read_stdout(stdout, connections).map_err(|e| {
-            // Agent sent an invalide message. Terminate it.
-            self.0.terminate();
-            e
-        });
+    fn finish(&mut self, result: Result<()>, connections: &mut [Connection]) -> Result<()> {
+        // Agent sent an invalide message. Terminate it.
+        let r = result.inspect_err(|_| self.agent.terminate());
 
         // No more data is available to read.
-        let term_message = format!("{}T#\n", self.0.idx().0 + 1);
+        let term_message = format!("{}T#\n", self.agent.idx().0 + 1);
         for c in connections.iter_mut() {
             c.send(term_message.as_bytes());
         }
@@ -1,8 +1,8 @@
-use crate::cmd::{Command, RedirectList};
+use crate::cmd::{Command, MemoryMetric, RedirectList, SecurityProfile, TermSignal};
 use crate::misc::{b2mb, mb2b};
 
-use spawner::process::ExitStatus;
-use spawner::{Error, ProgramResult, TerminationReason};
+use spawner::process::{CpuCounter, Crash, ExitStatus, Rusage};
+use spawner::{Error, ProgramResult, SecurityViolation, TerminationReason};
 
 use json::{array, object, JsonValue};
 
@@ -18,13 +18,17 @@ pub struct Report {
     pub working_directory: Option<String>,
     pub create_process_method: String,
     pub username: Option<String>,
+    pub cpus: Vec<usize>,
     pub stdin: Vec<String>,
     pub stdout: Vec<String>,
     pub stderr: Vec<String>,
     pub result: ReportResult,
+    pub rusage: Option<Rusage>,
     pub terminate_reason: TerminateReason,
     pub exit_code: u32,
     pub exit_status: String,
+    pub crash: Option<Crash>,
+    pub security_violation: Option<SecurityViolation>,
     pub spawner_error: Vec<Error>,
 }
 
@@ -46,20 +50,41 @@ pub struct ReportResult {
     pub wall_clock_time: f64,
     pub memory: u64,
     pub bytes_written: u64,
+    pub bytes_read: u64,
+    pub disk_bytes_read: u64,
+    pub disk_bytes_written: u64,
+    pub disk_reads: u64,
+    pub disk_writes: u64,
     pub kernel_time: f64,
     pub processor_load: f64,
     pub processes_created: u64,
+    pub memory_limit_hits: u64,
+    pub oom_kills: u64,
+    pub cpu_counter: Option<CpuCounter>,
+    pub exited_within_grace_period: Option<bool>,
 }
 
 #[derive(Debug)]
 pub struct ReportLimit {
     pub time: Option<f64>,
+    pub cpu_quota: Option<f64>,
+    pub time_scale: f64,
+    pub instructions: Option<u64>,
     pub wall_clock_time: Option<f64>,
     pub memory: Option<u64>,
+    pub memory_high: Option<u64>,
+    pub swap: bool,
+    pub kernel_memory: bool,
     pub security_level: Option<u32>,
+    pub security_profile: Option<String>,
     pub io_bytes: Option<u64>,
+    pub io_read_bytes: Option<u64>,
+    pub disk_read_bandwidth: Option<u64>,
+    pub disk_write_bandwidth: Option<u64>,
     pub idleness_time: Option<f64>,
     pub idleness_processor_load: Option<f64>,
+    pub term_signal: Option<TermSignal>,
+    pub grace_period: f64,
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -67,13 +92,17 @@ pub enum TerminateReason {
     ExitProcess,
     AbnormalExitProcess,
     TimeLimitExceeded,
+    InstructionLimitExceeded,
     IdleTimeLimitExceeded,
     WriteLimitExceeded,
+    ReadLimitExceeded,
     MemoryLimitExceeded,
     ProcessesCountLimitExceeded,
     ActiveProcessesCountLimitExceeded,
     ActiveConnectionCountLimitExceeded,
+    SecurityViolation,
     TerminatedByController,
+    Interrupted,
 }
 
 #[derive(Debug)]
@@ -81,8 +110,10 @@ pub struct LegacyReport<'a> {
     pub application: &'a String,
     pub parameters: &'a Vec<String>,
     pub security_level: Option<u32>,
+    pub security_profile: &'a Option<String>,
     pub create_process_method: &'a String,
     pub username: &'a Option<String>,
+    pub cpus: &'a Vec<usize>,
     pub user_time_limit: Option<f64>,
     pub deadline: Option<f64>,
     pub memory_limit: Option<f64>,
@@ -113,18 +144,36 @@ impl Report {
         match result {
             Ok(runner_report) => {
                 report.result = ReportResult::from(&runner_report);
+                report.rusage = runner_report.rusage;
+                if cmd.memory_metric == MemoryMetric::Rss {
+                    let memory = runner_report.memory.unwrap_or_default();
+                    report.result.memory = memory.max_process_usage;
+                }
                 match runner_report.exit_status {
                     ExitStatus::Finished(code) => {
                         report.exit_code = code;
                         report.exit_status = code.to_string();
                     }
-                    ExitStatus::Crashed(cause) => {
+                    ExitStatus::Crashed(crash) => {
                         report.terminate_reason = TerminateReason::AbnormalExitProcess;
-                        report.exit_status = cause;
+                        report.exit_status = crash.cause.clone();
+                        report.crash = Some(crash);
                     }
                 }
                 if let Some(tr) = runner_report.termination_reason {
                     report.terminate_reason = TerminateReason::from(tr);
+                    if let TerminationReason::SecurityViolation(v) = tr {
+                        report.exit_status = match v.syscall {
+                            Some(syscall) => format!(
+                                "Forbidden syscall '{}' ({}) made by process {}",
+                                v.syscall_name.unwrap_or("unknown"),
+                                syscall,
+                                v.pid
+                            ),
+                            None => format!("Forbidden syscall made by process {}", v.pid),
+                        };
+                        report.security_violation = Some(v);
+                    }
                 }
             }
             Err(e) => report.spawner_error = e.errors,
@@ -133,7 +182,7 @@ impl Report {
     }
 
     pub fn to_json(&self) -> JsonValue {
-        object! {
+        let mut report = object! {
             "Application" => self.application.clone(),
             "Arguments" => self.arguments.clone(),
             "Limit" => self.limit.to_json(),
@@ -157,8 +206,15 @@ impl Report {
                 "WallClockTime" => self.result.wall_clock_time,
                 "Memory" => self.result.memory,
                 "BytesWritten" => self.result.bytes_written,
+                "BytesRead" => self.result.bytes_read,
+                "DiskBytesRead" => self.result.disk_bytes_read,
+                "DiskBytesWritten" => self.result.disk_bytes_written,
+                "DiskReads" => self.result.disk_reads,
+                "DiskWrites" => self.result.disk_writes,
                 "KernelTime" =>  self.result.kernel_time,
                 "ProcessorLoad" => self.result.processor_load,
+                "MemoryLimitHits" => self.result.memory_limit_hits,
+                "OomKills" => self.result.oom_kills,
             },
             "TerminateReason" => self.terminate_reason.to_string(),
             "ExitCode" => self.exit_code,
@@ -178,7 +234,46 @@ impl Report {
                     })
                     .collect::<Vec<JsonValue>>().into()
             }
+        };
+        match self.result.cpu_counter {
+            Some(CpuCounter::Instructions(n)) => report["Result"]["Instructions"] = n.into(),
+            Some(CpuCounter::TaskClock(t)) => {
+                report["Result"]["TaskClock"] = t.as_secs_f64().into()
+            }
+            None => {}
+        }
+        if let Some(v) = self.result.exited_within_grace_period {
+            report["Result"]["ExitedWithinGracePeriod"] = v.into();
+        }
+        if !self.cpus.is_empty() {
+            report["Cpus"] = self.cpus.clone().into();
+        }
+        if let Some(ref r) = self.rusage {
+            report["Rusage"] = object! {
+                "UserTime" => r.user_time.as_secs_f64(),
+                "KernelTime" => r.kernel_time.as_secs_f64(),
+                "PeakRss" => r.max_rss,
+                "MinorPageFaults" => r.minor_page_faults,
+                "MajorPageFaults" => r.major_page_faults,
+                "VoluntaryContextSwitches" => r.voluntary_context_switches,
+                "InvoluntaryContextSwitches" => r.involuntary_context_switches,
+            };
+        }
+        if let Some(ref c) = self.crash {
+            report["Crash"] = object! {
+                "Signal" => c.signal,
+                "CoreDumped" => c.core_dumped,
+                "FaultCode" => c.fault_code,
+            };
         }
+        if let Some(ref v) = self.security_violation {
+            report["SecurityViolation"] = object! {
+                "Syscall" => v.syscall_name.unwrap_or(""),
+                "SyscallNumber" => v.syscall,
+                "Pid" => v.pid,
+            };
+        }
+        report
     }
 
     fn as_legacy(&self) -> LegacyReport {
@@ -186,8 +281,10 @@ impl Report {
             application: &self.application,
             parameters: &self.arguments,
             security_level: self.limit.security_level,
+            security_profile: &self.limit.security_profile,
             create_process_method: &self.create_process_method,
             username: &self.username,
+            cpus: &self.cpus,
             user_time_limit: self.limit.time,
             deadline: self.limit.wall_clock_time,
             memory_limit: self.limit.memory.map(b2mb),
@@ -235,13 +332,17 @@ impl From<&Command> for Report {
             working_directory: cmd.working_directory.clone(),
             create_process_method: "CreateProcess".to_string(),
             username: cmd.username.clone(),
+            cpus: Vec::new(),
             stdin: Vec::from(&cmd.stdin_redirect),
             stdout: Vec::from(&cmd.stdout_redirect),
             stderr: Vec::from(&cmd.stderr_redirect),
             result: ReportResult::default(),
+            rusage: None,
             terminate_reason: TerminateReason::ExitProcess,
             exit_code: 0,
             exit_status: "0".to_string(),
+            crash: None,
+            security_violation: None,
             spawner_error: Vec::new(),
         }
     }
@@ -267,9 +368,18 @@ impl From<&spawner::Report> for ReportResult {
             wall_clock_time: wc_time,
             memory: memory.max_usage,
             bytes_written: io.total_bytes_written,
+            bytes_read: io.total_bytes_read,
+            disk_bytes_read: io.disk_bytes_read,
+            disk_bytes_written: io.disk_bytes_written,
+            disk_reads: io.disk_reads,
+            disk_writes: io.disk_writes,
             kernel_time: timers.total_kernel_time.as_secs_f64(),
             processor_load: if wc_time <= 1e-8 { 0.0 } else { time / wc_time },
             processes_created: pid_counters.total_processes as u64,
+            memory_limit_hits: memory.limit_hits,
+            oom_kills: memory.oom_kills,
+            cpu_counter: timers.cpu_counter,
+            exited_within_grace_period: report.exited_within_grace_period,
         }
     }
 }
@@ -283,21 +393,55 @@ impl ReportLimit {
         if let Some(t) = self.wall_clock_time {
             limit["WallClockTime"] = t.into();
         }
+        if let Some(v) = self.cpu_quota {
+            limit["CpuQuota"] = v.into();
+        }
+        if self.time_scale != 1.0 {
+            limit["TimeScale"] = self.time_scale.into();
+        }
+        if let Some(n) = self.instructions {
+            limit["Instructions"] = n.into();
+        }
         if let Some(v) = self.memory {
             limit["Memory"] = v.into();
         }
+        if let Some(v) = self.memory_high {
+            limit["MemoryHigh"] = v.into();
+        }
+        if !self.swap {
+            limit["Swap"] = false.into();
+        }
+        if !self.kernel_memory {
+            limit["KernelMemory"] = false.into();
+        }
         if let Some(lvl) = self.security_level {
             limit["SecurityLevel"] = lvl.into();
         }
+        if let Some(ref profile) = self.security_profile {
+            limit["SecurityProfile"] = profile.clone().into();
+        }
         if let Some(b) = self.io_bytes {
             limit["IOBytes"] = b.into();
         }
+        if let Some(b) = self.io_read_bytes {
+            limit["IOReadBytes"] = b.into();
+        }
+        if let Some(b) = self.disk_read_bandwidth {
+            limit["DiskReadBandwidth"] = b.into();
+        }
+        if let Some(b) = self.disk_write_bandwidth {
+            limit["DiskWriteBandwidth"] = b.into();
+        }
         if let Some(t) = self.idleness_time {
             limit["IdlenessTime"] = t.into();
         }
         if let Some(v) = self.idleness_processor_load {
             limit["IdlenessProcessorLoad"] = v.into();
         }
+        if let Some(signal) = self.term_signal {
+            limit["TerminationSignal"] = signal.to_string().into();
+            limit["GracePeriod"] = self.grace_period.into();
+        }
         limit
     }
 }
@@ -306,12 +450,30 @@ impl From<&Command> for ReportLimit {
     fn from(cmd: &Command) -> Self {
         Self {
             time: cmd.time_limit.map(|d| d.as_secs_f64()),
+            cpu_quota: cmd.cpu_quota,
+            time_scale: cmd.time_scale,
+            instructions: cmd.instruction_limit,
             wall_clock_time: cmd.wall_clock_time_limit.map(|d| d.as_secs_f64()),
             memory: cmd.memory_limit.map(mb2b),
-            security_level: if cmd.secure { Some(1) } else { None },
+            memory_high: cmd.memory_high.map(mb2b),
+            swap: cmd.swap,
+            kernel_memory: cmd.kernel_memory,
+            security_level: match cmd.secure {
+                SecurityProfile::Disabled => None,
+                _ => Some(1),
+            },
+            security_profile: match cmd.secure {
+                SecurityProfile::Disabled => None,
+                profile => Some(profile.to_string()),
+            },
             io_bytes: cmd.write_limit.map(mb2b),
+            io_read_bytes: cmd.read_limit.map(mb2b),
+            disk_read_bandwidth: cmd.read_bandwidth.map(mb2b),
+            disk_write_bandwidth: cmd.write_bandwidth.map(mb2b),
             idleness_time: cmd.idle_time_limit.map(|d| d.as_secs_f64()),
             idleness_processor_load: Some(cmd.load_ratio),
+            term_signal: cmd.term_signal,
+            grace_period: cmd.grace_period.as_secs_f64(),
         }
     }
 }
@@ -322,8 +484,10 @@ impl Display for TerminateReason {
             TerminateReason::ExitProcess => "ExitProcess",
             TerminateReason::AbnormalExitProcess => "AbnormalExitProcess",
             TerminateReason::TimeLimitExceeded => "TimeLimitExceeded",
+            TerminateReason::InstructionLimitExceeded => "InstructionLimitExceeded",
             TerminateReason::IdleTimeLimitExceeded => "IdleTimeLimitExceeded",
             TerminateReason::WriteLimitExceeded => "WriteLimitExceeded",
+            TerminateReason::ReadLimitExceeded => "ReadLimitExceeded",
             TerminateReason::MemoryLimitExceeded => "MemoryLimitExceeded",
             TerminateReason::ProcessesCountLimitExceeded => "ProcessesCountLimitExceeded",
             TerminateReason::ActiveProcessesCountLimitExceeded => {
@@ -332,7 +496,9 @@ impl Display for TerminateReason {
             TerminateReason::ActiveConnectionCountLimitExceeded => {
                 "ActiveConnectionCountLimitExceeded"
             }
+            TerminateReason::SecurityViolation => "SecurityViolation",
             TerminateReason::TerminatedByController => "TerminatedByController",
+            TerminateReason::Interrupted => "Interrupted",
         })
     }
 }
@@ -343,7 +509,11 @@ impl From<TerminationReason> for TerminateReason {
             TerminationReason::WallClockTimeLimitExceeded => TerminateReason::TimeLimitExceeded,
             TerminationReason::IdleTimeLimitExceeded => TerminateReason::IdleTimeLimitExceeded,
             TerminationReason::UserTimeLimitExceeded => TerminateReason::TimeLimitExceeded,
+            TerminationReason::InstructionLimitExceeded => {
+                TerminateReason::InstructionLimitExceeded
+            }
             TerminationReason::WriteLimitExceeded => TerminateReason::WriteLimitExceeded,
+            TerminationReason::ReadLimitExceeded => TerminateReason::ReadLimitExceeded,
             TerminationReason::MemoryLimitExceeded => TerminateReason::MemoryLimitExceeded,
             TerminationReason::ProcessLimitExceeded => TerminateReason::ProcessesCountLimitExceeded,
             TerminationReason::ActiveProcessLimitExceeded => {
@@ -352,7 +522,10 @@ impl From<TerminationReason> for TerminateReason {
             TerminationReason::ActiveNetworkConnectionLimitExceeded => {
                 TerminateReason::ActiveConnectionCountLimitExceeded
             }
+            TerminationReason::FileSizeLimitExceeded => TerminateReason::WriteLimitExceeded,
+            TerminationReason::SecurityViolation(_) => TerminateReason::SecurityViolation,
             TerminationReason::TerminatedByRunner => TerminateReason::TerminatedByController,
+            TerminationReason::Interrupted => TerminateReason::Interrupted,
         }
     }
 }
@@ -370,12 +543,19 @@ impl<'a> Display for LegacyReport<'a> {
         line!(f, "Application:", self.application)?;
         line!(f, "Parameters:", NoneOrJoin(self.parameters.iter()))?;
         line!(f, "SecurityLevel:", self.security_level.unwrap_or(0))?;
+        if let Some(ref profile) = self.security_profile {
+            line!(f, "SecurityProfile:", profile)?;
+        }
         line!(f, "CreateProcessMethod:", self.create_process_method)?;
         line!(
             f,
             "UserName:",
             self.username.as_ref().unwrap_or(&String::new())
         )?;
+        if !self.cpus.is_empty() {
+            let cpus = self.cpus.iter().map(|cpu| cpu.to_string());
+            line!(f, "Cpus:", NoneOrJoin(cpus))?;
+        }
         line!(f, "UserTimeLimit:", FltSecsOrInf(self.user_time_limit))?;
         line!(f, "DeadLine:", FltSecsOrInf(self.deadline))?;
         line!(f, "MemoryLimit:", MbOrInf(self.memory_limit))?;
@@ -10,13 +10,16 @@ use crate::sys::windows as imp;
 #[cfg(unix)]
 use crate::sys::unix as imp;
 
-use crate::cmd::{Command, RedirectFlags};
+use crate::cmd::{Command, RedirectFlags, TermSignal};
 use crate::driver::Warnings;
 
 use spawner::pipe::{ReadPipe, WritePipe};
 use spawner::process::{Group, ProcessInfo};
-use spawner::{Result, Run};
+use spawner::{MessageSender, Result, Run};
 
+use json::JsonValue;
+
+use std::fs::File;
 use std::io::{self, Write};
 use std::path::Path;
 use std::thread;
@@ -24,6 +27,19 @@ use std::time;
 
 pub struct ConsoleReader(imp::ConsoleReader);
 
+/// Interrupts the programs of a run once the spawner is asked to stop, e.g. by SIGINT or
+/// SIGTERM on unix. The run is unregistered when this is dropped.
+pub struct InterruptHandler {
+    _handler: imp::InterruptHandler,
+}
+
+/// CPUs a program runs on. A leased CPU is not given to other runs until this is dropped.
+#[derive(Default)]
+pub struct AssignedCpus {
+    pub cpus: Vec<usize>,
+    pub _lease: Option<File>,
+}
+
 impl ConsoleReader {
     pub fn spawn(mut dst: WritePipe) -> Self {
         Self(imp::ConsoleReader::spawn(move || {
@@ -51,6 +67,12 @@ impl ConsoleReader {
     }
 }
 
+impl InterruptHandler {
+    pub fn register(senders: Vec<MessageSender>) -> Result<Self> {
+        imp::InterruptHandler::register(senders).map(|handler| Self { _handler: handler })
+    }
+}
+
 pub fn open_input_file(file: &Path, flags: RedirectFlags, warnings: &Warnings) -> Result<ReadPipe> {
     imp::open_input_file(file, flags, warnings)
 }
@@ -63,11 +85,27 @@ pub fn open_output_file(
     imp::open_output_file(file, flags, warnings)
 }
 
+pub fn signal_number(signal: TermSignal) -> i32 {
+    imp::signal_number(signal)
+}
+
+pub fn setup_cgroups() -> Result<Vec<String>> {
+    imp::setup_cgroups()
+}
+
+pub fn cleanup_cgroups() -> Result<Vec<String>> {
+    imp::cleanup_cgroups()
+}
+
+pub fn check_capabilities() -> Result<JsonValue> {
+    imp::check_capabilities()
+}
+
 pub fn init_os_specific_process_extensions(
     cmd: &Command,
     info: &mut ProcessInfo,
     group: &mut Group,
     warnings: &Warnings,
-) -> Result<()> {
+) -> Result<AssignedCpus> {
     imp::init_os_specific_process_extensions(cmd, info, group, warnings)
 }
@@ -1,11 +1,30 @@
-use crate::cmd::{Command, Environment, RedirectFlags};
+use crate::cmd::{
+    Command, CpuAffinity, Environment, Network, RedirectFlags, RlimitResource, SecurityProfile,
+    TermSignal,
+};
 use crate::driver::Warnings;
+use crate::sys::AssignedCpus;
 
 use spawner::pipe::{ReadPipe, WritePipe};
 use spawner::process::{Group, ProcessInfo};
-use spawner::Result;
+use spawner::unix::process::{
+    self, CpuSet, FsView, NetworkIsolation, ProcessInfoExt, Rlimit, SeccompPolicy,
+};
+use spawner::{Error, MessageSender, ProgramMessage, Result};
 
-use std::path::Path;
+use json::{object, JsonValue};
+
+use std::env;
+use std::fs::{self, DirBuilder, File, OpenOptions, Permissions};
+use std::io;
+use std::mem;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+use std::thread;
 
 pub struct ConsoleReader(libc::pid_t);
 
@@ -17,6 +36,10 @@ impl ConsoleReader {
         match unsafe { libc::fork() } {
             -1 => unreachable!("Cannot create ConsoleReader"),
             0 => {
+                // Otherwise the reader keeps the pipes of the spawner open once it's killed.
+                unsafe {
+                    libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+                }
                 f();
                 std::process::exit(0);
             }
@@ -32,6 +55,108 @@ impl ConsoleReader {
     }
 }
 
+/// The write end of the pipe the signal handler wakes up the interrupt thread through.
+static INTERRUPT_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// Runs that are interrupted on SIGINT or SIGTERM.
+static INTERRUPT_REGISTRY: Mutex<InterruptRegistry> = Mutex::new(InterruptRegistry {
+    runs: Vec::new(),
+    next_id: 0,
+    installed: false,
+});
+
+struct InterruptRegistry {
+    runs: Vec<(usize, Vec<MessageSender>)>,
+    next_id: usize,
+    installed: bool,
+}
+
+/// Sends `Interrupt` to the programs of a run on SIGINT or SIGTERM. The signal handler is
+/// installed once per process and serves every registered run. The previous handlers are
+/// restored after the first signal, so that the next one kills the spawner as usual.
+pub struct InterruptHandler(usize);
+
+impl InterruptHandler {
+    pub fn register(senders: Vec<MessageSender>) -> Result<Self> {
+        let mut registry = INTERRUPT_REGISTRY.lock().unwrap();
+        if !registry.installed {
+            install_interrupt_handler()?;
+            registry.installed = true;
+        }
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.runs.push((id, senders));
+        Ok(Self(id))
+    }
+}
+
+impl Drop for InterruptHandler {
+    fn drop(&mut self) {
+        let mut registry = INTERRUPT_REGISTRY.lock().unwrap();
+        registry.runs.retain(|(id, _)| *id != self.0);
+    }
+}
+
+fn install_interrupt_handler() -> Result<()> {
+    let mut pipe = [-1; 2];
+    if unsafe { libc::pipe2(pipe.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+        return Err(Error::from(io::Error::last_os_error()));
+    }
+    INTERRUPT_PIPE.store(pipe[1], Ordering::SeqCst);
+
+    let old_actions = [libc::SIGINT, libc::SIGTERM]
+        .iter()
+        .map(|&sig| unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = handle_interrupt as extern "C" fn(libc::c_int) as usize;
+            action.sa_flags = libc::SA_RESTART;
+            let mut old_action = mem::zeroed();
+            libc::sigaction(sig, &action, &mut old_action);
+            (sig, old_action)
+        })
+        .collect::<Vec<_>>();
+
+    let read_end = pipe[0];
+    thread::spawn(move || {
+        let mut sig = [0_u8];
+        loop {
+            match unsafe { libc::read(read_end, sig.as_mut_ptr() as *mut libc::c_void, 1) } {
+                1 => break,
+                -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => {}
+                _ => return,
+            }
+        }
+        let registry = INTERRUPT_REGISTRY.lock().unwrap();
+        restore_signal_actions(&old_actions);
+        if registry.runs.is_empty() {
+            // Nothing to interrupt, so the signal is delivered as if it wasn't handled.
+            unsafe {
+                libc::raise(libc::c_int::from(sig[0]));
+            }
+        }
+        for sender in registry.runs.iter().flat_map(|(_, senders)| senders.iter()) {
+            let _ = sender.send(ProgramMessage::Interrupt);
+        }
+    });
+    Ok(())
+}
+
+extern "C" fn handle_interrupt(sig: libc::c_int) {
+    let fd = INTERRUPT_PIPE.load(Ordering::SeqCst);
+    let sig = sig as u8;
+    unsafe {
+        libc::write(fd, &sig as *const u8 as *const libc::c_void, 1);
+    }
+}
+
+fn restore_signal_actions(actions: &[(libc::c_int, libc::sigaction)]) {
+    for (sig, action) in actions.iter() {
+        unsafe {
+            libc::sigaction(*sig, action, ptr::null_mut());
+        }
+    }
+}
+
 pub fn open_input_file(file: &Path, flags: RedirectFlags, warnings: &Warnings) -> Result<ReadPipe> {
     if flags.exclusive {
         warnings.emit("Exclusive redirect works on windows only");
@@ -50,14 +175,78 @@ pub fn open_output_file(
     WritePipe::open(file)
 }
 
+const STRICT_POLICY: &str = include_str!("../profiles/strict.policy");
+const NATIVE_POLICY: &str = include_str!("../profiles/native.policy");
+const COMPILER_POLICY: &str = include_str!("../profiles/compiler.policy");
+const SINGLE_PROCESS_POLICY: &str = include_str!("../profiles/single_process.policy");
+
+// Names of syscalls that differ between architectures.
+#[cfg(target_arch = "x86_64")]
+const ARCH_POLICY: &str = "allow newfstatat";
+
+#[cfg(target_arch = "x86")]
+const ARCH_POLICY: &str = "
+allow fstat64 fstatat64 stat64 lstat64 _llseek mmap2 fcntl64 fadvise64_64 ugetrlimit sigreturn
+allow set_thread_area getuid32 geteuid32 getgid32 getegid32
+errno(EACCES) socketcall
+";
+
+const NO_CORE_DUMPS: (Rlimit, Option<u64>) = (Rlimit::CoreSize, Some(0));
+
+struct Profile {
+    policy: &'static [&'static str],
+    rlimits: &'static [(Rlimit, Option<u64>)],
+}
+
+impl Profile {
+    fn new(profile: SecurityProfile) -> Option<Self> {
+        let (policy, rlimits): (&'static [_], &'static [_]) = match profile {
+            SecurityProfile::Disabled => return None,
+            SecurityProfile::Strict => (
+                &[STRICT_POLICY],
+                &[NO_CORE_DUMPS, (Rlimit::OpenFiles, Some(64))],
+            ),
+            SecurityProfile::Native => (
+                &[STRICT_POLICY, NATIVE_POLICY],
+                &[
+                    NO_CORE_DUMPS,
+                    (Rlimit::OpenFiles, Some(256)),
+                    (Rlimit::Stack, None),
+                ],
+            ),
+            SecurityProfile::Jvm => (
+                &[COMPILER_POLICY, SINGLE_PROCESS_POLICY],
+                &[NO_CORE_DUMPS, (Rlimit::OpenFiles, Some(1024))],
+            ),
+            SecurityProfile::Python => (
+                &[COMPILER_POLICY, SINGLE_PROCESS_POLICY],
+                &[
+                    NO_CORE_DUMPS,
+                    (Rlimit::OpenFiles, Some(256)),
+                    (Rlimit::Stack, None),
+                ],
+            ),
+            SecurityProfile::Compiler => (
+                &[COMPILER_POLICY],
+                &[NO_CORE_DUMPS, (Rlimit::OpenFiles, Some(1024))],
+            ),
+        };
+        Some(Self { policy, rlimits })
+    }
+
+    fn seccomp_policy(&self) -> Result<SeccompPolicy> {
+        let mut policy = self.policy.concat();
+        policy.push_str(ARCH_POLICY);
+        SeccompPolicy::parse(&policy)
+    }
+}
+
 pub fn init_os_specific_process_extensions(
     cmd: &Command,
     info: &mut ProcessInfo,
     _group: &mut Group,
     warnings: &Warnings,
-) -> Result<()> {
-    use spawner::unix::process::{CpuSet, ProcessInfoExt, SyscallFilterBuilder};
-
+) -> Result<AssignedCpus> {
     if cmd.show_window {
         warnings.emit("'-sw' option works on windows only");
     }
@@ -68,61 +257,213 @@ pub fn init_os_specific_process_extensions(
         info.env_inherit();
     }
 
+    if cmd.root.is_some()
+        || !cmd.read_only_binds.is_empty()
+        || !cmd.writable_binds.is_empty()
+        || !cmd.tmpfs.is_empty()
+    {
+        let mut view = FsView::new();
+        if let Some(ref root) = cmd.root {
+            view.root(root);
+        }
+        for bind in cmd.read_only_binds.iter() {
+            view.bind_read_only(&bind.source, &bind.target);
+        }
+        for bind in cmd.writable_binds.iter() {
+            view.bind_writable(&bind.source, &bind.target);
+        }
+        for dir in cmd.tmpfs.iter() {
+            view.tmpfs(dir, None);
+        }
+        info.fs_view(view);
+    }
+
+    // In rootless mode namespaces need no privileges, so profiles also run programs
+    // without network and in a new PID namespace.
+    let profile = Profile::new(cmd.secure);
+    let isolate = profile.is_some() && cmd.rootless;
+    match cmd.network {
+        Network::Host if !isolate => {}
+        Network::Loopback => {
+            info.network_isolation(NetworkIsolation::Loopback);
+        }
+        Network::Host | Network::Disabled => {
+            info.network_isolation(NetworkIsolation::Full);
+        }
+    }
+
+    if cmd.pid_namespace || isolate {
+        info.pid_namespace(true);
+    }
+
+    if cmd.rootless {
+        if cmd.username.is_some() {
+            return Err(Error::from("'-u' option can't be used in rootless mode"));
+        }
+        info.user_namespace(true);
+    }
+
+    // Explicit limits override the ones of the profile.
+    for &(resource, value) in profile.iter().flat_map(|p| p.rlimits) {
+        info.rlimit(resource, value);
+    }
+    for rlimit in cmd.rlimits.iter() {
+        let resource = match rlimit.resource {
+            RlimitResource::Stack => Rlimit::Stack,
+            RlimitResource::FileSize => Rlimit::FileSize,
+            RlimitResource::OpenFiles => Rlimit::OpenFiles,
+            RlimitResource::CoreSize => Rlimit::CoreSize,
+            RlimitResource::AddressSpace => Rlimit::AddressSpace,
+        };
+        info.rlimit(resource, rlimit.value);
+    }
+
     // On unix C++ spawner runs all processes on the first core.
+    let assigned = match cmd.cpu_affinity {
+        None => AssignedCpus {
+            cpus: vec![0],
+            _lease: None,
+        },
+        Some(CpuAffinity::Cpus(ref cpus)) => AssignedCpus {
+            cpus: cpus.clone(),
+            _lease: None,
+        },
+        Some(CpuAffinity::Auto) => {
+            let (cpu, lease) = lease_cpu()?;
+            AssignedCpus {
+                cpus: vec![cpu],
+                _lease: Some(lease),
+            }
+        }
+    };
     let mut cpuset = CpuSet::new();
-    cpuset.set(0)?;
+    for &cpu in assigned.cpus.iter() {
+        cpuset
+            .set(cpu)
+            .map_err(|_| Error::from(format!("Invalid CPU {}", cpu)))?;
+    }
     info.cpuset(cpuset);
 
-    // Syscall codes to allow execve.
-    #[cfg(target_arch = "x86")]
-    let syscall_codes = [
-        173, // rt_sigreturn
-        252, // exit_group
-        1,   // exit
-        3,   // read
-        4,   // write
-        175, // rt_sigprocmask
-        174, // rt_sigaction
-        162, // nanosleep
-        45,  // brk
-        11,  // execve
-        6,   // close
-        5,   // open
-        33,  // access
-        108, // fstat
-        90,  // mmap
-        91,  // munmap
-        125, // mprotect
-    ];
-
-    #[cfg(target_arch = "x86_64")]
-    let syscall_codes = [
-        15,  // rt_sigreturn
-        231, // exit_group
-        60,  // exit
-        0,   // read
-        1,   // write
-        14,  // rt_sigprocmask
-        13,  // rt_sigaction
-        35,  // nanosleep
-        12,  // brk
-        59,  // execve
-        3,   // close
-        2,   // open
-        21,  // access
-        5,   // fstat
-        9,   // mmap
-        158, // arch_prctl
-        11,  // munmap
-        10,  // mprotect
-    ];
-
-    if cmd.secure {
-        let mut builder = SyscallFilterBuilder::block_all();
-        for syscall in syscall_codes.iter() {
-            builder.allow(*syscall);
-        }
-        info.syscall_filter(builder.build());
+    if let Some(ref path) = cmd.seccomp_policy {
+        info.syscall_filter(SeccompPolicy::load(path)?.build()?);
+    } else if let Some(ref profile) = profile {
+        info.syscall_filter(profile.seccomp_policy()?.build()?);
     }
-    Ok(())
+    Ok(assigned)
+}
+
+pub fn signal_number(signal: TermSignal) -> i32 {
+    match signal {
+        TermSignal::Int => libc::SIGINT,
+        TermSignal::Term => libc::SIGTERM,
+        TermSignal::Hup => libc::SIGHUP,
+        TermSignal::Quit => libc::SIGQUIT,
+        TermSignal::Usr1 => libc::SIGUSR1,
+        TermSignal::Usr2 => libc::SIGUSR2,
+    }
+}
+
+/// The cgroups are made owned by the user who runs `sudo sp --setup`, if any.
+pub fn setup_cgroups() -> Result<Vec<String>> {
+    let id = |var: &str, default: u32| {
+        env::var(var)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    };
+    let uid = id("SUDO_UID", unsafe { libc::getuid() });
+    let gid = id("SUDO_GID", unsafe { libc::getgid() });
+    process::setup_cgroups(uid, gid)
+}
+
+pub fn cleanup_cgroups() -> Result<Vec<String>> {
+    process::cleanup_cgroups()
+}
+
+/// Returns the capabilities as an array of `{"Name", "Status", "Details"}` objects.
+pub fn check_capabilities() -> Result<JsonValue> {
+    Ok(JsonValue::Array(
+        process::check_capabilities()
+            .into_iter()
+            .map(|c| {
+                object! {
+                    "Name" => c.name,
+                    "Status" => c.status.to_string(),
+                    "Details" => c.details,
+                }
+            })
+            .collect(),
+    ))
+}
+
+/// Locks a free CPU out of the ones available to spawner. CPUs locked by other runs
+/// are skipped, the lock is released when the file is closed, even if spawner crashes.
+fn lease_cpu() -> Result<(usize, File)> {
+    let dir = open_cpu_lease_dir()?;
+
+    let mut available: libc::cpu_set_t = unsafe { mem::zeroed() };
+    if unsafe { libc::sched_getaffinity(0, mem::size_of_val(&available), &mut available) } == -1 {
+        return Err(Error::from(io::Error::last_os_error()));
+    }
+    for cpu in
+        (0..libc::CPU_SETSIZE as usize).filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &available) })
+    {
+        let path = dir.join(cpu.to_string());
+        // Lock files of other users can be opened for reading only. Neither links nor
+        // FIFOs planted by other users are followed or blocked on.
+        let flags = libc::O_NOFOLLOW | libc::O_NONBLOCK;
+        let file = match OpenOptions::new()
+            .read(true)
+            .custom_flags(flags)
+            .open(&path)
+        {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .mode(0o644)
+                .custom_flags(flags)
+                .open(&path)?,
+            file => file?,
+        };
+        if !file.metadata()?.is_file() {
+            return Err(Error::from(format!(
+                "{} is not a regular file",
+                path.display()
+            )));
+        }
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            return Ok((cpu, file));
+        }
+    }
+    Err(Error::from("All CPUs are leased by other runs"))
+}
+
+/// Creates the directory of CPU lock files shared by all users. An existing one is only
+/// trusted if it's a sticky directory owned by root or the current user.
+fn open_cpu_lease_dir() -> Result<PathBuf> {
+    let dir = env::temp_dir().join("spawner-cpus");
+    match DirBuilder::new().mode(0o1777).create(&dir) {
+        // The umask may have cleared some of the bits.
+        Ok(_) => OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY | libc::O_NOFOLLOW)
+            .open(&dir)?
+            .set_permissions(Permissions::from_mode(0o1777))?,
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(Error::from(e)),
+    }
+
+    let metadata = fs::symlink_metadata(&dir)?;
+    let uid = metadata.uid();
+    if !metadata.is_dir()
+        || (uid != 0 && uid != unsafe { libc::geteuid() })
+        || metadata.mode() & libc::S_ISVTX == 0
+    {
+        return Err(Error::from(format!(
+            "{} must be a sticky directory owned by root or the current user",
+            dir.display()
+        )));
+    }
+    Ok(dir)
 }
@@ -1,11 +1,14 @@
-use crate::cmd::{Command, Environment, RedirectFlags};
+use crate::cmd::{Command, Environment, Network, RedirectFlags, SecurityProfile, TermSignal};
 use crate::driver::Warnings;
+use crate::sys::AssignedCpus;
 
 use spawner::pipe::{ReadPipe, WritePipe};
 use spawner::process::{Group, ProcessInfo};
 use spawner::windows::pipe::{ReadPipeExt, WritePipeExt};
 use spawner::windows::process::{GroupExt, ProcessInfoExt, UiRestrictions};
-use spawner::Result;
+use spawner::{Error, MessageSender, Result};
+
+use json::JsonValue;
 
 use std::os::windows::io::AsRawHandle;
 use std::path::Path;
@@ -32,6 +35,15 @@ impl ConsoleReader {
     }
 }
 
+/// Console control events are not handled on Windows.
+pub struct InterruptHandler;
+
+impl InterruptHandler {
+    pub fn register(_senders: Vec<MessageSender>) -> Result<Self> {
+        Ok(Self)
+    }
+}
+
 pub fn open_input_file(
     file: &Path,
     flags: RedirectFlags,
@@ -56,19 +68,70 @@ pub fn open_output_file(
     }
 }
 
+/// There are no signals on Windows, groups are killed at once instead.
+pub fn signal_number(_signal: TermSignal) -> i32 {
+    0
+}
+
+pub fn setup_cgroups() -> Result<Vec<String>> {
+    Err(Error::from("'--setup' works on unix only"))
+}
+
+pub fn cleanup_cgroups() -> Result<Vec<String>> {
+    Err(Error::from("'--cleanup' works on unix only"))
+}
+
+pub fn check_capabilities() -> Result<JsonValue> {
+    Err(Error::from("'--check' works on unix only"))
+}
+
 pub fn init_os_specific_process_extensions(
     cmd: &Command,
     info: &mut ProcessInfo,
     group: &mut Group,
-    _warnings: &Warnings,
-) -> Result<()> {
+    warnings: &Warnings,
+) -> Result<AssignedCpus> {
+    if cmd.root.is_some()
+        || !cmd.read_only_binds.is_empty()
+        || !cmd.writable_binds.is_empty()
+        || !cmd.tmpfs.is_empty()
+    {
+        warnings.emit("'-root', '-bind-ro', '-bind-rw' and '-tmpfs' options work on unix only");
+    }
+    if cmd.network != Network::Host {
+        warnings.emit("'-net' option works on unix only");
+    }
+    if cmd.pid_namespace {
+        warnings.emit("'-pid-ns' option works on unix only");
+    }
+    if cmd.rootless {
+        warnings.emit("'-rootless' option works on unix only");
+    }
+    if !cmd.rlimits.is_empty() {
+        warnings.emit("'-rlimit' option works on unix only");
+    }
+    if cmd.seccomp_policy.is_some() {
+        warnings.emit("'-seccomp' option works on unix only");
+    }
+    if cmd.cpu_affinity.is_some() {
+        warnings.emit("'-cpu' option works on unix only");
+    }
+    if cmd.term_signal.is_some() {
+        warnings.emit("'-term-signal' option works on unix only, executables are killed at once");
+    }
+    match cmd.secure {
+        SecurityProfile::Disabled | SecurityProfile::Strict => {}
+        _ => {
+            warnings.emit("Named security profiles work on unix only, '-s=1' will be used instead")
+        }
+    }
     if cmd.show_window {
         info.show_window(true);
     }
     if cmd.env == Environment::UserDefault {
         info.env_user();
     }
-    if cmd.secure {
+    if cmd.secure != SecurityProfile::Disabled {
         group.set_ui_restrictions(
             UiRestrictions::new()
                 .limit_desktop()
@@ -81,5 +144,5 @@ pub fn init_os_specific_process_extensions(
                 .limit_system_parameters(),
         )?;
     }
-    Ok(())
+    Ok(AssignedCpus::default())
 }
@@ -28,16 +28,32 @@ fn parse_opt_delimeters() {
 fn parse_basic_opts() {
     check_opt!(&["-tl=10"], time_limit, Some(fsec2dur(10.0)));
     check_opt!(&["-d=10"], wall_clock_time_limit, Some(fsec2dur(10.0)));
+    check_opt!(&["-il=1000"], instruction_limit, Some(1000));
+    check_opt!(&["-perf=1"], perf, true);
     check_opt!(&["-ml=10"], memory_limit, Some(10.0));
+    check_opt!(&["-mh=10"], memory_high, Some(10.0));
+    check_opt!(&["-swap=0"], swap, false);
+    check_opt!(&["-kmem=0"], kernel_memory, false);
     check_opt!(&["-wl=10"], write_limit, Some(10.0));
-    check_opt!(&["-s=1"], secure, true);
+    check_opt!(&["-rl=10"], read_limit, Some(10.0));
+    check_opt!(&["-rbw=10"], read_bandwidth, Some(10.0));
+    check_opt!(&["-wbw=10"], write_bandwidth, Some(10.0));
+    check_opt!(&["-s=1"], secure, SecurityProfile::Strict);
     check_opt!(&["-y=10"], idle_time_limit, Some(fsec2dur(10.0)));
     check_opt!(&["-lr=10"], load_ratio, 10.0);
     check_opt!(&["-lr=10%"], load_ratio, 10.0);
+    check_opt!(&["-cpu-quota=50%"], cpu_quota, Some(50.0));
     check_opt!(&["-sw=1"], show_window, true);
     check_opt!(&["--debug=1"], debug, true);
     check_opt!(&["-mi=0.1"], monitor_interval, fsec2dur(0.1));
     check_opt!(&["-wd=asd"], working_directory, Some(String::from("asd")));
+    check_opt!(&["-pid-ns=1"], pid_namespace, true);
+    check_opt!(&["-rootless=1"], rootless, true);
+    check_opt!(
+        &["-seccomp=policy.txt"],
+        seccomp_policy,
+        Some(String::from("policy.txt"))
+    );
     check_opt!(&["-hr=1"], hide_report, true);
     check_opt!(&["-ho=1"], hide_output, true);
     check_opt!(&["-runas=1"], delegated, true);
@@ -63,6 +79,10 @@ fn parse_basic_opts() {
     check_opt!(&["-j"], use_json, true);
     check_opt!(&["--json"], use_json, true);
     check_opt!(&["--wait-for-children"], wait_for_children, true);
+    check_opt!(&["--multiplex"], multiplex, true);
+    check_opt!(&["--setup"], setup, true);
+    check_opt!(&["--cleanup"], cleanup, true);
+    check_opt!(&["--check"], check, true);
 }
 
 #[test]
@@ -87,6 +107,76 @@ fn parse_env_type() {
     }
 }
 
+#[test]
+fn parse_network() {
+    check_opt!(&["-net=host"], network, Network::Host);
+    check_opt!(&["-net=loopback"], network, Network::Loopback);
+    check_opt!(&["-net=none"], network, Network::Disabled);
+    assert!(Command::default().parse_argv(["-net=all"]).is_err());
+}
+
+#[test]
+fn parse_term_signal() {
+    check_opt!(&["-term-signal=term"], term_signal, Some(TermSignal::Term));
+    check_opt!(&["-term-signal=usr1"], term_signal, Some(TermSignal::Usr1));
+    check_opt!(&["-grace-period=0.5"], grace_period, fsec2dur(0.5));
+    assert!(Command::default()
+        .parse_argv(["-term-signal=kill"])
+        .is_err());
+}
+
+#[test]
+fn parse_cpu_quota() {
+    check_opt!(&["-cpu-quota=1"], cpu_quota, Some(1.0));
+    check_opt!(&["-cpu-quota=250%"], cpu_quota, Some(250.0));
+    assert!(Command::default().parse_argv(["-cpu-quota=0.5%"]).is_err());
+    assert!(Command::default().parse_argv(["-cpu-quota=0"]).is_err());
+    assert!(Command::default().parse_argv(["-cpu-quota=-10%"]).is_err());
+}
+
+#[test]
+fn parse_time_scale() {
+    check_opt!(&["-time-scale=2"], time_scale, 2.0);
+    assert!(Command::default().parse_argv(["-time-scale=0"]).is_err());
+    assert!(Command::default().parse_argv(["-time-scale=fast"]).is_err());
+    assert!(Command::default()
+        .parse_argv(["-time-scale=1e-20"])
+        .is_err());
+    assert!(Command::default().parse_argv(["-time-scale=1e20"]).is_err());
+}
+
+#[test]
+fn parse_memory_metric() {
+    check_opt!(&["-mm=group"], memory_metric, MemoryMetric::Group);
+    check_opt!(&["-mm=rss"], memory_metric, MemoryMetric::Rss);
+    assert!(Command::default().parse_argv(["-mm=vms"]).is_err());
+}
+
+#[test]
+fn parse_cpu_affinity() {
+    check_opt!(&["-cpu=auto"], cpu_affinity, Some(CpuAffinity::Auto));
+    check_opt!(&["-cpu=1"], cpu_affinity, Some(CpuAffinity::Cpus(vec![1])));
+    check_opt!(
+        &["-cpu=4,0-2,1"],
+        cpu_affinity,
+        Some(CpuAffinity::Cpus(vec![0, 1, 2, 4]))
+    );
+    assert!(Command::default().parse_argv(["-cpu=2-1"]).is_err());
+    assert!(Command::default().parse_argv(["-cpu=a"]).is_err());
+    assert!(Command::default().parse_argv(["-cpu=1,"]).is_err());
+}
+
+#[test]
+fn parse_security_profile() {
+    check_opt!(&["-s=0"], secure, SecurityProfile::Disabled);
+    check_opt!(&["-s=strict"], secure, SecurityProfile::Strict);
+    check_opt!(&["-s=native"], secure, SecurityProfile::Native);
+    check_opt!(&["-s=jvm"], secure, SecurityProfile::Jvm);
+    check_opt!(&["-s=python"], secure, SecurityProfile::Python);
+    check_opt!(&["-s=compiler"], secure, SecurityProfile::Compiler);
+    assert!(Command::default().parse_argv(["-s=2"]).is_err());
+}
+
 #[test]
 fn parse_env_var() {
     let mut cmd = Command::default();
@@ -99,6 +189,74 @@ fn parse_env_var() {
     assert_eq!(v1.1, "d");
 }
 
+#[test]
+fn parse_fs_view() {
+    let mut cmd = Command::default();
+    let _ = cmd.parse_argv([
+        "-root=/srv/jail",
+        "-bind-ro=/usr",
+        "-bind-ro=/opt/tests:/tests",
+        "-bind-rw:/tmp/run:/home",
+        "-tmpfs=/tmp",
+    ]);
+    assert_eq!(cmd.root, Some(String::from("/srv/jail")));
+    assert_eq!(
+        cmd.read_only_binds,
+        vec![
+            BindMount {
+                source: String::from("/usr"),
+                target: String::from("/usr"),
+            },
+            BindMount {
+                source: String::from("/opt/tests"),
+                target: String::from("/tests"),
+            },
+        ]
+    );
+    assert_eq!(
+        cmd.writable_binds,
+        vec![BindMount {
+            source: String::from("/tmp/run"),
+            target: String::from("/home"),
+        }]
+    );
+    assert_eq!(cmd.tmpfs, vec![String::from("/tmp")]);
+    assert!(Command::default().parse_argv(["-bind-ro=/usr:"]).is_err());
+}
+
+#[test]
+fn parse_rlimits() {
+    let mut cmd = Command::default();
+    let _ = cmd.parse_argv([
+        "-rlimit=stack:unlimited",
+        "-rlimit=nofile:64",
+        "-rlimit=fsize:1M",
+        "-rlimit=fsize:2kB",
+    ]);
+    assert_eq!(
+        cmd.rlimits,
+        vec![
+            Rlimit {
+                resource: RlimitResource::Stack,
+                value: None,
+            },
+            Rlimit {
+                resource: RlimitResource::OpenFiles,
+                value: Some(64),
+            },
+            Rlimit {
+                resource: RlimitResource::FileSize,
+                value: Some(2048),
+            },
+        ]
+    );
+    assert!(Command::default().parse_argv(["-rlimit=cpu:1"]).is_err());
+    assert!(Command::default()
+        .parse_argv(["-rlimit=nofile:1k"])
+        .is_err());
+    assert!(Command::default().parse_argv(["-rlimit=stack"]).is_err());
+}
+
 macro_rules! check_file_flags {
     ($init_flush:expr, $init_exclusive:expr, $input:expr, $expected_flush:expr, $expected_exclusive:expr) => {{
         let mut cmd = Command::default();
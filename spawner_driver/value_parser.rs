@@ -1,6 +1,7 @@
 use crate::cmd::{
-    Command, Environment, Redirect, RedirectFlags, RedirectKind, RedirectList, StderrRedirectList,
-    StdinRedirectList, StdoutRedirectList,
+    BindMount, Command, CpuAffinity, Environment, MemoryMetric, Network, Redirect, RedirectFlags,
+    RedirectKind, RedirectList, Rlimit, RlimitResource, SecurityProfile, StderrRedirectList,
+    StdinRedirectList, StdoutRedirectList, TermSignal,
 };
 
 use spawner_opts::OptionValueParser;
@@ -26,6 +27,17 @@ impl OptionValueParser<Option<usize>> for DefaultValueParser {
     }
 }
 
+impl OptionValueParser<Option<u64>> for DefaultValueParser {
+    fn parse(opt: &mut Option<u64>, v: &str) -> Result<(), String> {
+        if let Ok(v) = v.parse::<u64>() {
+            *opt = Some(v);
+            Ok(())
+        } else {
+            Err(format!("Invalid value '{}'", v))
+        }
+    }
+}
+
 impl OptionValueParser<bool> for DefaultValueParser {
     fn parse(opt: &mut bool, v: &str) -> Result<(), String> {
         if v.len() == 1 {
@@ -72,6 +84,79 @@ impl OptionValueParser<Environment> for DefaultValueParser {
     }
 }
 
+impl OptionValueParser<MemoryMetric> for DefaultValueParser {
+    fn parse(metric: &mut MemoryMetric, v: &str) -> Result<(), String> {
+        match v {
+            "group" => *metric = MemoryMetric::Group,
+            "rss" => *metric = MemoryMetric::Rss,
+            _ => {
+                return Err(format!(
+                    "Unknown memory metric '{}' expected one of: group, rss",
+                    v
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OptionValueParser<Option<TermSignal>> for DefaultValueParser {
+    fn parse(signal: &mut Option<TermSignal>, v: &str) -> Result<(), String> {
+        *signal = Some(match v {
+            "int" => TermSignal::Int,
+            "term" => TermSignal::Term,
+            "hup" => TermSignal::Hup,
+            "quit" => TermSignal::Quit,
+            "usr1" => TermSignal::Usr1,
+            "usr2" => TermSignal::Usr2,
+            _ => {
+                return Err(format!(
+                    "Unknown signal '{}' expected one of: int, term, hup, quit, usr1, usr2",
+                    v
+                ));
+            }
+        });
+        Ok(())
+    }
+}
+
+impl OptionValueParser<Network> for DefaultValueParser {
+    fn parse(net: &mut Network, v: &str) -> Result<(), String> {
+        match v {
+            "host" => *net = Network::Host,
+            "loopback" => *net = Network::Loopback,
+            "none" => *net = Network::Disabled,
+            _ => {
+                return Err(format!(
+                    "Unknown network type '{}' expected one of: host, loopback, none",
+                    v
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OptionValueParser<SecurityProfile> for DefaultValueParser {
+    fn parse(profile: &mut SecurityProfile, v: &str) -> Result<(), String> {
+        *profile = match v {
+            "0" => SecurityProfile::Disabled,
+            "1" | "strict" => SecurityProfile::Strict,
+            "native" => SecurityProfile::Native,
+            "jvm" => SecurityProfile::Jvm,
+            "python" => SecurityProfile::Python,
+            "compiler" => SecurityProfile::Compiler,
+            _ => {
+                return Err(format!(
+                    "Unknown security profile '{}' expected one of: 0, 1, strict, native, jvm, python, compiler",
+                    v
+                ));
+            }
+        };
+        Ok(())
+    }
+}
+
 impl OptionValueParser<Vec<(String, String)>> for DefaultValueParser {
     fn parse(vars: &mut Vec<(String, String)>, v: &str) -> Result<(), String> {
         if let Some(pos) = v.find(|x| x == '=') {
@@ -86,6 +171,105 @@ impl OptionValueParser<Vec<(String, String)>> for DefaultValueParser {
     }
 }
 
+impl OptionValueParser<Vec<String>> for DefaultValueParser {
+    fn parse(values: &mut Vec<String>, v: &str) -> Result<(), String> {
+        values.push(v.to_string());
+        Ok(())
+    }
+}
+
+impl OptionValueParser<Vec<BindMount>> for DefaultValueParser {
+    fn parse(binds: &mut Vec<BindMount>, v: &str) -> Result<(), String> {
+        let (source, target) = match v.find(':') {
+            Some(pos) => (&v[0..pos], &v[pos + 1..v.len()]),
+            None => (v, v),
+        };
+        if source.is_empty() || target.is_empty() {
+            return Err(format!(
+                "Invalid bind mount '{}'. Expected <src>[:<dst>]",
+                v
+            ));
+        }
+        binds.push(BindMount {
+            source: source.to_string(),
+            target: target.to_string(),
+        });
+        Ok(())
+    }
+}
+
+impl OptionValueParser<Vec<Rlimit>> for DefaultValueParser {
+    fn parse(rlimits: &mut Vec<Rlimit>, v: &str) -> Result<(), String> {
+        let (name, value) = match v.find(':') {
+            Some(pos) => (&v[0..pos], &v[pos + 1..v.len()]),
+            None => {
+                return Err(format!(
+                    "Invalid resource limit '{}'. Expected <resource>:<value>",
+                    v
+                ))
+            }
+        };
+        let resource = match name {
+            "stack" => RlimitResource::Stack,
+            "fsize" => RlimitResource::FileSize,
+            "nofile" => RlimitResource::OpenFiles,
+            "core" => RlimitResource::CoreSize,
+            "as" => RlimitResource::AddressSpace,
+            _ => {
+                return Err(format!(
+                    "Unknown resource '{}' expected one of: stack, fsize, nofile, core, as",
+                    name
+                ))
+            }
+        };
+        let value = if value == "unlimited" {
+            None
+        } else if resource == RlimitResource::OpenFiles {
+            Some(
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid value '{}'", value))?,
+            )
+        } else {
+            // Sizes are in megabytes by default, like the memory limit.
+            let (val, mult) = parse_value(value, parse_mem_degree, parse_mem_unit)
+                .ok_or_else(|| format!("Invalid value '{}'", value))?;
+            Some((val * mult.unwrap_or_else(|| f64::powf(2.0, 20.0))) as u64)
+        };
+        rlimits.retain(|r| r.resource != resource);
+        rlimits.push(Rlimit { resource, value });
+        Ok(())
+    }
+}
+
+impl OptionValueParser<Option<CpuAffinity>> for DefaultValueParser {
+    fn parse(affinity: &mut Option<CpuAffinity>, v: &str) -> Result<(), String> {
+        if v == "auto" {
+            *affinity = Some(CpuAffinity::Auto);
+            return Ok(());
+        }
+        let parse_cpu = |s: &str| {
+            s.parse::<usize>()
+                .map_err(|_| format!("Invalid CPU list '{}'", v))
+        };
+        let mut cpus = Vec::new();
+        for range in v.split(',') {
+            let (first, last) = match range.find('-') {
+                Some(pos) => (parse_cpu(&range[..pos])?, parse_cpu(&range[pos + 1..])?),
+                None => (parse_cpu(range)?, parse_cpu(range)?),
+            };
+            if first > last {
+                return Err(format!("Invalid CPU list '{}'", v));
+            }
+            cpus.extend(first..=last);
+        }
+        cpus.sort_unstable();
+        cpus.dedup();
+        *affinity = Some(CpuAffinity::Cpus(cpus));
+        Ok(())
+    }
+}
+
 impl OptionValueParser<Duration> for DefaultValueParser {
     fn parse(opt: &mut Duration, v: &str) -> Result<(), String> {
         *opt = parse_time_value(v)?;
@@ -93,6 +277,22 @@ impl OptionValueParser<Duration> for DefaultValueParser {
     }
 }
 
+impl OptionValueParser<f64> for DefaultValueParser {
+    fn parse(opt: &mut f64, v: &str) -> Result<(), String> {
+        // The value scales durations, which overflow beyond these bounds.
+        match v.parse::<f64>() {
+            Ok(num) if (1e-6..=1e6).contains(&num) => {
+                *opt = num;
+                Ok(())
+            }
+            _ => Err(format!(
+                "Invalid value '{}', expected a number from 1e-6 to 1e6",
+                v
+            )),
+        }
+    }
+}
+
 impl OptionValueParser<Option<Duration>> for DefaultValueParser {
     fn parse(opt: &mut Option<Duration>, v: &str) -> Result<(), String> {
         *opt = Some(parse_time_value(v)?);
@@ -125,6 +325,19 @@ impl OptionValueParser<f64> for PercentValueParser {
     }
 }
 
+impl OptionValueParser<Option<f64>> for PercentValueParser {
+    fn parse(opt: &mut Option<f64>, v: &str) -> Result<(), String> {
+        let mut num = 0.0;
+        PercentValueParser::parse(&mut num, v)?;
+        // The kernel rejects CPU quotas shorter than 1ms of a 100ms period.
+        if !num.is_finite() || num < 1.0 {
+            return Err(format!("Invalid value '{}', expected at least 1%", v));
+        }
+        *opt = Some(num);
+        Ok(())
+    }
+}
+
 macro_rules! check_redirect {
     ($redirect:expr, $expected:ident, invalid => ($a:ident, $b:ident)) => {{
         match $redirect.kind {
@@ -2,7 +2,7 @@ use std::alloc::{alloc, Layout};
 use std::env;
 use std::fs;
 use std::io::*;
-use std::net::{TcpListener, UdpSocket};
+use std::net::{TcpListener, TcpStream, UdpSocket};
 use std::process;
 use std::ptr;
 use std::str;
@@ -62,6 +62,58 @@ fn fwrite(filename: String, kb: usize) {
     }
 }
 
+fn fread(filename: String) {
+    let mut file = fs::File::open(&filename).unwrap();
+    let mut chunk = [0_u8; 1024];
+    while file.read(&mut chunk).is_ok_and(|n| n > 0) {}
+}
+
+#[cfg(unix)]
+fn fread_uncached(filename: String) {
+    use std::os::unix::io::AsRawFd;
+
+    // Dropping the file from the page cache makes the reads hit the disk.
+    let file = fs::File::open(&filename).unwrap();
+    file.sync_all().unwrap();
+    unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    fread(filename);
+}
+
+fn create_files(prefix: String, num: usize) {
+    for i in 0..num {
+        fs::File::create(format!("{}{}", prefix, i)).unwrap();
+    }
+}
+
+fn print_memory_limits() {
+    // Prints the swap limit and the throttling threshold of the memory cgroup.
+    let cgroups = fs::read_to_string("/proc/self/cgroup").unwrap();
+    let path = |controllers: &str| {
+        cgroups
+            .lines()
+            .map(|line| line.splitn(3, ':').skip(1).collect::<Vec<_>>())
+            .find(|parts| parts[0] == controllers)
+            .map(|parts| parts[1].to_string())
+    };
+    let read = |file: String| fs::read_to_string(file).unwrap().trim().to_string();
+    if let Some(path) = path("memory") {
+        let dir = format!("/sys/fs/cgroup/memory{}", path);
+        let memsw: u64 = read(format!("{}/memory.memsw.limit_in_bytes", dir))
+            .parse()
+            .unwrap();
+        let mem: u64 = read(format!("{}/memory.limit_in_bytes", dir))
+            .parse()
+            .unwrap();
+        let soft_limit = read(format!("{}/memory.soft_limit_in_bytes", dir));
+        print!("{} {}", memsw - mem, soft_limit);
+    } else {
+        let dir = format!("/sys/fs/cgroup{}", path("").unwrap());
+        let swap = read(format!("{}/memory.swap.max", dir));
+        let high = read(format!("{}/memory.high", dir));
+        print!("{} {}", swap, high);
+    }
+}
+
 fn pipe_loop() {
     let mut chunk = [0_u8; 128];
     while let Ok(bytes) = stdin().read(&mut chunk) {
@@ -124,7 +176,12 @@ fn main() {
             "sleep" => thread::sleep(p.parse_flt_secs()),
             "alloc" => alloc_((p.parse::<f64>() * 1024.0 * 1024.0) as usize),
             "fwrite" => fwrite(p.next(), p.parse()),
+            "fread" => fread(p.next()),
+            #[cfg(unix)]
+            "fread_uncached" => fread_uncached(p.next()),
+            "create_files" => create_files(p.next(), p.parse()),
             "pipe_loop" => pipe_loop(),
+            "print_memory_limits" => print_memory_limits(),
             "print_n" => {
                 let s = p.next();
                 (0..p.parse::<usize>()).for_each(|_| print!("{}", s));
@@ -140,6 +197,11 @@ fn main() {
                 thread::sleep(Duration::from_secs(3));
                 return;
             }
+            "connect" => {
+                if TcpStream::connect(p.next()).is_err() {
+                    process::exit(1);
+                }
+            }
             "create_tcpv4_sockets" => create_tcp_sockets(p.parse(), "127.0.0.1"),
             "create_tcpv6_sockets" => create_tcp_sockets(p.parse(), "[::1]"),
             "create_udpv4_sockets" => create_udp_sockets(p.parse(), "127.0.0.1"),
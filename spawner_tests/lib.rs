@@ -7,6 +7,7 @@ mod common;
 
 cfg_if! {
     if #[cfg(test)] {
+        extern crate json;
         extern crate rand;
         extern crate spawner;
         extern crate spawner_driver;
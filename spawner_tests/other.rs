@@ -1,8 +1,10 @@
-use crate::term_reason::{ensure_ok, ensure_wall_clock_time_limit_exceeded};
+#[cfg(unix)]
+use crate::term_reason::ensure_security_violation;
+use crate::term_reason::{
+    ensure_ok, ensure_user_time_limit_exceeded, ensure_wall_clock_time_limit_exceeded,
+};
 
-use crate::common::APP;
-#[cfg(windows)]
-use crate::common::{read_all, write_all, TmpDir};
+use crate::common::{read_all, write_all, TmpDir, APP};
 
 use spawner_driver::run;
 
@@ -145,6 +147,60 @@ fn wait_for_children() {
     ensure_wall_clock_time_limit_exceeded(&r[0]);
 }
 
+#[test]
+fn multiplex() {
+    let r = run([
+        "--separator=@",
+        "--multiplex",
+        "--@",
+        "-d=0.3",
+        APP,
+        "sleep",
+        "1",
+        "--@",
+        "-tl=0.2",
+        APP,
+        "loop",
+        "1",
+        "--@",
+        APP,
+        "sleep",
+        "0.1",
+    ])
+    .unwrap();
+    ensure_wall_clock_time_limit_exceeded(&r[0]);
+    ensure_user_time_limit_exceeded(&r[1]);
+    ensure_ok(&r[2]);
+}
+
+#[test]
+fn multiplexed_pipes() {
+    // The data that doesn't fit into a pipe waits until the reader drains it.
+    let tmp = TmpDir::new();
+    let stderr = tmp.file("stderr.txt");
+    let r = run([
+        "--separator=@",
+        "--multiplex",
+        "-d=5",
+        "--@",
+        "--out=*1.stdin",
+        APP,
+        "print_n",
+        "0123456789",
+        "100000",
+        "--@",
+        format!("--err={}", stderr).as_str(),
+        APP,
+        "pipe_loop",
+    ])
+    .unwrap();
+    ensure_ok(&r[0]);
+    ensure_ok(&r[1]);
+    let data = read_all(stderr);
+    assert_eq!(data.len(), 1_000_000);
+    assert!(data.starts_with("0123456789") && data.ends_with("0123456789"));
+}
+
 #[cfg(windows)]
 #[test]
 fn search_in_path_enabled() {
@@ -172,3 +228,280 @@ fn search_in_path_disabled() {
     let r = run(&["sh", "-c", "exit"]).unwrap();
     assert!(!r[0].spawner_error.is_empty());
 }
+
+#[cfg(unix)]
+#[test]
+fn read_only_bind() {
+    let tmp = TmpDir::new();
+    let file = tmp.file("file.txt");
+    let dir = std::path::Path::new(&file)
+        .parent()
+        .unwrap()
+        .display()
+        .to_string();
+    let bind = format!("-bind-ro={}:/mnt/ro", dir);
+    let script = "echo data > /mnt/ro/file.txt || echo data > /mnt/file.txt";
+    let r = run(["-tmpfs=/mnt", &bind, "-c", "sh", "-c", script]).unwrap();
+    ensure_ok(&r[0]);
+    assert_eq!(read_all(&file), "");
+
+    // Mount points are not created on the host.
+    let missing = format!("-bind-ro={}:{}/missing", dir, dir);
+    let r = run([&missing, "-c", "sh", "-c", "exit"]).unwrap();
+    assert!(!r[0].spawner_error.is_empty());
+    assert!(!std::path::Path::new(&dir).join("missing").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn network_isolation() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let r = run([APP, "connect", &addr]).unwrap();
+    assert_eq!(r[0].exit_code, 0);
+
+    // The host loopback isn't reachable from the loopback of the namespace.
+    for net in ["-net=loopback", "-net=none"].iter() {
+        let r = run([net, APP, "connect", &addr]).unwrap();
+        ensure_ok(&r[0]);
+        assert_eq!(r[0].exit_code, 1);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn pid_namespace() {
+    // Host processes can't be signaled and the program is one of the first in the namespace.
+    let script = format!(
+        "! kill -0 {} 2>/dev/null && [ $$ -le 2 ]",
+        std::process::id()
+    );
+    let r = run(["-pid-ns=1", "-c", "sh", "-c", &script]).unwrap();
+    ensure_ok(&r[0]);
+    assert_eq!(r[0].exit_code, 0);
+
+    let r = run(["-c", "sh", "-c", &script]).unwrap();
+    assert_eq!(r[0].exit_code, 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn rootless() {
+    // Only the user itself is mapped into the user namespace.
+    let script = "read inner outer count < /proc/self/uid_map; [ $count = 1 ] && [ $$ -le 2 ]";
+    let r = run(["-rootless=1", "-pid-ns=1", "-c", "sh", "-c", script]).unwrap();
+    ensure_ok(&r[0]);
+    assert_eq!(r[0].exit_code, 0);
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let r = run(["-rootless=1", "-net=none", APP, "connect", &addr]).unwrap();
+    ensure_ok(&r[0]);
+    assert_eq!(r[0].exit_code, 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn seccomp_policy() {
+    let tmp = TmpDir::new();
+    let policy = tmp.file("policy.txt");
+    write_all(&policy, "default allow\nkill clock_nanosleep\n");
+    let r = run([&format!("-seccomp={}", policy), APP, "sleep", "0.1"]).unwrap();
+    ensure_security_violation(&r[0], "clock_nanosleep");
+
+    write_all(
+        &policy,
+        "default allow\nerrno(EPERM) clock_nanosleep if arg0 > 0xffff\n",
+    );
+    let r = run([&format!("-seccomp={}", policy), APP, "sleep", "0.1"]).unwrap();
+    ensure_ok(&r[0]);
+
+    write_all(&policy, "default allow\nexec once\n");
+    let r = run([&format!("-seccomp={}", policy), APP, "exec_rest", APP]).unwrap();
+    ensure_security_violation(&r[0], "execve");
+    let r = run([&format!("-seccomp={}", policy), "-c", "true"]).unwrap();
+    ensure_ok(&r[0]);
+}
+
+#[cfg(unix)]
+#[test]
+fn security_profiles() {
+    for profile in ["strict", "native", "jvm", "python", "compiler"].iter() {
+        let r = run([&format!("-s={}", profile), APP, "sleep", "0.01"]).unwrap();
+        ensure_ok(&r[0]);
+    }
+
+    let tmp = TmpDir::new();
+    let file = tmp.file("file.txt");
+    let r = run(["-s=strict", APP, "fwrite", &file, "1"]).unwrap();
+    ensure_security_violation(&r[0], "unlink");
+    let r = run(["-s=native", APP, "fwrite", &file, "1"]).unwrap();
+    ensure_ok(&r[0]);
+
+    let r = run(["-s=python", APP, "exec_rest", APP]).unwrap();
+    ensure_security_violation(&r[0], "clone");
+    let r = run(["-s=compiler", APP, "exec_rest", APP]).unwrap();
+    ensure_ok(&r[0]);
+    let r = run(["-s=compiler", "-c", "unshare", "-n", "true"]).unwrap();
+    ensure_security_violation(&r[0], "unshare");
+}
+
+#[cfg(unix)]
+#[test]
+fn security_profile_namespaces() {
+    // Profiles use namespaces only in rootless mode, where they need no privileges.
+    let script = "[ $$ -gt 2 ]";
+    let r = run(["-s=compiler", "-c", "sh", "-c", script]).unwrap();
+    ensure_ok(&r[0]);
+    assert_eq!(r[0].exit_code, 0);
+    let r = run(["-s=compiler", "-rootless=1", "-c", "sh", "-c", script]).unwrap();
+    ensure_ok(&r[0]);
+    assert_eq!(r[0].exit_code, 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn invalid_seccomp_policy() {
+    let tmp = TmpDir::new();
+    let policy = tmp.file("policy.txt");
+    write_all(&policy, "allow no_such_syscall\n");
+    assert!(run([&format!("-seccomp={}", policy), APP]).is_err());
+    write_all(&policy, "exec twice\n");
+    assert!(run([&format!("-seccomp={}", policy), APP]).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn cleanup_cgroups() {
+    // Cgroups of running spawners are locked and left alone.
+    let r = run(["--cleanup"]).unwrap();
+    assert!(r.is_empty());
+    assert!(run(["--cleanup", APP, "exit", "0"]).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn cleanup_orphaned_cgroup() {
+    use std::fs::{self, File};
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+    use std::process::Command;
+
+    // Delegated subtrees are only set up by the spawner itself.
+    let root = Path::new("/sys/fs/cgroup");
+    let dirs = if root.join("cgroup.controllers").exists() {
+        vec![root.join("sp")]
+    } else {
+        vec![root.join("freezer/sp"), root.join("memory/sp")]
+    };
+    if !dirs[0].is_dir() {
+        return;
+    }
+
+    // A cgroup left behind by a crashed spawner has tasks and no lock. Like the spawner,
+    // the test locks the parent while the cgroup is set up, so that concurrent cleanups
+    // wait for the task to be added.
+    let name = format!("task_orphan{}", std::process::id());
+    let mut child = Command::new(APP).args(["sleep", "10"]).spawn().unwrap();
+    let parent = File::open(&dirs[0]).unwrap();
+    unsafe { libc::flock(parent.as_raw_fd(), libc::LOCK_SH) };
+    for dir in dirs.iter() {
+        fs::create_dir(dir.join(&name)).unwrap();
+        fs::write(dir.join(&name).join("cgroup.procs"), child.id().to_string()).unwrap();
+    }
+    drop(parent);
+
+    assert!(run(["--cleanup"]).unwrap().is_empty());
+    assert!(dirs.iter().all(|dir| !dir.join(&name).exists()));
+    assert!(!child.wait().unwrap().success());
+}
+
+#[cfg(unix)]
+#[test]
+fn check_capabilities() {
+    use crate::common::SP;
+    use std::process::Command;
+    use std::str;
+
+    let output = Command::new(SP).arg("--check").output().unwrap();
+    assert!(output.status.success());
+    let caps = json::parse(str::from_utf8(&output.stdout).unwrap()).unwrap();
+    assert!(caps.is_array());
+    assert!(!caps.is_empty());
+    for cap in caps.members() {
+        assert!(cap["Name"].is_string());
+        let status = cap["Status"].as_str().unwrap();
+        assert!(["Available", "Degraded", "Unavailable"].contains(&status));
+        assert!(cap["Details"].is_string());
+    }
+    assert!(caps.members().any(|cap| cap["Name"] == "cgroups"));
+    assert!(run(["--check", APP, "exit", "0"]).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn cpu_affinity() {
+    let r = run(["-cpu=0", APP, "sleep", "0.01"]).unwrap();
+    ensure_ok(&r[0]);
+    assert_eq!(r[0].cpus, vec![0]);
+
+    let r = run([
+        "--separator=@",
+        "-cpu=auto",
+        APP,
+        "sleep",
+        "0.01",
+        "--@",
+        "-cpu=auto",
+        APP,
+        "sleep",
+        "0.01",
+    ]);
+    if std::thread::available_parallelism().unwrap().get() > 1 {
+        let r = r.unwrap();
+        ensure_ok(&r[0]);
+        ensure_ok(&r[1]);
+        assert_eq!(r[0].cpus.len(), 1);
+        assert_eq!(r[1].cpus.len(), 1);
+        assert_ne!(r[0].cpus, r[1].cpus);
+    } else {
+        assert!(r.is_err());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn supervisor_wakeups() {
+    use crate::common::SP;
+    use std::fs;
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use std::time::Duration;
+
+    // Messages to the program wake up the supervisor, so the usage is sampled
+    // at the monitor interval rather than every millisecond.
+    let wakeups = |pid: u32| -> u64 {
+        fs::read_dir(format!("/proc/{}/task", pid))
+            .unwrap()
+            .filter_map(|task| fs::read_to_string(task.unwrap().path().join("status")).ok())
+            .flat_map(|status| {
+                status
+                    .lines()
+                    .filter_map(|l| l.strip_prefix("voluntary_ctxt_switches:"))
+                    .map(|v| v.trim().parse::<u64>().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .sum()
+    };
+    let mut sp = Command::new(SP)
+        .args(["-mi=0.1s", APP, "sleep", "1"])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    let before = wakeups(sp.id());
+    thread::sleep(Duration::from_millis(500));
+    let after = wakeups(sp.id());
+    assert!(sp.wait().unwrap().success());
+    assert!(after - before < 50, "{} wakeups in 0.5s", after - before);
+}
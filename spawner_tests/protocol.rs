@@ -84,6 +84,31 @@ fn message_to_agent() {
     assert_eq!("message\n", read_all(stderr2));
 }
 
+#[test]
+fn multiplexed_message_to_agent() {
+    let tmp = TmpDir::new();
+    let stderr = tmp.file("stderr.txt");
+
+    let r = run([
+        "--separator=@",
+        "--multiplex",
+        "-d=1",
+        "--@",
+        "--controller",
+        APP,
+        "1W#\n1#message\n",
+        "--@",
+        format!("--err={}", stderr).as_str(),
+        "--in=*0.stdout",
+        APP,
+        "pipe_loop",
+    ])
+    .unwrap();
+
+    ensure_ok(&r[0]);
+    assert_eq!("message\n", read_all(stderr));
+}
+
 #[test]
 fn message_from_agent() {
     let tmp = TmpDir::new();
@@ -271,6 +296,39 @@ fn agent_idle_time_limit() {
     ensure_idle_time_limit_exceeded(&r[1]);
 }
 
+#[test]
+fn suspend_agent_children() {
+    let tmp = TmpDir::new();
+    let file = tmp.file("file.txt");
+    let r = run([
+        "--separator=@",
+        "-d=2",
+        "--@",
+        "--controller",
+        "--in=*1.stdout",
+        "--out=*1.stdin",
+        APP,
+        "1W#\n",
+        "sleep",
+        "1",
+        "1S#\n",
+        "--@",
+        APP,
+        "exec_rest_and_sleep",
+        APP,
+        "message\n",
+        "sleep",
+        "0.3",
+        "fwrite",
+        &file,
+        "1",
+    ])
+    .unwrap();
+    // The child is suspended along with the agent once the message is sent.
+    ensure_terminated_by_controller(&r[1]);
+    assert!(read_all(file).is_empty());
+}
+
 fn agent_time_usage(sleep_kind: &str) -> Vec<Report> {
     run(&[
         "--separator=@",
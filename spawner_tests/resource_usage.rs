@@ -1,6 +1,7 @@
 use crate::assert_approx_eq;
 use crate::common::{TmpDir, APP, MEM_ERR, TIME_ERR};
 
+use spawner::process::CpuCounter;
 use spawner_driver::run;
 
 fn total_user_time(arg: &str) {
@@ -163,3 +164,63 @@ fn memory_usage_mi_1ms() {
 fn memory_usage_mi_1s() {
     memory_usage("-mi=1s");
 }
+
+#[cfg(unix)]
+#[test]
+fn memory_usage_without_kernel_memory() {
+    // Inodes and dentries of the files are kernel memory, the directory blocks in the page
+    // cache aren't.
+    let tmp = TmpDir::new();
+    let args = |kmem| {
+        run([
+            kmem,
+            APP,
+            "alloc",
+            "8",
+            "create_files",
+            tmp.file(kmem).as_str(),
+            "10000",
+        ])
+        .unwrap()
+    };
+    let with_kmem = args("-kmem=1");
+    let without_kmem = args("-kmem=0");
+    assert!(with_kmem[0].result.memory > without_kmem[0].result.memory + 4 * MEM_ERR);
+    assert_approx_eq!(without_kmem[0].result.memory, 8 * 1024 * 1024, MEM_ERR * 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn peak_rss() {
+    let r = run([APP, "alloc", "16"]).unwrap();
+    let rusage = r[0].rusage.unwrap();
+    assert_approx_eq!(rusage.max_rss, 16 * 1024 * 1024, MEM_ERR * 2);
+    assert!(rusage.minor_page_faults > 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn cpu_counter() {
+    let r = run(["-perf=1", APP, "loop", "0.2"]).unwrap();
+    match r[0].result.cpu_counter.unwrap() {
+        CpuCounter::Instructions(n) => assert!(n > 0),
+        CpuCounter::TaskClock(t) => assert_approx_eq!(
+            t.as_secs_f64(),
+            r[0].result.time + r[0].result.kernel_time,
+            TIME_ERR
+        ),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn cpu_quota() {
+    let r = run(["-cpu-quota=50%", "-d=1", APP, "loop", "2"]).unwrap();
+    assert_approx_eq!(r[0].result.time, 0.5, TIME_ERR);
+}
+
+#[test]
+fn wall_clock_time_mi_1s() {
+    let r = run(["-mi=1s", APP, "sleep", "0.2"]).unwrap();
+    assert_approx_eq!(r[0].result.wall_clock_time, 0.2, TIME_ERR);
+}
@@ -27,6 +27,11 @@ pub fn ensure_write_limit_exceeded(report: &Report) {
     assert!(report.result.bytes_written >= report.limit.io_bytes.unwrap());
 }
 
+pub fn ensure_read_limit_exceeded(report: &Report) {
+    check_tr(report, TerminateReason::ReadLimitExceeded);
+    assert!(report.result.bytes_read >= report.limit.io_read_bytes.unwrap());
+}
+
 pub fn ensure_process_limit_exceeded(report: &Report) {
     check_tr(report, TerminateReason::ProcessesCountLimitExceeded);
 }
@@ -52,6 +57,12 @@ pub fn ensure_abnormal_exit(report: &Report) {
     check_tr(report, TerminateReason::AbnormalExitProcess);
 }
 
+pub fn ensure_security_violation(report: &Report, syscall: &str) {
+    check_tr(report, TerminateReason::SecurityViolation);
+    let violation = report.security_violation.unwrap();
+    assert_eq!(violation.syscall_name, Some(syscall));
+}
+
 pub fn ensure_active_connection_count_limit_exceeded(report: &Report) {
     check_tr(report, TerminateReason::ActiveConnectionCountLimitExceeded);
 }
@@ -62,12 +73,158 @@ fn mem_limit() {
     ensure_mem_limit_exceeded(&r[0]);
 }
 
+#[cfg(unix)]
+#[test]
+fn mem_limit_page_cache() {
+    // The page cache is reclaimed once the limit is hit, nothing gets killed.
+    let tmp = TmpDir::new();
+    let r = run([
+        "-ml=10",
+        APP,
+        "fwrite",
+        tmp.file("file.txt").as_str(),
+        format!("{}", 40 * 1024).as_str(),
+    ])
+    .unwrap();
+    check_tr(&r[0], TerminateReason::ExitProcess);
+    assert!(r[0].result.memory_limit_hits > 0);
+    assert_eq!(r[0].result.oom_kills, 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn mem_limit_without_swap() {
+    use crate::common::read_all;
+    use std::path::Path;
+
+    let r = run(["-d=3", "-swap=0", "-mh=5", "-ml=10", APP, "alloc", "10"]).unwrap();
+    ensure_mem_limit_exceeded(&r[0]);
+    assert!(!r[0].limit.swap);
+    assert_eq!(r[0].limit.memory_high, Some(5 * 1024 * 1024));
+
+    // The group can't use swap and is throttled above `-mh`.
+    let tmp = TmpDir::new();
+    let out = tmp.file("out.txt");
+    let limits = |args: &[&str]| {
+        run(args.iter().cloned().chain(vec![
+            "-mh=5",
+            "-ml=10",
+            format!("--out={}", out).as_str(),
+            APP,
+            "print_memory_limits",
+        ]))
+        .unwrap();
+        read_all(&out)
+    };
+    assert_eq!(limits(&["-swap=0"]), "0 5242880");
+    assert_ne!(limits(&["-swap=1"]), "0 5242880");
+
+    // The legacy hierarchy limits memory and swap combined.
+    let r = run(["-swap=0", APP, "alloc", "1"]).unwrap();
+    assert_eq!(
+        r[0].spawner_error.is_empty(),
+        Path::new("/sys/fs/cgroup/cgroup.controllers").exists()
+    );
+}
+
+#[test]
+fn mem_limit_rss() {
+    // The peak RSS is polled, so it may exceed the limit by more than the OS limit does.
+    let r = run(["-d=3", "-mm=rss", "-ml=10", APP, "alloc", "10"]).unwrap();
+    check_tr(&r[0], TerminateReason::MemoryLimitExceeded);
+    assert!(r[0].result.memory >= r[0].limit.memory.unwrap());
+}
+
 #[test]
 fn user_time_limit() {
     let r = run(&["-tl=0.2", APP, "loop", "1"]).unwrap();
     ensure_user_time_limit_exceeded(&r[0]);
 }
 
+#[test]
+fn scaled_user_time_limit() {
+    let r = run(["-time-scale=2", "-tl=0.4", APP, "loop", "1"]).unwrap();
+    ensure_user_time_limit_exceeded(&r[0]);
+    assert_approx_eq!(r[0].result.wall_clock_time, 0.2, TIME_ERR);
+}
+
+#[test]
+fn user_time_limit_mi_1s() {
+    let r = run(["-tl=0.2", "-mi=1s", APP, "loop", "1"]).unwrap();
+    ensure_user_time_limit_exceeded(&r[0]);
+}
+
+#[cfg(unix)]
+#[test]
+fn graceful_termination() {
+    let script = "trap 'exit 3' TERM; while :; do :; done";
+    let r = run(["-tl=0.2", "-term-signal=term", "-c", "sh", "-c", script]).unwrap();
+    ensure_user_time_limit_exceeded(&r[0]);
+    assert_eq!(r[0].exit_code, 3);
+    assert_eq!(r[0].result.exited_within_grace_period, Some(true));
+}
+
+#[cfg(unix)]
+#[test]
+fn grace_period_expired() {
+    let script = "trap '' TERM; while :; do :; done";
+    let r = run([
+        "-tl=0.2",
+        "-term-signal=term",
+        "-grace-period=0.2",
+        "-c",
+        "sh",
+        "-c",
+        script,
+    ])
+    .unwrap();
+    check_tr(&r[0], TerminateReason::TimeLimitExceeded);
+    assert_eq!(r[0].result.exited_within_grace_period, Some(false));
+    assert_approx_eq!(r[0].result.wall_clock_time, 0.4, TIME_ERR);
+}
+
+#[cfg(unix)]
+#[test]
+fn interrupted() {
+    use crate::common::{read_all, SP};
+    use std::process::Command;
+    use std::thread;
+    use std::time::Duration;
+
+    let tmp = TmpDir::new();
+    let report = tmp.file("report.json");
+    let mut sp = Command::new(SP)
+        .args([&format!("-sr={}", report), "--json", APP, "sleep", "10"])
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(500));
+    unsafe {
+        libc::kill(sp.id() as libc::pid_t, libc::SIGTERM);
+    }
+    assert!(sp.wait().unwrap().success());
+
+    let r = json::parse(&read_all(&report)).unwrap();
+    assert_eq!(r[0]["TerminateReason"], "Interrupted");
+    assert!(r[0]["Result"]["WallClockTime"].as_f64().unwrap() < 5.0);
+}
+
+#[cfg(unix)]
+#[test]
+fn instruction_limit() {
+    use spawner::process::CpuCounter;
+
+    let r = run(["-perf=1", APP, "exit", "0"]).unwrap();
+    let hardware_counters = matches!(r[0].result.cpu_counter, Some(CpuCounter::Instructions(_)));
+    let r = run(["-il=100000000", APP, "loop", "1"]).unwrap();
+    if hardware_counters {
+        check_tr(&r[0], TerminateReason::InstructionLimitExceeded);
+        assert!(r[0].result.cpu_counter.unwrap().value() >= 100_000_000);
+    } else {
+        // The task clock isn't taken for instructions.
+        assert!(!r[0].spawner_error.is_empty());
+    }
+}
+
 #[test]
 fn write_limit() {
     let tmp = TmpDir::new();
@@ -82,6 +239,57 @@ fn write_limit() {
     ensure_write_limit_exceeded(&r[0]);
 }
 
+#[test]
+fn read_limit() {
+    let tmp = TmpDir::new();
+    let file = tmp.file("file.txt");
+    let size = format!("{}", 20 * 1024);
+    let r = run(["-rl=10", APP, "fwrite", &file, &size, "fread", &file]).unwrap();
+    ensure_read_limit_exceeded(&r[0]);
+}
+
+#[cfg(unix)]
+#[test]
+fn disk_bandwidth() {
+    let tmp = TmpDir::new();
+    let file = tmp.file("file.txt");
+    let r = run([
+        "-rbw=1", "-wbw=1", APP, "fwrite", &file, "1024", "fread", &file,
+    ])
+    .unwrap();
+    ensure_ok(&r[0]);
+    assert_eq!(r[0].limit.disk_read_bandwidth, Some(1024 * 1024));
+}
+
+#[cfg(unix)]
+#[test]
+fn disk_bandwidth_throttling() {
+    // Reads that miss the page cache are charged to the group and delayed.
+    let tmp = TmpDir::new();
+    let file = tmp.file("file.txt");
+    run([APP, "fwrite", &file, "3072"]).unwrap();
+    let r = run(["-rbw=1", APP, "fread_uncached", &file]).unwrap();
+    ensure_ok(&r[0]);
+    assert!(r[0].result.disk_bytes_read >= 3 * 1024 * 1024);
+    assert!(r[0].result.disk_reads > 0);
+    assert!(r[0].result.wall_clock_time > 2.0);
+}
+
+#[cfg(unix)]
+#[test]
+fn file_size_rlimit() {
+    let tmp = TmpDir::new();
+    let r = run([
+        "-rlimit=fsize:1M",
+        APP,
+        "fwrite",
+        tmp.file("file.txt").as_str(),
+        format!("{}", 2 * 1024).as_str(),
+    ])
+    .unwrap();
+    check_tr(&r[0], TerminateReason::WriteLimitExceeded);
+}
+
 #[test]
 fn null_stdout_write_limit() {
     let r = run(&[
@@ -142,6 +350,12 @@ fn wall_clock_time_limit_using_sleep() {
     ensure_wall_clock_time_limit_exceeded(&r[0]);
 }
 
+#[test]
+fn wall_clock_time_limit_mi_1s() {
+    let r = run(["-d=0.2", "-mi=1s", APP, "sleep", "1"]).unwrap();
+    ensure_wall_clock_time_limit_exceeded(&r[0]);
+}
+
 #[test]
 fn wall_clock_time_limit_using_loop() {
     let r = run(&["-d=0.2", APP, "loop", "1"]).unwrap();
@@ -154,6 +368,16 @@ fn abnormal_exit() {
     ensure_abnormal_exit(&r[0]);
 }
 
+#[cfg(unix)]
+#[test]
+fn crash_signal() {
+    let r = run(["-c", "sh", "-c", "kill -FPE $$"]).unwrap();
+    ensure_abnormal_exit(&r[0]);
+    assert_eq!(r[0].crash.as_ref().unwrap().signal, Some(8));
+    assert_eq!(r[0].to_json()["Crash"]["Signal"], 8);
+    assert!(r[0].to_json()["Crash"]["FaultCode"].is_null());
+}
+
 #[test]
 fn close_stdout_on_exit() {
     // if stdout writer does not close stdout on exit then the reader will hang on stdin().read(...).